@@ -1,12 +1,19 @@
-use std::path::Path;
-
 use proto::frontend::{RenameAction, Signal as FrontendSignal, SignalAction, UploadAction};
 use sysinfo::{Pid, Signal};
 use tokio::fs;
 
-use crate::client::BackendContext;
+use crate::{
+    client::BackendContext,
+    policy::{self, Access},
+};
 
 pub fn process_signal(mut ctx: BackendContext, action: SignalAction) {
+    // Processes of other users can only be signalled as root
+    if let Some(helper) = &ctx.helper {
+        helper.signal(action.pid, action.signal);
+        return;
+    }
+
     let sys = &mut ctx.system();
 
     let signal = match action.signal {
@@ -23,29 +30,49 @@ pub fn process_signal(mut ctx: BackendContext, action: SignalAction) {
     proc.kill_with(signal);
 }
 
-pub async fn new_file(path: String) {
-    let path = Path::new(&path);
+// Paths are checked against the file browser policy here, so the UI isn't the only thing keeping them in bounds
+pub async fn new_file(ctx: BackendContext, path: String) {
+    let Some(path) = policy::check_path(&ctx.config, &path, Access::Write) else {
+        return;
+    };
     if !path.exists() {
         let _ = fs::write(path, []).await;
     }
 }
 
-pub async fn new_folder(path: String) {
+pub async fn new_folder(ctx: BackendContext, path: String) {
+    let Some(path) = policy::check_path(&ctx.config, &path, Access::Write) else {
+        return;
+    };
     let _ = fs::create_dir(path).await;
 }
 
-pub async fn rename(action: RenameAction) {
-    let _ = fs::rename(action.from, action.to).await;
+pub async fn rename(ctx: BackendContext, action: RenameAction) {
+    let from = policy::check_path(&ctx.config, &action.from, Access::Entry);
+    let to = policy::check_path(&ctx.config, &action.to, Access::Entry);
+    let (Some(from), Some(to)) = (from, to) else {
+        return;
+    };
+    let _ = fs::rename(from, to).await;
 }
 
-pub async fn delete_file(path: String) {
+pub async fn delete_file(ctx: BackendContext, path: String) {
+    let Some(path) = policy::check_path(&ctx.config, &path, Access::Entry) else {
+        return;
+    };
     let _ = fs::remove_file(path).await;
 }
 
-pub async fn delete_folder(path: String) {
+pub async fn delete_folder(ctx: BackendContext, path: String) {
+    let Some(path) = policy::check_recursive_delete(&ctx.config, &path) else {
+        return;
+    };
     let _ = fs::remove_dir_all(path).await;
 }
 
-pub async fn write(action: UploadAction) {
-    let _ = fs::write(action.path, action.data).await;
+pub async fn write(ctx: BackendContext, action: UploadAction) {
+    let Some(path) = policy::check_path(&ctx.config, &action.path, Access::Write) else {
+        return;
+    };
+    let _ = fs::write(path, action.data).await;
 }
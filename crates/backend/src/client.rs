@@ -1,16 +1,24 @@
-use std::sync::{Arc, Mutex};
+use std::{
+    sync::{Arc, Mutex},
+    time::{Duration, Instant, SystemTime},
+};
 
 use anyhow::{Context, Result};
 use config::PROTOCOL_VERSION;
 use proto::{
     DashboardSocket,
-    backend::{ActionBackendMessage, BackendMessage, Handshake, ResponseBackendMessage},
+    backend::{ActionBackendMessage, BackendMessage, BoardInfo, Handshake, ResponseBackendMessage},
     frontend::{ActionFrontendMessage, FrontendMessage, RequestFrontendMessage},
 };
 use sysinfo::{Components, Disks, Networks, System};
 use tokio::{fs, net::TcpStream, sync::mpsc};
 
-use crate::{SharedConfig, actions, getters};
+use crate::{
+    SharedConfig, actions,
+    getters::{self, DiskStats, HistorySampler, SwapStats},
+    jobs,
+    privileged::SharedHelper,
+};
 
 macro_rules! getters {
     ($req:expr, $ctx:expr, {
@@ -32,6 +40,22 @@ pub struct SystemComponents {
     pub components: Components,
     pub disks: Disks,
     pub networks: Networks,
+    // Used to turn the network counters into rates
+    pub networks_refreshed: Instant,
+    pub networks_interval: Duration,
+    // The last two reads of /proc/diskstats, which rates are worked out between
+    pub disk_stats: Option<DiskStats>,
+    pub prev_disk_stats: Option<DiskStats>,
+    // Same as above, for swap activity from /proc/vmstat
+    pub swap_stats: Option<SwapStats>,
+    pub prev_swap_stats: Option<SwapStats>,
+    pub history: HistorySampler,
+    // Board details that don't change while running, read on the first Host request
+    pub board: Option<Option<BoardInfo>>,
+    // When dpkg's database was last changed, and how many packages it had then
+    pub packages: Option<(SystemTime, usize)>,
+    // So an unknown primary_sensor is only reported once
+    pub warned_sensor: bool,
 }
 
 impl SystemComponents {
@@ -41,6 +65,16 @@ impl SystemComponents {
             components: Components::new_with_refreshed_list(),
             disks: Disks::new_with_refreshed_list(),
             networks: Networks::new_with_refreshed_list(),
+            networks_refreshed: Instant::now(),
+            networks_interval: Duration::ZERO,
+            disk_stats: None,
+            prev_disk_stats: None,
+            swap_stats: None,
+            prev_swap_stats: None,
+            history: HistorySampler::new(),
+            board: None,
+            packages: None,
+            warned_sensor: false,
         }
     }
 }
@@ -51,6 +85,8 @@ pub struct BackendContext {
     pub system: SharedSystem,
     pub socket_tx: mpsc::UnboundedSender<BackendMessage>,
     pub term_tx: mpsc::UnboundedSender<ActionFrontendMessage>,
+    // Set when privileges were dropped, for the few things that still need root
+    pub helper: Option<SharedHelper>,
 }
 
 impl BackendContext {
@@ -113,6 +149,7 @@ impl<'a> BackendClient<'a> {
         let handshake = Handshake {
             nickname,
             update,
+            tags: self.context.config.tags.clone(),
             version: PROTOCOL_VERSION,
         };
 
@@ -147,7 +184,9 @@ impl RequestHandler {
                     Mem => getters::memory,
                     Disk => getters::disks,
                     NetIO => getters::network_io,
+                    DiskIO => getters::disk_io,
                     Processes => getters::processes,
+                    ProcessDetail(req) => getters::process_detail,
                     Host => getters::host,
                     Software => getters::software,
                     Command(action) => getters::command,
@@ -155,6 +194,7 @@ impl RequestHandler {
                     Directory(path) => getters::list_directory,
                     Download(path) => getters::read_file,
                     ReadConfig => getters::read_config,
+                    HistorySample => getters::history_sample,
                 });
 
                 let resp = BackendMessage::Response(id, resp);
@@ -170,12 +210,15 @@ impl RequestHandler {
                         .await
                         .unwrap()
                 }
-                ActionFrontendMessage::NewFile(path) => actions::new_file(path).await,
-                ActionFrontendMessage::NewFolder(path) => actions::new_folder(path).await,
-                ActionFrontendMessage::Rename(action) => actions::rename(action).await,
-                ActionFrontendMessage::DeleteFile(path) => actions::delete_file(path).await,
-                ActionFrontendMessage::DeleteFolder(path) => actions::delete_folder(path).await,
-                ActionFrontendMessage::Upload(action) => actions::write(action).await,
+                ActionFrontendMessage::NewFile(path) => actions::new_file(ctx, path).await,
+                ActionFrontendMessage::NewFolder(path) => actions::new_folder(ctx, path).await,
+                ActionFrontendMessage::Rename(action) => actions::rename(ctx, action).await,
+                ActionFrontendMessage::DeleteFile(path) => actions::delete_file(ctx, path).await,
+                ActionFrontendMessage::DeleteFolder(path) => {
+                    actions::delete_folder(ctx, path).await
+                }
+                ActionFrontendMessage::Upload(action) => actions::write(ctx, action).await,
+                ActionFrontendMessage::StartCommand(start) => jobs::run(ctx, start).await,
             },
         }
     }
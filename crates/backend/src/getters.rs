@@ -1,25 +1,92 @@
-use std::os::unix::fs::MetadataExt;
-use std::{fs, path::PathBuf, process::Command};
+use std::os::unix::{ffi::OsStrExt, fs::MetadataExt};
+use std::{
+    collections::{HashMap, HashSet},
+    ffi::CString,
+    fs,
+    net::{IpAddr, SocketAddr},
+    os::unix::process::ExitStatusExt,
+    path::{Path, PathBuf},
+    process::Command,
+    time::{Duration, Instant, SystemTime},
+};
 
+use log::warn;
 use mime_guess::mime;
 use proto::backend::FileKind;
 use proto::{
     backend::{
-        CommandResponse, CpuResponse, DirectoryItemInfo, DirectoryResponse, DiskInfo, DiskResponse,
-        HostResponse, MemResponse, NetworkResponse, ProcessInfo, ProcessResponse, ProcessStatus,
-        ServiceInfo, ServiceResponse, ServiceStatus, SoftwareInfo, SoftwareResponse, TempResponse,
-        UsageData,
+        BoardInfo, CommandResponse, CompressedMemory, CpuFrequency, CpuResponse, DeviceRate,
+        DirectoryItemInfo, DirectoryResponse, DiskIOInfo, DiskIOResponse, DiskInfo, DiskResponse,
+        HistorySampleResponse, HostResponse, ListeningSocket, LoadAverage, MemDetails, MemResponse,
+        NetworkInterface, NetworkResponse, Pressure, PressureAverages, PressureInfo, ProcessDetail,
+        ProcessInfo, ProcessResponse, ProcessStatus, ServiceInfo, ServiceResponse, ServiceStatus,
+        SoftwareInfo, SoftwareResponse, TempResponse, TempSensor, Throttling, UsageData,
     },
-    frontend::CommandAction,
+    frontend::{CommandAction, ProcessDetailRequest},
 };
-use sysinfo::{ProcessRefreshKind, ProcessesToUpdate, System, UpdateKind};
+use sysinfo::{Disk, Pid, ProcessRefreshKind, ProcessesToUpdate, System, UpdateKind};
 
-use crate::client::BackendContext;
+use crate::{
+    SharedConfig,
+    client::BackendContext,
+    policy::{self, Access},
+    privileged::{self, HelperOutput, Program},
+};
 
 fn round_to_2(num: f32) -> f32 {
     (num * 100.).round() / 100.
 }
 
+fn parse_pressure_line(line: &str) -> Option<PressureAverages> {
+    let mut fields = line
+        .split_whitespace()
+        .skip(1)
+        .filter_map(|x| x.split_once('='));
+    let mut avg = |name: &str| {
+        fields
+            .find(|(key, _)| *key == name)
+            .and_then(|(_, val)| val.parse().ok())
+    };
+
+    Some(PressureAverages {
+        avg10: avg("avg10")?,
+        avg60: avg("avg60")?,
+        avg300: avg("avg300")?,
+    })
+}
+
+fn read_pressure(resource: &str) -> Option<Pressure> {
+    let data = fs::read_to_string(format!("/proc/pressure/{resource}")).ok()?;
+    let line = |kind: &str| data.lines().find(|x| x.starts_with(kind));
+
+    Some(Pressure {
+        some: parse_pressure_line(line("some ")?)?,
+        full: line("full ").and_then(parse_pressure_line),
+    })
+}
+
+// cpufreq reports kHz
+fn read_cpu_freq(cpu: usize, name: &str) -> Option<u32> {
+    fs::read_to_string(format!("/sys/devices/system/cpu/cpu{cpu}/cpufreq/{name}"))
+        .ok()?
+        .trim()
+        .parse::<u32>()
+        .ok()
+        .map(|x| x / 1000)
+}
+
+fn cpu_frequencies(count: usize) -> Vec<CpuFrequency> {
+    (0..count)
+        .map_while(|cpu| {
+            Some(CpuFrequency {
+                current: read_cpu_freq(cpu, "scaling_cur_freq")?,
+                min: read_cpu_freq(cpu, "cpuinfo_min_freq")?,
+                max: read_cpu_freq(cpu, "cpuinfo_max_freq")?,
+            })
+        })
+        .collect()
+}
+
 pub fn cpu(mut ctx: BackendContext) -> CpuResponse {
     let sys = &mut ctx.system().system;
 
@@ -32,28 +99,149 @@ pub fn cpu(mut ctx: BackendContext) -> CpuResponse {
         .map(|x| round_to_2(x.cpu_usage()))
         .collect();
 
-    CpuResponse { global_cpu, cpus }
+    let load = System::load_average();
+    let load = LoadAverage {
+        one: round_to_2(load.one as f32),
+        five: round_to_2(load.five as f32),
+        fifteen: round_to_2(load.fifteen as f32),
+    };
+
+    let pressure = PressureInfo {
+        cpu: read_pressure("cpu"),
+        memory: read_pressure("memory"),
+        io: read_pressure("io"),
+    };
+
+    // Either every core has a frequency or none do, so they always line up with cpus
+    let mut frequencies = cpu_frequencies(cpus.len());
+    if frequencies.len() != cpus.len() {
+        frequencies.clear();
+    }
+
+    CpuResponse {
+        global_cpu,
+        cpus,
+        load,
+        pressure,
+        frequencies,
+    }
 }
 
 pub fn temp(mut ctx: BackendContext) -> TempResponse {
-    let components = &mut ctx.system().components;
-    components.refresh(false);
-    let components = components.list();
+    let primary_sensor = ctx.config.primary_sensor.clone();
+
+    let mut system = ctx.system();
+    let system = &mut *system;
+    system.components.refresh(false);
+    let components = system.components.list();
 
     let known_sensor_names = ["coretemp Package", "tdie"];
 
-    let temp = components
+    let configured = (!primary_sensor.is_empty())
+        .then(|| {
+            components
+                .iter()
+                .position(|x| x.label().contains(&primary_sensor))
+        })
+        .flatten();
+
+    // A misspelled or vanished sensor shouldn't leave the dashboard without a temperature
+    if configured.is_none() && !primary_sensor.is_empty() && !system.warned_sensor {
+        warn!("primary_sensor \"{primary_sensor}\" wasn't found, falling back to auto-detection");
+        system.warned_sensor = true;
+    }
+
+    let primary = configured.or_else(|| {
+        components
+            .iter()
+            .position(|x| known_sensor_names.iter().any(|y| x.label().contains(y)))
+            .or((!components.is_empty()).then_some(0))
+    });
+
+    let sensors: Vec<_> = components
         .iter()
-        .find(|x| known_sensor_names.iter().any(|y| x.label().contains(y)))
-        .or_else(|| components.first())
-        .and_then(|x| x.temperature())
-        .map(round_to_2);
+        .enumerate()
+        .map(|(idx, x)| TempSensor {
+            label: x.label().to_string(),
+            temp: x.temperature().map(round_to_2),
+            max: x.max().map(round_to_2),
+            critical: x.critical().map(round_to_2),
+            primary: primary == Some(idx),
+        })
+        .collect();
+
+    let temp = primary.and_then(|idx| sensors[idx].temp);
 
-    TempResponse { temp }
+    TempResponse { temp, sensors }
+}
+
+pub struct SwapStats {
+    time: Instant,
+    pages_in: u64,
+    pages_out: u64,
+}
+
+fn read_swap_stats() -> Option<SwapStats> {
+    let data = fs::read_to_string("/proc/vmstat").ok()?;
+    let time = Instant::now();
+
+    let counter = |name: &str| {
+        data.lines()
+            .find_map(|x| x.strip_prefix(name)?.strip_prefix(' '))
+            .and_then(|x| x.parse().ok())
+    };
+
+    Some(SwapStats {
+        time,
+        pages_in: counter("pswpin")?,
+        pages_out: counter("pswpout")?,
+    })
+}
+
+// Values in /proc/meminfo are in kB
+fn parse_meminfo(data: &str) -> HashMap<&str, u64> {
+    data.lines()
+        .filter_map(|line| {
+            let (key, val) = line.split_once(':')?;
+            let val = val.trim().trim_end_matches(" kB").parse::<u64>().ok()?;
+            Some((key, val * 1024))
+        })
+        .collect()
+}
+
+// Summed over every zram device, from the fields described in the kernel's zram docs
+fn zram_usage() -> Option<CompressedMemory> {
+    let mut usage = None;
+
+    for entry in fs::read_dir("/sys/block").ok()?.flatten() {
+        if !entry.file_name().to_string_lossy().starts_with("zram") {
+            continue;
+        }
+
+        let Ok(stat) = fs::read_to_string(entry.path().join("mm_stat")) else {
+            continue;
+        };
+        let fields: Vec<u64> = stat
+            .split_whitespace()
+            .filter_map(|x| x.parse().ok())
+            .collect();
+        if let (Some(original), Some(used)) = (fields.first(), fields.get(2)) {
+            let usage = usage.get_or_insert(CompressedMemory {
+                original: 0,
+                compressed: 0,
+            });
+            usage.original += original;
+            usage.compressed += used;
+        }
+    }
+
+    usage
 }
 
 pub fn memory(mut ctx: BackendContext) -> MemResponse {
-    let sys = &mut ctx.system().system;
+    let mut system = ctx.system();
+    let system = &mut *system;
+    let sys = &mut system.system;
 
     // Refreshes both RAM and Swap
     sys.refresh_memory();
@@ -68,48 +256,533 @@ pub fn memory(mut ctx: BackendContext) -> MemResponse {
         total: sys.total_swap(),
     };
 
-    MemResponse { ram, swap }
+    let meminfo = fs::read_to_string("/proc/meminfo").unwrap_or_default();
+    let meminfo = parse_meminfo(&meminfo);
+    let field = |name: &str| meminfo.get(name).copied().unwrap_or_default();
+
+    let zswap_enabled =
+        fs::read_to_string("/sys/module/zswap/parameters/enabled").is_ok_and(|x| x.trim() == "Y");
+    let zswap = zswap_enabled.then(|| CompressedMemory {
+        original: field("Zswapped"),
+        compressed: field("Zswap"),
+    });
+
+    let stale = system
+        .swap_stats
+        .as_ref()
+        .is_none_or(|x| x.time.elapsed() >= MIN_RATE_INTERVAL);
+    if stale && let Some(stats) = read_swap_stats() {
+        system.prev_swap_stats = system.swap_stats.replace(stats);
+    }
+
+    let (swap_in, swap_out) = match (&system.swap_stats, &system.prev_swap_stats) {
+        (Some(current), Some(prev)) => {
+            let secs = current.time.duration_since(prev.time).as_secs_f64();
+            let page_size = page_size();
+            let rate = |current: u64, prev: u64| {
+                (current.saturating_sub(prev) as f64 * page_size as f64 / secs) as u64
+            };
+            (
+                rate(current.pages_in, prev.pages_in),
+                rate(current.pages_out, prev.pages_out),
+            )
+        }
+        _ => (0, 0),
+    };
+
+    let details = MemDetails {
+        free: field("MemFree"),
+        available: field("MemAvailable"),
+        buffers: field("Buffers"),
+        cached: field("Cached") + field("SReclaimable"),
+        shared: field("Shmem"),
+        dirty: field("Dirty"),
+        zram: zram_usage(),
+        zswap,
+        swap_in,
+        swap_out,
+    };
+
+    MemResponse { ram, swap, details }
+}
+
+fn page_size() -> u64 {
+    // SAFETY: sysconf has no preconditions
+    let size = unsafe { libc::sysconf(libc::_SC_PAGESIZE) };
+    if size > 0 { size as u64 } else { 4096 }
+}
+
+// Virtual filesystems that sysinfo doesn't already leave out
+const IGNORED_FILESYSTEMS: [&str; 6] = [
+    "tmpfs", "devtmpfs", "overlay", "squashfs", "ramfs", "efivarfs",
+];
+
+fn inode_usage(path: &Path) -> UsageData {
+    let Ok(path) = CString::new(path.as_os_str().as_bytes()) else {
+        return UsageData { used: 0, total: 0 };
+    };
+
+    // SAFETY: statvfs only writes to the struct, which is valid when zeroed
+    let mut stat: libc::statvfs = unsafe { std::mem::zeroed() };
+    if unsafe { libc::statvfs(path.as_ptr(), &mut stat) } != 0 {
+        return UsageData { used: 0, total: 0 };
+    }
+
+    // Some filesystems report more free inodes than total, which would otherwise underflow.
+    // fsfilcnt_t is only u64 on 64-bit targets, the armv6/armv7 builds still need the casts.
+    #[allow(clippy::unnecessary_cast)]
+    let (used, total) = (
+        stat.f_files.saturating_sub(stat.f_ffree) as u64,
+        stat.f_files as u64,
+    );
+
+    UsageData { used, total }
+}
+
+// Device paths like /dev/disk/by-uuid/... are symlinks, resolve them to the real device.
+// Other sources (tmpfs, overlay, network shares) aren't paths, so they're left alone
+fn resolve_device(name: &str) -> String {
+    if !name.starts_with("/dev/") {
+        return name.into();
+    }
+
+    fs::canonicalize(name)
+        .map(|x| x.to_string_lossy().into())
+        .unwrap_or_else(|_| name.into())
 }
 
 pub fn disks(mut ctx: BackendContext) -> DiskResponse {
     let mnt_points = &ctx.config.disks;
     let mnt_points: Vec<_> = mnt_points.iter().map(PathBuf::from).collect();
+    let discover = ctx.config.discover_disks;
 
     let disks = &mut ctx.system().disks;
-    disks.refresh(false);
+    // Drops drives that were unplugged, and picks up new ones
+    disks.refresh(true);
     let disks = disks.list();
 
+    let configured = |disk: &&Disk| mnt_points.iter().any(|path| path == disk.mount_point());
+
+    let mut seen = HashSet::new();
     let disks: Vec<_> = disks
         .iter()
-        .filter(|disk| mnt_points.iter().any(|path| path == disk.mount_point()))
-        .map(|disk| DiskInfo {
-            name: disk.name().to_str().unwrap_or("unknown").into(),
-            mnt_point: disk.mount_point().to_str().unwrap_or("unknown").into(),
-            usage: UsageData {
-                used: disk.total_space() - disk.available_space(),
-                total: disk.total_space(),
-            },
+        .filter(|disk| {
+            if configured(disk) {
+                return true;
+            }
+
+            // Bind mounts would show the same filesystem more than once, so only the first mount is kept
+            discover
+                && !IGNORED_FILESYSTEMS.iter().any(|x| disk.file_system() == *x)
+                && !disks
+                    .iter()
+                    .any(|x| x.name() == disk.name() && configured(&x))
+                && seen.insert(disk.name())
+        })
+        .map(|disk| {
+            let name = disk.name().to_str().unwrap_or("unknown");
+
+            DiskInfo {
+                name: name.into(),
+                mnt_point: disk.mount_point().to_str().unwrap_or("unknown").into(),
+                usage: UsageData {
+                    used: disk.total_space() - disk.available_space(),
+                    total: disk.total_space(),
+                },
+                fs_type: disk.file_system().to_string_lossy().into(),
+                device: resolve_device(name),
+                read_only: disk.is_read_only(),
+                removable: disk.is_removable(),
+                inodes: inode_usage(disk.mount_point()),
+            }
         })
         .collect();
 
     DiskResponse { disks }
 }
 
+// Refreshing more often than this makes the rates too noisy, so the previous ones are reused instead
+const MIN_RATE_INTERVAL: Duration = Duration::from_millis(500);
+
+// Link state and drops aren't covered by sysinfo
+fn read_net_stat(name: &str, stat: &str) -> Option<String> {
+    fs::read_to_string(format!("/sys/class/net/{name}/{stat}"))
+        .ok()
+        .map(|x| x.trim().to_string())
+}
+
+fn read_net_counter(name: &str, stat: &str) -> u64 {
+    read_net_stat(name, &format!("statistics/{stat}"))
+        .and_then(|x| x.parse().ok())
+        .unwrap_or_default()
+}
+
+fn interface_included(config: &SharedConfig, name: &str) -> bool {
+    let included = config
+        .network_interfaces
+        .iter()
+        .any(|x| policy::glob_match(x, name));
+    let ignored = config
+        .ignored_interfaces
+        .iter()
+        .any(|x| policy::glob_match(x, name));
+    included && !ignored
+}
+
 pub fn network_io(mut ctx: BackendContext) -> NetworkResponse {
-    let networks = &mut ctx.system().networks;
-    networks.refresh(false);
-    let networks = networks.list();
+    let config = ctx.config.clone();
+    let mut system = ctx.system();
+    let system = &mut *system;
+
+    let now = Instant::now();
+    if now.duration_since(system.networks_refreshed) >= MIN_RATE_INTERVAL {
+        system.networks.refresh(false);
+        system.networks_interval = now.duration_since(system.networks_refreshed);
+        system.networks_refreshed = now;
+    }
+
+    let secs = system.networks_interval.as_secs_f64();
+    let rate = |bytes: u64| {
+        if secs == 0. {
+            0
+        } else {
+            (bytes as f64 / secs) as u64
+        }
+    };
 
-    let mut resp = NetworkResponse { sent: 0, recv: 0 };
+    let mut resp = NetworkResponse {
+        sent: 0,
+        recv: 0,
+        total_sent: 0,
+        total_recv: 0,
+        interfaces: Vec::new(),
+    };
 
-    for net in networks.values() {
-        resp.recv += net.received();
-        resp.sent += net.transmitted();
+    for (name, net) in system.networks.list() {
+        if !interface_included(&config, name) {
+            continue;
+        }
+
+        let (ipv4, ipv6): (Vec<_>, Vec<_>) = net
+            .ip_networks()
+            .iter()
+            .map(|x| (x.addr.is_ipv4(), x.to_string()))
+            .partition(|x| x.0);
+
+        let interface = NetworkInterface {
+            name: name.clone(),
+            mac: net.mac_address().to_string(),
+            ipv4: ipv4.into_iter().map(|x| x.1).collect(),
+            ipv6: ipv6.into_iter().map(|x| x.1).collect(),
+            state: read_net_stat(name, "operstate").unwrap_or_else(|| "unknown".into()),
+            sent: rate(net.transmitted()),
+            recv: rate(net.received()),
+            total_sent: net.total_transmitted(),
+            total_recv: net.total_received(),
+            packets_sent: net.total_packets_transmitted(),
+            packets_recv: net.total_packets_received(),
+            errors_sent: net.total_errors_on_transmitted(),
+            errors_recv: net.total_errors_on_received(),
+            drops_sent: read_net_counter(name, "tx_dropped"),
+            drops_recv: read_net_counter(name, "rx_dropped"),
+        };
+
+        resp.sent += interface.sent;
+        resp.recv += interface.recv;
+        resp.total_sent += interface.total_sent;
+        resp.total_recv += interface.total_recv;
+        resp.interfaces.push(interface);
     }
 
+    resp.interfaces.sort_by(|a, b| a.name.cmp(&b.name));
+
     resp
 }
 
+// Counters for one block device from /proc/diskstats
+#[derive(Clone, Copy)]
+struct DiskCounters {
+    reads: u64,
+    sectors_read: u64,
+    writes: u64,
+    sectors_written: u64,
+    busy_ms: u64,
+}
+
+pub struct DiskStats {
+    time: Instant,
+    devices: HashMap<String, DiskCounters>,
+}
+
+// The kernel always counts these in 512 byte sectors, whatever the device uses
+const SECTOR_SIZE: u64 = 512;
+
+fn read_disk_stats() -> Option<DiskStats> {
+    let data = fs::read_to_string("/proc/diskstats").ok()?;
+    let time = Instant::now();
+
+    let devices = data
+        .lines()
+        .filter_map(|line| {
+            let fields: Vec<_> = line.split_whitespace().collect();
+            let name = *fields.get(2)?;
+
+            // Partitions are left out, since they'd be counted again in their disk.
+            // Loop and RAM devices are rarely interesting.
+            if !fs::exists(format!("/sys/block/{name}")).unwrap_or(false)
+                || name.starts_with("loop")
+                || name.starts_with("ram")
+            {
+                return None;
+            }
+
+            let field = |idx: usize| fields.get(idx).and_then(|x| x.parse::<u64>().ok());
+
+            let counters = DiskCounters {
+                reads: field(3)?,
+                sectors_read: field(5)?,
+                writes: field(7)?,
+                sectors_written: field(9)?,
+                busy_ms: field(12)?,
+            };
+
+            Some((name.to_string(), counters))
+        })
+        .collect();
+
+    Some(DiskStats { time, devices })
+}
+
+pub fn disk_io(mut ctx: BackendContext) -> DiskIOResponse {
+    let mut system = ctx.system();
+    let system = &mut *system;
+
+    let stale = system
+        .disk_stats
+        .as_ref()
+        .is_none_or(|x| x.time.elapsed() >= MIN_RATE_INTERVAL);
+    if stale && let Some(stats) = read_disk_stats() {
+        system.prev_disk_stats = system.disk_stats.replace(stats);
+    }
+
+    let Some(current) = &system.disk_stats else {
+        return DiskIOResponse {
+            devices: Vec::new(),
+        };
+    };
+
+    let prev = system.prev_disk_stats.as_ref();
+    let secs = prev.map_or(0., |x| current.time.duration_since(x.time).as_secs_f64());
+
+    let mut devices: Vec<_> = current
+        .devices
+        .iter()
+        .map(|(name, counters)| {
+            let last = prev.and_then(|x| x.devices.get(name)).filter(|_| secs > 0.);
+            // Counters can only go backwards if the device was removed and added again
+            let rate = |f: fn(&DiskCounters) -> u64| {
+                last.map_or(0., |last| f(counters).saturating_sub(f(last)) as f64 / secs)
+            };
+
+            DiskIOInfo {
+                name: name.clone(),
+                read_bytes: (rate(|x| x.sectors_read) * SECTOR_SIZE as f64) as u64,
+                write_bytes: (rate(|x| x.sectors_written) * SECTOR_SIZE as f64) as u64,
+                read_ops: round_to_2(rate(|x| x.reads) as f32),
+                write_ops: round_to_2(rate(|x| x.writes) as f32),
+                busy: round_to_2((rate(|x| x.busy_ms) / 10.).min(100.) as f32),
+                total_read: counters.sectors_read * SECTOR_SIZE,
+                total_written: counters.sectors_written * SECTOR_SIZE,
+            }
+        })
+        .collect();
+
+    devices.sort_by(|a, b| a.name.cmp(&b.name));
+
+    DiskIOResponse { devices }
+}
+
+// Bytes sent and received by each included interface, counted since boot
+pub struct NetCounters {
+    time: Instant,
+    interfaces: HashMap<String, (u64, u64)>,
+}
+
+fn read_net_counters(config: &SharedConfig) -> NetCounters {
+    let time = Instant::now();
+
+    let interfaces = fs::read_dir("/sys/class/net")
+        .into_iter()
+        .flatten()
+        .flatten()
+        .filter_map(|entry| entry.file_name().into_string().ok())
+        .filter(|name| interface_included(config, name))
+        .map(|name| {
+            let counters = (
+                read_net_counter(&name, "tx_bytes"),
+                read_net_counter(&name, "rx_bytes"),
+            );
+            (name, counters)
+        })
+        .collect();
+
+    NetCounters { time, interfaces }
+}
+
+// History samples keep their own counters, so they don't shorten the intervals the pages work out rates over
+pub struct HistorySampler {
+    system: System,
+    net: Option<NetCounters>,
+    disk: Option<DiskStats>,
+}
+
+impl HistorySampler {
+    pub fn new() -> Self {
+        Self {
+            system: System::new(),
+            net: None,
+            disk: None,
+        }
+    }
+}
+
+// Per second rate between two readings of a counter, or 0 if there's nothing to compare against
+fn counter_rate(current: u64, prev: Option<u64>, secs: f64) -> u64 {
+    match prev {
+        Some(prev) if secs > 0. => (current.saturating_sub(prev) as f64 / secs) as u64,
+        _ => 0,
+    }
+}
+
+pub fn history_sample(mut ctx: BackendContext) -> HistorySampleResponse {
+    // Reading sensors doesn't affect anything else
+    let temp = temp(ctx.clone()).temp;
+
+    let config = ctx.config.clone();
+    let mut system = ctx.system();
+    let sampler = &mut system.history;
+
+    // Averaged since the previous sample, rather than since whatever refreshed last
+    sampler.system.refresh_cpu_usage();
+    sampler.system.refresh_memory();
+
+    let meminfo = fs::read_to_string("/proc/meminfo").unwrap_or_default();
+    let meminfo = parse_meminfo(&meminfo);
+    let field = |name: &str| meminfo.get(name).copied().unwrap_or_default();
+
+    let net = read_net_counters(&config);
+    let prev_net = sampler.net.replace(net);
+    let net = sampler.net.as_ref().unwrap();
+    let secs = prev_net
+        .as_ref()
+        .map_or(0., |x| net.time.duration_since(x.time).as_secs_f64());
+
+    let mut interfaces: Vec<_> = net
+        .interfaces
+        .iter()
+        .map(|(name, &(sent, recv))| {
+            let prev = prev_net.as_ref().and_then(|x| x.interfaces.get(name));
+            DeviceRate {
+                name: name.clone(),
+                sent: counter_rate(sent, prev.map(|x| x.0), secs),
+                recv: counter_rate(recv, prev.map(|x| x.1), secs),
+            }
+        })
+        .collect();
+    interfaces.sort_by(|a, b| a.name.cmp(&b.name));
+
+    let mut disks = Vec::new();
+    if let Some(stats) = read_disk_stats() {
+        let prev_disk = sampler.disk.replace(stats);
+        let disk = sampler.disk.as_ref().unwrap();
+        let secs = prev_disk
+            .as_ref()
+            .map_or(0., |x| disk.time.duration_since(x.time).as_secs_f64());
+
+        disks = disk
+            .devices
+            .iter()
+            .map(|(name, counters)| {
+                let prev = prev_disk.as_ref().and_then(|x| x.devices.get(name));
+                DeviceRate {
+                    name: name.clone(),
+                    sent: counter_rate(
+                        counters.sectors_written * SECTOR_SIZE,
+                        prev.map(|x| x.sectors_written * SECTOR_SIZE),
+                        secs,
+                    ),
+                    recv: counter_rate(
+                        counters.sectors_read * SECTOR_SIZE,
+                        prev.map(|x| x.sectors_read * SECTOR_SIZE),
+                        secs,
+                    ),
+                }
+            })
+            .collect();
+        disks.sort_by(|a, b| a.name.cmp(&b.name));
+    }
+
+    let sys = &sampler.system;
+
+    HistorySampleResponse {
+        cpu: round_to_2(sys.global_cpu_usage()),
+        temp,
+        ram: UsageData {
+            used: sys.used_memory(),
+            total: sys.total_memory(),
+        },
+        swap: UsageData {
+            used: sys.used_swap(),
+            total: sys.total_swap(),
+        },
+        free: field("MemFree"),
+        buffers: field("Buffers"),
+        cached: field("Cached") + field("SReclaimable"),
+        sent: interfaces.iter().map(|x| x.sent).sum(),
+        recv: interfaces.iter().map(|x| x.recv).sum(),
+        interfaces,
+        disks,
+    }
+}
+
+// Works for both /etc/passwd and /etc/group, which keep the name first and the ID third
+fn read_id_names(file: &str) -> HashMap<u32, String> {
+    let Ok(data) = fs::read_to_string(file) else {
+        return HashMap::new();
+    };
+    data.lines()
+        .filter_map(|line| {
+            let mut fields = line.split(':');
+            let name = fields.next()?;
+            let id = fields.nth(1)?.parse().ok()?;
+            Some((id, name.to_string()))
+        })
+        .collect()
+}
+
+fn process_status(status: sysinfo::ProcessStatus) -> ProcessStatus {
+    match status {
+        sysinfo::ProcessStatus::Run => ProcessStatus::Running,
+        sysinfo::ProcessStatus::Sleep => ProcessStatus::Sleeping,
+        sysinfo::ProcessStatus::Stop => ProcessStatus::Paused,
+        _ => ProcessStatus::Other,
+    }
+}
+
+// Set in the flags of every kernel thread, see include/linux/sched.h
+const PF_KTHREAD: u64 = 0x00200000;
+
+// Same check as ps, which reads the flags field from /proc/<pid>/stat
+fn is_kernel_thread(pid: u32) -> bool {
+    let Ok(data) = fs::read_to_string(format!("/proc/{pid}/stat")) else {
+        return false;
+    };
+    // The name can contain spaces and parentheses, so fields are counted after the last ')'
+    data.rsplit_once(')')
+        .and_then(|(_, x)| x.split_whitespace().nth(6)?.parse::<u64>().ok())
+        .is_some_and(|flags| flags & PF_KTHREAD != 0)
+}
+
 pub fn processes(mut ctx: BackendContext) -> ProcessResponse {
     let sys = &mut ctx.system().system;
 
@@ -119,41 +792,325 @@ pub fn processes(mut ctx: BackendContext) -> ProcessResponse {
         ProcessRefreshKind::nothing()
             .with_cpu()
             .with_memory()
-            .with_cmd(UpdateKind::OnlyIfNotSet),
+            .with_cmd(UpdateKind::OnlyIfNotSet)
+            .with_user(UpdateKind::OnlyIfNotSet),
     );
 
+    let users = read_id_names("/etc/passwd");
+
     let processes = sys
         .processes()
         .iter()
-        .filter(|(_, proc)| !proc.cmd().is_empty())
         .map(|(pid, proc)| ProcessInfo {
             pid: pid.as_u32(),
             name: proc.name().to_string_lossy().into(),
             cpu: round_to_2(proc.cpu_usage()),
             mem: proc.memory(),
-            status: match proc.status() {
-                sysinfo::ProcessStatus::Run => ProcessStatus::Running,
-                sysinfo::ProcessStatus::Sleep => ProcessStatus::Sleeping,
-                sysinfo::ProcessStatus::Stop => ProcessStatus::Paused,
-                _ => ProcessStatus::Other,
-            },
+            status: process_status(proc.status()),
+            cmd: proc
+                .cmd()
+                .iter()
+                .map(|x| x.to_string_lossy())
+                .collect::<Vec<_>>()
+                .join(" "),
+            parent: proc.parent().map(|x| x.as_u32()),
+            user: proc.user_id().map(|uid| {
+                let uid = **uid;
+                users.get(&uid).cloned().unwrap_or_else(|| uid.to_string())
+            }),
+            kernel_thread: is_kernel_thread(pid.as_u32()),
         })
         .collect();
 
     ProcessResponse { processes }
 }
 
-pub fn host(mut ctx: BackendContext) -> HostResponse {
-    let net = &ctx.system().networks;
+// Returns the thread count and nice value
+fn read_proc_stat(pid: u32) -> Option<(u32, i32)> {
+    let data = fs::read_to_string(format!("/proc/{pid}/stat")).ok()?;
+    // The name can contain spaces and parentheses, so fields are counted after the last ')'
+    let fields: Vec<_> = data.rsplit_once(')')?.1.split_whitespace().collect();
+    let nice = fields.get(16)?.parse().ok()?;
+    let threads = fields.get(17)?.parse().ok()?;
+    Some((threads, nice))
+}
+
+// Returns the number of open file descriptors, along with the inodes of any sockets among them
+fn read_fds(pid: u32) -> Option<(u32, HashSet<u64>)> {
+    let mut count = 0;
+    let mut sockets = HashSet::new();
+
+    for entry in fs::read_dir(format!("/proc/{pid}/fd")).ok()?.flatten() {
+        count += 1;
+        let Ok(target) = fs::read_link(entry.path()) else {
+            continue;
+        };
+        if let Some(inode) = target
+            .to_str()
+            .and_then(|x| x.strip_prefix("socket:["))
+            .and_then(|x| x.strip_suffix(']'))
+            .and_then(|x| x.parse().ok())
+        {
+            sockets.insert(inode);
+        }
+    }
+
+    Some((count, sockets))
+}
+
+// Addresses in /proc/net are hex, with the IP in host byte order and the port in network byte order
+fn parse_socket_addr(hex: &str) -> Option<SocketAddr> {
+    let (ip, port) = hex.split_once(':')?;
+    let port = u16::from_str_radix(port, 16).ok()?;
+
+    let ip = match ip.len() {
+        8 => IpAddr::from(u32::from_str_radix(ip, 16).ok()?.to_ne_bytes()),
+        32 => {
+            let mut bytes = [0; 16];
+            for (chunk, word) in bytes.chunks_exact_mut(4).zip(0..) {
+                let word = ip.get(word * 8..word * 8 + 8)?;
+                chunk.copy_from_slice(&u32::from_str_radix(word, 16).ok()?.to_ne_bytes());
+            }
+            IpAddr::from(bytes)
+        }
+        _ => return None,
+    };
+
+    Some(SocketAddr::new(ip, port))
+}
 
+fn listening_sockets(pid: u32, inodes: &HashSet<u64>) -> Vec<ListeningSocket> {
+    // TCP sockets are listening in state 0A, while bound UDP sockets sit in 07 as they aren't connected
+    let tables = [("tcp", "0A"), ("tcp6", "0A"), ("udp", "07"), ("udp6", "07")];
+    let mut sockets = Vec::new();
+
+    for (protocol, state) in tables {
+        // Read through the process, so sockets in its network namespace are found
+        let Ok(data) = fs::read_to_string(format!("/proc/{pid}/net/{protocol}")) else {
+            continue;
+        };
+        for line in data.lines().skip(1) {
+            let fields: Vec<_> = line.split_whitespace().collect();
+            if fields.len() < 10 || fields[3] != state {
+                continue;
+            }
+            let Some(inode) = fields[9].parse().ok() else {
+                continue;
+            };
+            if !inodes.contains(&inode) {
+                continue;
+            }
+            if let Some(addr) = parse_socket_addr(fields[1]) {
+                sockets.push(ListeningSocket {
+                    protocol: protocol.into(),
+                    addr: addr.to_string(),
+                });
+            }
+        }
+    }
+
+    sockets
+}
+
+fn read_cgroup(pid: u32) -> Option<String> {
+    let data = fs::read_to_string(format!("/proc/{pid}/cgroup")).ok()?;
+    // Prefer the unified hierarchy, falling back to the first v1 controller
+    data.lines()
+        .find_map(|line| line.strip_prefix("0::"))
+        .or_else(|| data.lines().next()?.splitn(3, ':').nth(2))
+        .map(String::from)
+}
+
+fn read_environment(pid: u32) -> Option<Vec<String>> {
+    let data = fs::read(format!("/proc/{pid}/environ")).ok()?;
+    Some(
+        data.split(|&x| x == 0)
+            .filter(|x| !x.is_empty())
+            .map(|x| String::from_utf8_lossy(x).into_owned())
+            .collect(),
+    )
+}
+
+pub fn process_detail(
+    mut ctx: BackendContext,
+    req: ProcessDetailRequest,
+) -> Option<Box<ProcessDetail>> {
+    let sys = &mut ctx.system().system;
+    let pid = Pid::from_u32(req.pid);
+
+    sys.refresh_processes_specifics(
+        ProcessesToUpdate::Some(&[pid]),
+        true,
+        ProcessRefreshKind::nothing()
+            .with_cpu()
+            .with_memory()
+            .with_cmd(UpdateKind::Always)
+            .with_exe(UpdateKind::Always)
+            .with_cwd(UpdateKind::Always)
+            .with_user(UpdateKind::Always),
+    );
+
+    let proc = sys.process(pid)?;
+
+    let uid = proc.user_id().map(|x| **x);
+    let gid = proc.group_id().map(|x| *x);
+    let (threads, nice) = read_proc_stat(req.pid).unzip();
+    let (open_fds, socket_inodes) = read_fds(req.pid).unzip();
+
+    Some(Box::new(ProcessDetail {
+        pid: req.pid,
+        name: proc.name().to_string_lossy().into(),
+        status: process_status(proc.status()),
+        cpu: round_to_2(proc.cpu_usage()),
+        mem: proc.memory(),
+        cmd: proc
+            .cmd()
+            .iter()
+            .map(|x| x.to_string_lossy().into_owned())
+            .collect(),
+        exe: proc.exe().map(|x| x.to_string_lossy().into_owned()),
+        cwd: proc.cwd().map(|x| x.to_string_lossy().into_owned()),
+        uid,
+        user: uid.and_then(|x| read_id_names("/etc/passwd").remove(&x)),
+        gid,
+        group: gid.and_then(|x| read_id_names("/etc/group").remove(&x)),
+        parent: proc.parent().map(|x| x.as_u32()),
+        start_time: proc.start_time(),
+        threads,
+        nice,
+        open_fds,
+        cgroup: read_cgroup(req.pid),
+        sockets: socket_inodes.map(|inodes| listening_sockets(req.pid, &inodes)),
+        environment: req.environment.then(|| read_environment(req.pid)).flatten(),
+    }))
+}
+
+// Device tree strings end with a NUL byte
+fn read_device_tree(name: &str) -> Option<String> {
+    let data = fs::read(format!("/proc/device-tree/{name}")).ok()?;
+    let data = String::from_utf8_lossy(&data);
+    let data = data.trim_end_matches('\0').trim();
+    (!data.is_empty()).then(|| data.to_string())
+}
+
+fn read_cpuinfo_field(cpuinfo: &str, name: &str) -> Option<String> {
+    cpuinfo.lines().find_map(|line| {
+        let (key, val) = line.split_once(':')?;
+        (key.trim() == name).then(|| val.trim().to_string())
+    })
+}
+
+fn vcgencmd(args: &[&str]) -> Option<String> {
+    let output = Command::new("vcgencmd").args(args).output().ok()?;
+    output
+        .status
+        .success()
+        .then(|| String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
+
+fn throttling() -> Option<Throttling> {
+    // Newer kernels expose it through sysfs, otherwise the firmware has to be asked
+    let bits = fs::read_to_string("/sys/devices/platform/soc/soc:firmware/get_throttled")
+        .ok()
+        .map(|x| x.trim().to_string())
+        .or_else(|| {
+            Some(
+                vcgencmd(&["get_throttled"])?
+                    .strip_prefix("throttled=")?
+                    .into(),
+            )
+        })?;
+    let bits = u32::from_str_radix(bits.trim_start_matches("0x"), 16).ok()?;
+
+    let bit = |n: u32| bits & (1 << n) != 0;
+
+    Some(Throttling {
+        under_voltage: bit(0),
+        freq_capped: bit(1),
+        throttled: bit(2),
+        soft_temp_limit: bit(3),
+        under_voltage_occurred: bit(16),
+        freq_capped_occurred: bit(17),
+        throttled_occurred: bit(18),
+        soft_temp_limit_occurred: bit(19),
+    })
+}
+
+// vcgencmd prints e.g. "gpu=76M"
+fn memory_split(kind: &str) -> Option<u64> {
+    let output = vcgencmd(&["get_mem", kind])?;
+    let megabytes = output
+        .strip_prefix(kind)?
+        .strip_prefix('=')?
+        .strip_suffix('M')?;
+    Some(megabytes.parse::<u64>().ok()? * 1024 * 1024)
+}
+
+// Everything but the throttling state, which is filled in on each request
+fn board() -> Option<BoardInfo> {
+    let cpuinfo = fs::read_to_string("/proc/cpuinfo").unwrap_or_default();
+
+    // DMI covers most x86 machines, which don't have a device tree
+    let model = read_device_tree("model").or_else(|| {
+        let product = fs::read_to_string("/sys/class/dmi/id/product_name").ok()?;
+        let vendor = fs::read_to_string("/sys/class/dmi/id/sys_vendor").unwrap_or_default();
+        let model = format!("{} {}", vendor.trim(), product.trim());
+        Some(model.trim().to_string()).filter(|x| !x.is_empty())
+    })?;
+
+    // Boards without one tend to report all zeros
+    let serial = read_device_tree("serial-number")
+        .or_else(|| read_cpuinfo_field(&cpuinfo, "Serial"))
+        .filter(|x| !x.trim_start_matches('0').is_empty());
+    let revision = read_cpuinfo_field(&cpuinfo, "Revision");
+
+    Some(BoardInfo {
+        model,
+        serial,
+        revision,
+        throttling: None,
+        arm_memory: memory_split("arm"),
+        gpu_memory: memory_split("gpu"),
+    })
+}
+
+// Only recounted when dpkg's database changes, since the fleet page asks for this often
+fn package_count(cached: &mut Option<(SystemTime, usize)>) -> usize {
+    let modified = fs::metadata("/var/lib/dpkg/status").and_then(|x| x.modified());
+
+    if let (Ok(modified), Some((time, count))) = (&modified, &cached)
+        && modified == time
+    {
+        return *count;
+    }
+
+    let pkg_list = Command::new("dpkg").arg("--get-selections").output().ok();
+    let count = pkg_list
+        .map(|output| output.stdout.into_iter().filter(|&x| x == b'\n').count())
+        .unwrap_or(0);
+
+    *cached = modified.ok().map(|x| (x, count));
+
+    count
+}
+
+pub fn host(mut ctx: BackendContext) -> HostResponse {
     let unknown = || "unknown".to_string();
 
-    let nic = net
-        .iter()
-        .max_by_key(|(_, net)| net.total_transmitted())
-        .map(|(name, _)| name)
-        .cloned()
-        .unwrap_or_else(unknown);
+    // dpkg and vcgencmd can be slow, so they're run with copies of the cached values rather than under the lock
+    let (nic, mut packages, cached_board) = {
+        let system = ctx.system();
+
+        let nic = system
+            .networks
+            .iter()
+            .max_by_key(|(_, net)| net.total_transmitted())
+            .map(|(name, _)| name)
+            .cloned()
+            .unwrap_or_else(unknown);
+
+        (nic, system.packages, system.board.clone())
+    };
 
     let uptime = System::uptime();
     let arch = {
@@ -179,10 +1136,21 @@ pub fn host(mut ctx: BackendContext) -> HostResponse {
         })
         .unwrap_or_else(unknown);
 
-    let pkg_list = Command::new("dpkg").arg("--get-selections").output().ok();
-    let num_pkgs = pkg_list
-        .map(|output| output.stdout.into_iter().filter(|&x| x == b'\n').count())
-        .unwrap_or(0);
+    let num_pkgs = package_count(&mut packages);
+
+    // The model and memory split can't change while running, so vcgencmd is only asked once
+    let board_info = cached_board.unwrap_or_else(board);
+
+    {
+        let mut system = ctx.system();
+        system.packages = packages;
+        system.board = Some(board_info.clone());
+    }
+
+    let board = board_info.map(|board| BoardInfo {
+        throttling: throttling(),
+        ..board
+    });
 
     HostResponse {
         nic,
@@ -193,6 +1161,7 @@ pub fn host(mut ctx: BackendContext) -> HostResponse {
         hostname,
         dp_version,
         num_pkgs,
+        board,
     }
 }
 
@@ -229,11 +1198,13 @@ fn parse_software_line(line: &str) -> Option<(SoftwareInfo, bool)> {
     ))
 }
 
-pub fn software(_ctx: BackendContext) -> SoftwareResponse {
-    let cmd_out = Command::new("/boot/dietpi/dietpi-software")
-        .args(["list", "--machine-readable"])
-        .output()
-        .ok();
+pub fn software(ctx: BackendContext) -> SoftwareResponse {
+    let cmd_out = privileged::output(
+        &ctx,
+        Program::DietpiSoftware,
+        vec!["list".into(), "--machine-readable".into()],
+    )
+    .ok();
     let cmd_out = cmd_out.and_then(|output| String::from_utf8(output.stdout).ok());
 
     let software_iter = cmd_out
@@ -275,22 +1246,52 @@ fn remove_escape_codes(s: impl Iterator<Item = u8>) -> Vec<u8> {
     .collect()
 }
 
-pub fn command(_ctx: BackendContext, action: CommandAction) -> CommandResponse {
-    let output = Command::new(action.cmd)
-        .args(&action.args)
-        .output()
-        .map(|out| out.stdout.into_iter())
-        .map(remove_escape_codes)
-        .unwrap_or_else(|err| format!("command execution failed: {err}").into());
+pub fn command(ctx: BackendContext, action: CommandAction) -> CommandResponse {
+    let start = Instant::now();
+
+    let failed = |err: String, blocked: bool| CommandResponse {
+        output: Vec::new(),
+        stderr: Vec::new(),
+        exit_code: None,
+        signal: None,
+        duration_ms: start.elapsed().as_millis() as u64,
+        error: Some(err),
+        blocked,
+    };
+
+    if let Err(err) = policy::check_command(&ctx.config, &action) {
+        return failed(err, true);
+    }
+
+    let output = match Program::from_path(&action.cmd) {
+        Some(program) => privileged::output(&ctx, program, action.args),
+        None => Command::new(action.cmd)
+            .args(&action.args)
+            .output()
+            .map(|out| HelperOutput {
+                stdout: out.stdout,
+                stderr: out.stderr,
+                exit_code: out.status.code(),
+                signal: out.status.signal(),
+            }),
+    };
 
-    CommandResponse { output }
+    match output {
+        Ok(out) => CommandResponse {
+            output: remove_escape_codes(out.stdout.into_iter()),
+            stderr: remove_escape_codes(out.stderr.into_iter()),
+            exit_code: out.exit_code,
+            signal: out.signal,
+            duration_ms: start.elapsed().as_millis() as u64,
+            error: None,
+            blocked: false,
+        },
+        Err(err) => failed(format!("command execution failed: {err}"), false),
+    }
 }
 
-fn services_helper() -> Option<ServiceResponse> {
-    let output = Command::new("/boot/dietpi/dietpi-services")
-        .arg("status")
-        .output()
-        .ok()?;
+fn services_helper(ctx: &BackendContext) -> Option<ServiceResponse> {
+    let output = privileged::output(ctx, Program::DietpiServices, vec!["status".into()]).ok()?;
 
     let stdout = remove_escape_codes(output.stdout.into_iter());
     let stdout = std::str::from_utf8(&stdout).ok()?;
@@ -344,8 +1345,8 @@ fn services_helper() -> Option<ServiceResponse> {
     Some(ServiceResponse { services })
 }
 
-pub fn services(_ctx: BackendContext) -> ServiceResponse {
-    services_helper().unwrap_or_default()
+pub fn services(ctx: BackendContext) -> ServiceResponse {
+    services_helper(&ctx).unwrap_or_default()
 }
 
 pub fn list_directory_helper(path: String) -> Option<DirectoryResponse> {
@@ -380,12 +1381,16 @@ pub fn list_directory_helper(path: String) -> Option<DirectoryResponse> {
     Some(DirectoryResponse { dir_list })
 }
 
-pub fn list_directory(_ctx: BackendContext, path: String) -> DirectoryResponse {
-    list_directory_helper(path).unwrap_or_default()
+pub fn list_directory(ctx: BackendContext, path: String) -> DirectoryResponse {
+    policy::check_path(&ctx.config, &path, Access::Read)
+        .and_then(|path| list_directory_helper(path.into_os_string().into_string().ok()?))
+        .unwrap_or_default()
 }
 
-pub fn read_file(_ctx: BackendContext, path: String) -> Vec<u8> {
-    fs::read(path).unwrap_or_default()
+pub fn read_file(ctx: BackendContext, path: String) -> Vec<u8> {
+    policy::check_path(&ctx.config, &path, Access::Read)
+        .and_then(|path| fs::read(path).ok())
+        .unwrap_or_default()
 }
 
 pub fn read_config_helper() -> Option<String> {
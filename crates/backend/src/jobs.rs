@@ -0,0 +1,154 @@
+use std::{os::unix::process::ExitStatusExt, process::Stdio, time::Instant};
+
+use log::info;
+use proto::{
+    backend::{ActionBackendMessage, BackendMessage, CommandExit, CommandOutput},
+    frontend::StartCommandAction,
+};
+use tokio::{io::AsyncReadExt, process::Command};
+
+use crate::{
+    client::BackendContext,
+    policy,
+    privileged::{HelperEvent, Program},
+};
+
+fn send_output(ctx: &BackendContext, id: u32, stderr: bool, data: Vec<u8>) {
+    let msg = ActionBackendMessage::CommandOutput(CommandOutput { id, stderr, data });
+    let _ = ctx.socket_tx.send(BackendMessage::Action(msg));
+}
+
+fn send_exit(ctx: &BackendContext, exit: CommandExit) {
+    let msg = ActionBackendMessage::CommandExit(exit);
+    let _ = ctx.socket_tx.send(BackendMessage::Action(msg));
+}
+
+// Output is sent as-is, escape codes included, so the frontend can show progress bars and colors
+pub async fn run(ctx: BackendContext, start: StartCommandAction) {
+    let StartCommandAction { id, action } = start;
+
+    info!("Starting command job {id:08x}: {}", action.cmd);
+
+    let start = Instant::now();
+
+    if let Err(err) = policy::check_command(&ctx.config, &action) {
+        send_exit(
+            &ctx,
+            CommandExit {
+                id,
+                exit_code: None,
+                signal: None,
+                duration_ms: 0,
+                error: Some(err),
+                blocked: true,
+            },
+        );
+        return;
+    }
+
+    if let (Some(helper), Some(program)) = (&ctx.helper, Program::from_path(&action.cmd)) {
+        let mut rx = helper.start(program, action.args);
+
+        while let Some(event) = rx.recv().await {
+            match event {
+                HelperEvent::Output { stderr, data } => send_output(&ctx, id, stderr, data),
+                HelperEvent::Exit {
+                    exit_code,
+                    signal,
+                    error,
+                } => {
+                    info!("Command job {id:08x} finished");
+
+                    send_exit(
+                        &ctx,
+                        CommandExit {
+                            id,
+                            exit_code,
+                            signal,
+                            duration_ms: start.elapsed().as_millis() as u64,
+                            error,
+                            blocked: false,
+                        },
+                    );
+                    return;
+                }
+            }
+        }
+
+        send_exit(
+            &ctx,
+            CommandExit {
+                id,
+                exit_code: None,
+                signal: None,
+                duration_ms: start.elapsed().as_millis() as u64,
+                error: Some("privileged helper exited".into()),
+                blocked: false,
+            },
+        );
+        return;
+    }
+
+    let child = Command::new(&action.cmd)
+        .args(&action.args)
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn();
+
+    let mut child = match child {
+        Ok(child) => child,
+        Err(err) => {
+            send_exit(
+                &ctx,
+                CommandExit {
+                    id,
+                    exit_code: None,
+                    signal: None,
+                    duration_ms: start.elapsed().as_millis() as u64,
+                    error: Some(format!("command execution failed: {err}")),
+                    blocked: false,
+                },
+            );
+            return;
+        }
+    };
+
+    // Both are always set, since they were piped above
+    let mut stdout = child.stdout.take().unwrap();
+    let mut stderr = child.stderr.take().unwrap();
+
+    let mut stdout_buf = [0; 4096];
+    let mut stderr_buf = [0; 4096];
+    let mut stdout_open = true;
+    let mut stderr_open = true;
+
+    while stdout_open || stderr_open {
+        tokio::select! {
+            n = stdout.read(&mut stdout_buf), if stdout_open => match n {
+                Ok(0) | Err(_) => stdout_open = false,
+                Ok(n) => send_output(&ctx, id, false, stdout_buf[..n].to_vec()),
+            },
+            n = stderr.read(&mut stderr_buf), if stderr_open => match n {
+                Ok(0) | Err(_) => stderr_open = false,
+                Ok(n) => send_output(&ctx, id, true, stderr_buf[..n].to_vec()),
+            },
+        }
+    }
+
+    let status = child.wait().await.ok();
+
+    info!("Command job {id:08x} finished");
+
+    send_exit(
+        &ctx,
+        CommandExit {
+            id,
+            exit_code: status.and_then(|x| x.code()),
+            signal: status.and_then(|x| x.signal()),
+            duration_ms: start.elapsed().as_millis() as u64,
+            error: None,
+            blocked: false,
+        },
+    );
+}
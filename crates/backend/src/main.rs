@@ -7,7 +7,7 @@ use anyhow::{Context, Result};
 use client::{BackendClient, BackendContext, SystemComponents};
 use config::{
     APP_VERSION,
-    backend::{BackendConfig, get_config},
+    backend::{BackendConfig, CommandPolicy, get_config},
 };
 use log::{error, info};
 use proto::backend::BackendMessage;
@@ -18,6 +18,9 @@ use tokio::sync::mpsc;
 mod actions;
 mod client;
 mod getters;
+mod jobs;
+mod policy;
+mod privileged;
 mod terminal;
 
 pub type SharedConfig = Arc<BackendConfig>;
@@ -39,19 +42,38 @@ async fn main() -> Result<()> {
         .init()
         .unwrap();
 
+    if std::env::args().nth(1).as_deref() == Some(privileged::HELPER_ARG) {
+        privileged::run_helper();
+    }
+
     info!("Starting DietPi-Dashboard backend v{APP_VERSION}...");
 
+    // Done before anything else starts, so nothing is left running as root by accident
+    let helper =
+        privileged::start(&config.unprivileged_user).context("failed to drop privileges")?;
+
+    // The allowlist is the default, so make it clear why a command the frontend sends could be refused
+    if config.command_policy == CommandPolicy::Allowlist {
+        info!(
+            "command_policy is \"allowlist\", so the command runner and scheduled jobs can only run commands in allowed_commands, and shell commands are blocked"
+        );
+    }
+
     info!("Connecting to {}", config.frontend_addr);
 
     let (term_tx, term_rx) = mpsc::unbounded_channel();
     let (socket_tx, mut socket_rx) = mpsc::unbounded_channel();
 
-    let terminal = Terminal::new(socket_tx.clone(), term_rx);
-    match terminal {
-        Ok(terminal) => {
-            tokio::spawn(terminal.run());
+    // agetty needs root to log in
+    if helper.is_some() {
+        info!("Running unprivileged, so the terminal is disabled");
+    } else {
+        match Terminal::new(socket_tx.clone(), term_rx) {
+            Ok(terminal) => {
+                tokio::spawn(terminal.run());
+            }
+            Err(err) => error!("terminal failed to start: {err:?}"),
         }
-        Err(err) => error!("terminal failed to start: {err:?}"),
     }
 
     let system = Arc::new(Mutex::new(SystemComponents::new()));
@@ -60,6 +82,7 @@ async fn main() -> Result<()> {
         system,
         term_tx,
         socket_tx,
+        helper,
     };
 
     let mut errors = 0;
@@ -0,0 +1,342 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use config::backend::CommandPolicy;
+use log::warn;
+use proto::frontend::CommandAction;
+
+use crate::SharedConfig;
+
+// Simple glob, where "*" matches any run of characters (including none)
+// Only ever used on a single argument, so it can't match across argument boundaries
+pub fn glob_match(pattern: &str, text: &str) -> bool {
+    let mut parts = pattern.split('*');
+
+    // There's always at least one part, even for an empty pattern
+    let first = parts.next().unwrap();
+    let Some(mut rest) = text.strip_prefix(first) else {
+        return false;
+    };
+
+    let mut parts: Vec<_> = parts.collect();
+    let Some(last) = parts.pop() else {
+        // No wildcards, so it has to be an exact match
+        return rest.is_empty();
+    };
+
+    for part in parts {
+        match rest.find(part) {
+            Some(idx) => rest = &rest[idx + part.len()..],
+            None => return false,
+        }
+    }
+
+    rest.ends_with(last)
+}
+
+// Shells run whatever their script says, so allowing them by their arguments would allow anything
+const SHELLS: &[&str] = &["sh", "bash", "dash", "ash", "zsh", "busybox"];
+
+fn is_shell_script(action: &CommandAction) -> bool {
+    let name = Path::new(&action.cmd)
+        .file_name()
+        .and_then(|x| x.to_str())
+        .unwrap_or_default();
+
+    // Also catches combined flags, like "sh -ec"
+    SHELLS.contains(&name)
+        && action
+            .args
+            .iter()
+            .any(|arg| arg.starts_with('-') && !arg.starts_with("--") && arg.contains('c'))
+}
+
+// The first word has to match the program exactly, then every other word is matched against one argument
+// A lone "*" at the end matches any remaining arguments, including none
+fn entry_match(entry: &str, action: &CommandAction) -> bool {
+    let mut words = entry.split_whitespace();
+    if words.next() != Some(action.cmd.as_str()) {
+        return false;
+    }
+
+    let mut patterns: Vec<_> = words.collect();
+    let rest = patterns.last() == Some(&"*");
+    if rest {
+        patterns.pop();
+    }
+
+    if action.args.len() < patterns.len() || (!rest && action.args.len() != patterns.len()) {
+        return false;
+    }
+
+    patterns
+        .iter()
+        .zip(&action.args)
+        .all(|(pattern, arg)| glob_match(pattern, arg))
+}
+
+fn command_line(action: &CommandAction) -> String {
+    let mut line = action.cmd.clone();
+    for arg in &action.args {
+        line.push(' ');
+        line.push_str(arg);
+    }
+    line
+}
+
+// Rejections are logged here, and the returned message is sent back to the frontend
+pub fn check_command(config: &SharedConfig, action: &CommandAction) -> Result<(), String> {
+    let line = command_line(action);
+
+    let err = match config.command_policy {
+        CommandPolicy::All => return Ok(()),
+        CommandPolicy::None => "command execution is disabled on this backend".into(),
+        CommandPolicy::Allowlist if is_shell_script(action) => {
+            "shell commands need command_policy = \"all\" on this backend".into()
+        }
+        CommandPolicy::Allowlist => {
+            if config
+                .allowed_commands
+                .iter()
+                .any(|entry| entry_match(entry, action))
+            {
+                return Ok(());
+            }
+            format!("command isn't in this backend's allowed_commands: {line}")
+        }
+    };
+
+    warn!("Rejected command not allowed by command_policy: {line}");
+
+    Err(err)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Access {
+    Read,
+    Write,
+    // Deleting or renaming acts on a symlink itself rather than its target, so only the link has to be allowed
+    Entry,
+}
+
+// Config paths are resolved the same way as requested ones, so symlinked config paths still match
+fn canonical(path: &str) -> PathBuf {
+    fs::canonicalize(path).unwrap_or_else(|_| path.into())
+}
+
+// Resolves everything but the last component, so that renaming or deleting a symlink acts on the link itself
+fn resolve(path: &str) -> Result<PathBuf, String> {
+    let path = Path::new(path);
+    if !path.is_absolute() {
+        return Err("path must be absolute".into());
+    }
+
+    match (path.parent(), path.file_name()) {
+        (Some(parent), Some(name)) => fs::canonicalize(parent)
+            .map(|x| x.join(name))
+            .map_err(|err| format!("failed to resolve {}: {err}", parent.display())),
+        _ => fs::canonicalize(path)
+            .map_err(|err| format!("failed to resolve {}: {err}", path.display())),
+    }
+}
+
+fn check_resolved(config: &SharedConfig, path: &Path, access: Access) -> Result<(), String> {
+    let in_root = config
+        .browser_roots
+        .iter()
+        .any(|root| path.starts_with(canonical(root)));
+    if !in_root {
+        return Err(format!("{} is outside of browser_roots", path.display()));
+    }
+
+    let read_only = config
+        .browser_read_only
+        .iter()
+        .any(|x| path.starts_with(canonical(x)));
+    if access != Access::Read && read_only {
+        return Err(format!("{} is read-only", path.display()));
+    }
+
+    Ok(())
+}
+
+fn check_path_inner(config: &SharedConfig, path: &str, access: Access) -> Result<PathBuf, String> {
+    let entry = resolve(path)?;
+    check_resolved(config, &entry, access)?;
+
+    // A symlink could point anywhere, so wherever it ends up has to be allowed too
+    if access != Access::Entry && entry.is_symlink() {
+        let target = fs::canonicalize(&entry)
+            .map_err(|err| format!("failed to resolve {}: {err}", entry.display()))?;
+        check_resolved(config, &target, access)?;
+    }
+
+    Ok(entry)
+}
+
+// Returns the resolved path, which should be used instead of the requested one
+pub fn check_path(config: &SharedConfig, path: &str, access: Access) -> Option<PathBuf> {
+    check_path_inner(config, path, access)
+        .inspect_err(|err| warn!("Rejected file browser access: {err}"))
+        .ok()
+}
+
+// Like check_path, but also refuses anything that would take a protected path with it
+pub fn check_recursive_delete(config: &SharedConfig, path: &str) -> Option<PathBuf> {
+    let entry = check_path(config, path, Access::Entry)?;
+
+    let protected = config
+        .browser_protected
+        .iter()
+        .any(|x| canonical(x).starts_with(&entry));
+    if protected {
+        warn!(
+            "Rejected file browser access: {} is protected from recursive deletes",
+            entry.display()
+        );
+        return None;
+    }
+
+    Some(entry)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn action(cmd: &str, args: &[&str]) -> CommandAction {
+        CommandAction {
+            cmd: cmd.into(),
+            args: args.iter().map(|&x| x.into()).collect(),
+        }
+    }
+
+    #[test]
+    fn glob_exact() {
+        assert!(glob_match("status", "status"));
+        assert!(!glob_match("status", "statuses"));
+        assert!(!glob_match("status", "stat"));
+        assert!(glob_match("", ""));
+        assert!(!glob_match("", "x"));
+    }
+
+    #[test]
+    fn glob_wildcards() {
+        assert!(glob_match("*", ""));
+        assert!(glob_match("*", "anything"));
+        assert!(glob_match("--log=*", "--log=debug"));
+        assert!(glob_match("--log=*", "--log="));
+        assert!(!glob_match("--log=*", "--level=debug"));
+        assert!(glob_match("*.conf", "nginx.conf"));
+        assert!(!glob_match("*.conf", "nginx.conf.bak"));
+        assert!(glob_match("a*b*c", "abc"));
+        assert!(glob_match("a*b*c", "a-b-b-c"));
+        assert!(!glob_match("a*b*c", "acb"));
+    }
+
+    #[test]
+    fn glob_parts_dont_overlap() {
+        assert!(!glob_match("ab*b", "ab"));
+        assert!(!glob_match("a*bc*c", "abc"));
+        assert!(glob_match("a*bc*c", "abcc"));
+    }
+
+    #[test]
+    fn entry_exact_args() {
+        let entry = "systemctl restart nginx";
+        assert!(entry_match(
+            entry,
+            &action("systemctl", &["restart", "nginx"])
+        ));
+        assert!(!entry_match(entry, &action("systemctl", &["restart"])));
+        assert!(!entry_match(
+            entry,
+            &action("systemctl", &["restart", "nginx", "mariadb"])
+        ));
+        assert!(!entry_match(
+            entry,
+            &action("systemctl", &["stop", "nginx"])
+        ));
+    }
+
+    #[test]
+    fn entry_no_args() {
+        assert!(entry_match("uptime", &action("uptime", &[])));
+        assert!(!entry_match("uptime", &action("uptime", &["-p"])));
+    }
+
+    #[test]
+    fn entry_argument_wildcards() {
+        // A wildcard in the middle still stands for exactly one argument
+        let entry = "systemctl * nginx";
+        assert!(entry_match(
+            entry,
+            &action("systemctl", &["restart", "nginx"])
+        ));
+        assert!(!entry_match(entry, &action("systemctl", &["nginx"])));
+        assert!(!entry_match(
+            entry,
+            &action("systemctl", &["restart", "--now", "nginx"])
+        ));
+
+        let entry = "systemctl restart nginx-*";
+        assert!(entry_match(
+            entry,
+            &action("systemctl", &["restart", "nginx-proxy"])
+        ));
+        assert!(!entry_match(
+            entry,
+            &action("systemctl", &["restart", "nginx-proxy", "mariadb"])
+        ));
+    }
+
+    #[test]
+    fn entry_trailing_wildcard() {
+        let entry = "/boot/dietpi/dietpi-software *";
+        let cmd = "/boot/dietpi/dietpi-software";
+        assert!(entry_match(entry, &action(cmd, &[])));
+        assert!(entry_match(entry, &action(cmd, &["install", "17"])));
+
+        let entry = "apt install *";
+        assert!(entry_match(entry, &action("apt", &["install"])));
+        assert!(entry_match(
+            entry,
+            &action("apt", &["install", "vim", "git"])
+        ));
+        assert!(!entry_match(entry, &action("apt", &["remove", "vim"])));
+    }
+
+    #[test]
+    fn entry_arguments_keep_their_spaces() {
+        // Quoted arguments arrive as one argument, and a wildcard can't reach past it
+        let entry = "echo *";
+        assert!(entry_match(entry, &action("echo", &["hello world"])));
+
+        let entry = "echo hello";
+        assert!(!entry_match(entry, &action("echo", &["hello world"])));
+        assert!(!entry_match(entry, &action("echo", &["hello", "world"])));
+    }
+
+    #[test]
+    fn entry_program_must_match_exactly() {
+        let entry = "/boot/dietpi/dietpi-software *";
+        assert!(!entry_match(entry, &action("dietpi-software", &["list"])));
+        assert!(!entry_match(
+            entry,
+            &action("/tmp/boot/dietpi/dietpi-software", &["list"])
+        ));
+
+        assert!(!entry_match("reboot", &action("/sbin/reboot", &[])));
+        assert!(!entry_match("re*", &action("reboot", &[])));
+    }
+
+    #[test]
+    fn shell_scripts() {
+        assert!(is_shell_script(&action("sh", &["-c", "reboot"])));
+        assert!(is_shell_script(&action("/bin/bash", &["-ec", "reboot"])));
+        assert!(!is_shell_script(&action("bash", &["--version"])));
+        assert!(!is_shell_script(&action("ls", &["-c"])));
+    }
+}
@@ -0,0 +1,435 @@
+use std::{
+    collections::HashMap,
+    ffi::CString,
+    fs::File,
+    io::{self, Read, Write},
+    os::{fd::FromRawFd, unix::process::ExitStatusExt},
+    process::{Child, ChildStdin, Command, Stdio},
+    sync::{
+        Arc, Mutex,
+        atomic::{AtomicU32, Ordering},
+    },
+    thread,
+};
+
+use anyhow::{Context, Result, bail};
+use bitcode::{Decode, DecodeOwned, Encode};
+use log::{error, info, warn};
+use proto::frontend::Signal;
+use tokio::sync::mpsc;
+
+use crate::client::BackendContext;
+
+// Passed to the backend binary to start it as the helper instead
+pub const HELPER_ARG: &str = "--privileged-helper";
+
+// The only programs the helper will run, so it can't be used to run anything else as root
+#[derive(Debug, Clone, Copy, Encode, Decode)]
+pub enum Program {
+    DietpiSoftware,
+    DietpiServices,
+}
+
+impl Program {
+    pub fn path(self) -> &'static str {
+        match self {
+            Self::DietpiSoftware => "/boot/dietpi/dietpi-software",
+            Self::DietpiServices => "/boot/dietpi/dietpi-services",
+        }
+    }
+
+    pub fn from_path(path: &str) -> Option<Self> {
+        [Self::DietpiSoftware, Self::DietpiServices]
+            .into_iter()
+            .find(|x| x.path() == path)
+    }
+}
+
+#[derive(Debug, Encode, Decode)]
+enum HelperRequest {
+    Run { program: Program, args: Vec<String> },
+    Signal { pid: u32, signal: Signal },
+}
+
+#[derive(Debug, Encode, Decode)]
+pub enum HelperEvent {
+    Output {
+        stderr: bool,
+        data: Vec<u8>,
+    },
+    // Always the last event for a request
+    Exit {
+        exit_code: Option<i32>,
+        signal: Option<i32>,
+        error: Option<String>,
+    },
+}
+
+pub struct HelperOutput {
+    pub stdout: Vec<u8>,
+    pub stderr: Vec<u8>,
+    pub exit_code: Option<i32>,
+    pub signal: Option<i32>,
+}
+
+// Frames are a little-endian u32 length followed by the bitcode-encoded message
+fn write_frame(writer: &mut impl Write, msg: &impl Encode) -> io::Result<()> {
+    let data = bitcode::encode(msg);
+
+    writer.write_all(&(data.len() as u32).to_le_bytes())?;
+    writer.write_all(&data)?;
+    writer.flush()
+}
+
+fn read_frame<T: DecodeOwned>(reader: &mut impl Read) -> io::Result<Option<T>> {
+    let mut len = [0; 4];
+    match reader.read_exact(&mut len) {
+        Ok(()) => {}
+        Err(err) if err.kind() == io::ErrorKind::UnexpectedEof => return Ok(None),
+        Err(err) => return Err(err),
+    }
+
+    let mut data = vec![0; u32::from_le_bytes(len) as usize];
+    reader.read_exact(&mut data)?;
+
+    bitcode::decode(&data).map(Some).map_err(io::Error::other)
+}
+
+pub struct Helper {
+    stdin: Mutex<ChildStdin>,
+    pending: Arc<Mutex<HashMap<u32, mpsc::UnboundedSender<HelperEvent>>>>,
+    next_id: AtomicU32,
+    _child: Child,
+}
+
+pub type SharedHelper = Arc<Helper>;
+
+impl Helper {
+    // Has to be called while still root, before dropping privileges
+    pub fn spawn() -> Result<Self> {
+        let exe = std::env::current_exe().context("failed to get backend executable")?;
+
+        let mut child = Command::new(exe)
+            .arg(HELPER_ARG)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .spawn()
+            .context("failed to spawn privileged helper")?;
+
+        // Both are always set, since they were piped above
+        let stdin = child.stdin.take().unwrap();
+        let mut stdout = child.stdout.take().unwrap();
+
+        let pending: Arc<Mutex<HashMap<u32, mpsc::UnboundedSender<HelperEvent>>>> = Arc::default();
+        let thread_pending = pending.clone();
+
+        thread::spawn(move || {
+            loop {
+                let (id, event): (u32, HelperEvent) = match read_frame(&mut stdout) {
+                    Ok(Some(frame)) => frame,
+                    Ok(None) => break,
+                    Err(err) => {
+                        error!("Failed to read from privileged helper: {err}");
+                        break;
+                    }
+                };
+
+                let mut pending = thread_pending.lock().unwrap();
+                let done = matches!(event, HelperEvent::Exit { .. });
+                if let Some(tx) = pending.get(&id) {
+                    let _ = tx.send(event);
+                }
+                if done {
+                    pending.remove(&id);
+                }
+            }
+
+            error!("Privileged helper exited, privileged operations will fail");
+
+            // Dropping the senders lets anything waiting know it isn't getting a response
+            thread_pending.lock().unwrap().clear();
+        });
+
+        Ok(Self {
+            stdin: Mutex::new(stdin),
+            pending,
+            next_id: AtomicU32::new(0),
+            _child: child,
+        })
+    }
+
+    fn send(&self, req: HelperRequest) -> mpsc::UnboundedReceiver<HelperEvent> {
+        let (tx, rx) = mpsc::unbounded_channel();
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+
+        self.pending.lock().unwrap().insert(id, tx);
+
+        if let Err(err) = write_frame(&mut *self.stdin.lock().unwrap(), &(id, req)) {
+            error!("Failed to send request to privileged helper: {err}");
+            self.pending.lock().unwrap().remove(&id);
+        }
+
+        rx
+    }
+
+    // Events are sent as they happen, so output can be streamed
+    pub fn start(
+        &self,
+        program: Program,
+        args: Vec<String>,
+    ) -> mpsc::UnboundedReceiver<HelperEvent> {
+        self.send(HelperRequest::Run { program, args })
+    }
+
+    // Blocks, so should only be called from blocking contexts like the getters
+    pub fn output(&self, program: Program, args: Vec<String>) -> io::Result<HelperOutput> {
+        let mut rx = self.start(program, args);
+        let mut output = HelperOutput {
+            stdout: Vec::new(),
+            stderr: Vec::new(),
+            exit_code: None,
+            signal: None,
+        };
+
+        while let Some(event) = rx.blocking_recv() {
+            match event {
+                HelperEvent::Output {
+                    stderr: false,
+                    data,
+                } => output.stdout.extend(data),
+                HelperEvent::Output { stderr: true, data } => output.stderr.extend(data),
+                HelperEvent::Exit {
+                    error: Some(err), ..
+                } => return Err(io::Error::other(err)),
+                HelperEvent::Exit {
+                    exit_code, signal, ..
+                } => {
+                    output.exit_code = exit_code;
+                    output.signal = signal;
+                    return Ok(output);
+                }
+            }
+        }
+
+        Err(io::Error::other("privileged helper exited"))
+    }
+
+    pub fn signal(&self, pid: u32, signal: Signal) {
+        // The helper logs any failures itself
+        let _ = self.send(HelperRequest::Signal { pid, signal });
+    }
+}
+
+// Runs one of the programs that needs root, through the helper if privileges were dropped
+pub fn output(
+    ctx: &BackendContext,
+    program: Program,
+    args: Vec<String>,
+) -> io::Result<HelperOutput> {
+    if let Some(helper) = &ctx.helper {
+        return helper.output(program, args);
+    }
+
+    let output = Command::new(program.path()).args(args).output()?;
+
+    Ok(HelperOutput {
+        stdout: output.stdout,
+        stderr: output.stderr,
+        exit_code: output.status.code(),
+        signal: output.status.signal(),
+    })
+}
+
+struct User {
+    name: CString,
+    uid: libc::uid_t,
+    gid: libc::gid_t,
+}
+
+fn lookup_user(user: &str) -> Result<User> {
+    let name = CString::new(user).context("invalid user name")?;
+
+    // SAFETY: the name is a valid C string, and the returned entry is read before any other passwd calls
+    let (uid, gid) = unsafe {
+        let passwd = libc::getpwnam(name.as_ptr());
+        if passwd.is_null() {
+            bail!("user {user} doesn't exist");
+        }
+        ((*passwd).pw_uid, (*passwd).pw_gid)
+    };
+
+    // Switching to another name for root would leave everything running as root without saying so
+    if uid == 0 {
+        bail!("user {user} is root, set unprivileged_user to a user without root privileges");
+    }
+
+    Ok(User { name, uid, gid })
+}
+
+fn drop_privileges(user: &str, User { name, uid, gid }: User) -> Result<()> {
+    // SAFETY: plain syscalls, groups have to be set before the user ID or it isn't allowed anymore
+    unsafe {
+        if libc::initgroups(name.as_ptr(), gid as _) != 0 {
+            bail!("failed to set groups: {}", io::Error::last_os_error());
+        }
+        if libc::setgid(gid) != 0 {
+            bail!("failed to set group ID: {}", io::Error::last_os_error());
+        }
+        if libc::setuid(uid) != 0 {
+            bail!("failed to set user ID: {}", io::Error::last_os_error());
+        }
+    }
+
+    info!("Dropped privileges to user {user}");
+
+    Ok(())
+}
+
+// Starts the helper and switches to the unprivileged user, if one is set
+pub fn start(user: &str) -> Result<Option<SharedHelper>> {
+    if user.is_empty() {
+        return Ok(None);
+    }
+
+    // SAFETY: just reads the effective user ID
+    if unsafe { libc::geteuid() } != 0 {
+        warn!("unprivileged_user is set, but the backend isn't running as root");
+        return Ok(None);
+    }
+
+    // Checked first, so nothing is started for a user that can't be used
+    let resolved = lookup_user(user)?;
+
+    let helper = Helper::spawn()?;
+    drop_privileges(user, resolved)?;
+
+    Ok(Some(Arc::new(helper)))
+}
+
+fn run_program(program: Program, args: Vec<String>, send: impl Fn(HelperEvent) + Send + Sync) {
+    let child = Command::new(program.path())
+        .args(args)
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn();
+
+    let mut child = match child {
+        Ok(child) => child,
+        Err(err) => {
+            send(HelperEvent::Exit {
+                exit_code: None,
+                signal: None,
+                error: Some(format!("command execution failed: {err}")),
+            });
+            return;
+        }
+    };
+
+    // Both are always set, since they were piped above
+    let stdout = child.stdout.take().unwrap();
+    let stderr = child.stderr.take().unwrap();
+
+    let forward = |mut reader: Box<dyn Read + Send>, stderr: bool| {
+        let mut buf = [0; 4096];
+        loop {
+            match reader.read(&mut buf) {
+                Ok(0) | Err(_) => break,
+                Ok(n) => send(HelperEvent::Output {
+                    stderr,
+                    data: buf[..n].to_vec(),
+                }),
+            }
+        }
+    };
+
+    thread::scope(|s| {
+        s.spawn(|| forward(Box::new(stderr), true));
+        forward(Box::new(stdout), false);
+    });
+
+    let status = child.wait().ok();
+
+    send(HelperEvent::Exit {
+        exit_code: status.and_then(|x| x.code()),
+        signal: status.and_then(|x| x.signal()),
+        error: None,
+    });
+}
+
+fn send_signal(pid: u32, signal: Signal) -> Option<String> {
+    // Signalling init or every process at once is never what the process page meant to do
+    if pid <= 1 {
+        return Some(format!("refusing to signal pid {pid}"));
+    }
+
+    let signal = match signal {
+        Signal::Term => libc::SIGTERM,
+        Signal::Pause => libc::SIGSTOP,
+        Signal::Resume => libc::SIGCONT,
+        Signal::Kill => libc::SIGKILL,
+    };
+
+    // SAFETY: plain syscall, the pid was checked above
+    if unsafe { libc::kill(pid as libc::pid_t, signal) } != 0 {
+        return Some(io::Error::last_os_error().to_string());
+    }
+
+    None
+}
+
+// Entry point of the helper process, which stays root and only does what's in HelperRequest
+pub fn run_helper() -> ! {
+    // Frames go over the original stdout, and anything else printed there (like logs) gets moved to stderr
+    // SAFETY: plain syscalls on the standard file descriptors, which are open for the whole process
+    let frames = unsafe {
+        let fd = libc::dup(libc::STDOUT_FILENO);
+        if fd < 0 || libc::dup2(libc::STDERR_FILENO, libc::STDOUT_FILENO) < 0 {
+            error!(
+                "Privileged helper failed to set up output: {}",
+                io::Error::last_os_error()
+            );
+            std::process::exit(1);
+        }
+        File::from_raw_fd(fd)
+    };
+
+    let stdout = Arc::new(Mutex::new(frames));
+    let mut stdin = io::stdin().lock();
+
+    info!("Privileged helper started");
+
+    loop {
+        let (id, req): (u32, HelperRequest) = match read_frame(&mut stdin) {
+            Ok(Some(frame)) => frame,
+            // The backend exited, so there's nothing left to do
+            Ok(None) => std::process::exit(0),
+            Err(err) => {
+                error!("Privileged helper failed to read request: {err}");
+                std::process::exit(1);
+            }
+        };
+
+        info!("Privileged helper request: {req:?}");
+
+        let stdout = stdout.clone();
+        let send = move |event: HelperEvent| {
+            let _ = write_frame(&mut *stdout.lock().unwrap(), &(id, event));
+        };
+
+        thread::spawn(move || match req {
+            HelperRequest::Run { program, args } => run_program(program, args, send),
+            HelperRequest::Signal { pid, signal } => {
+                let error = send_signal(pid, signal);
+                if let Some(err) = &error {
+                    warn!("Privileged helper failed to signal pid {pid}: {err}");
+                }
+                send(HelperEvent::Exit {
+                    exit_code: None,
+                    signal: None,
+                    error,
+                });
+            }
+        });
+    }
+}
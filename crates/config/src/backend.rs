@@ -1,14 +1,15 @@
+use std::collections::BTreeMap;
 use std::net::SocketAddr;
 
 use anyhow::Result;
 use log::LevelFilter;
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
 use toml_migrate::build_migration_chain;
 
 use crate::custom_serde::HexArray;
 use crate::generate_config_file;
 
-pub type BackendConfig = BackendConfigV1;
+pub type BackendConfig = BackendConfigV2;
 
 pub fn get_config() -> Result<BackendConfig> {
     crate::read_config("config-backend.toml", generate_config_file)
@@ -21,11 +22,159 @@ fn generate_config_file(config: &BackendConfig) -> String {
         frontend_addr = config.frontend_addr,
         nickname = config.nickname,
         secret = config.secret,
-        disks = config.disks
+        disks = config.disks,
+        discover_disks = config.discover_disks,
+        primary_sensor = config.primary_sensor,
+        network_interfaces = config.network_interfaces,
+        ignored_interfaces = config.ignored_interfaces,
+        command_policy = config.command_policy,
+        allowed_commands = config.allowed_commands,
+        browser_roots = config.browser_roots,
+        browser_read_only = config.browser_read_only,
+        browser_protected = config.browser_protected,
+        unprivileged_user = config.unprivileged_user,
+        tags = TagTable { tags: &config.tags }
     )
 }
 
-build_migration_chain!(BackendConfigV0 = 0, BackendConfigV1 = 1);
+// Tables have to come after every plain key, so this is written at the end
+#[derive(Serialize)]
+struct TagTable<'a> {
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    tags: &'a BTreeMap<String, String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CommandPolicy {
+    All,
+    #[default]
+    Allowlist,
+    None,
+}
+
+// Only dietpi-software is needed for the software page
+fn default_allowed_commands() -> Vec<String> {
+    vec!["/boot/dietpi/dietpi-software *".into()]
+}
+
+fn default_network_interfaces() -> Vec<String> {
+    vec!["*".into()]
+}
+
+// Loopback and container networking would otherwise double count traffic
+fn default_ignored_interfaces() -> Vec<String> {
+    ["lo", "docker*", "veth*", "br-*"].map(String::from).into()
+}
+
+fn default_browser_roots() -> Vec<String> {
+    vec!["/".into()]
+}
+
+fn default_browser_read_only() -> Vec<String> {
+    vec!["/proc".into(), "/sys".into(), "/dev".into()]
+}
+
+fn default_browser_protected() -> Vec<String> {
+    [
+        "/",
+        "/bin",
+        "/boot",
+        "/etc",
+        "/home",
+        "/lib",
+        "/root",
+        "/sbin",
+        "/usr",
+        "/var",
+        "/mnt/dietpi_userdata",
+    ]
+    .map(String::from)
+    .into()
+}
+
+build_migration_chain!(
+    BackendConfigV0 = 0,
+    BackendConfigV1 = 1,
+    BackendConfigV2 = 2
+);
+
+#[derive(Deserialize)]
+pub struct BackendConfigV2 {
+    pub log_level: LevelFilter,
+    pub frontend_addr: SocketAddr,
+    pub nickname: String,
+    pub secret: HexArray<32>,
+    pub disks: Vec<String>,
+    #[serde(default)]
+    pub discover_disks: bool,
+    #[serde(default)]
+    pub primary_sensor: String,
+    #[serde(default = "default_network_interfaces")]
+    pub network_interfaces: Vec<String>,
+    #[serde(default = "default_ignored_interfaces")]
+    pub ignored_interfaces: Vec<String>,
+    #[serde(default)]
+    pub command_policy: CommandPolicy,
+    #[serde(default = "default_allowed_commands")]
+    pub allowed_commands: Vec<String>,
+    #[serde(default = "default_browser_roots")]
+    pub browser_roots: Vec<String>,
+    #[serde(default = "default_browser_read_only")]
+    pub browser_read_only: Vec<String>,
+    #[serde(default = "default_browser_protected")]
+    pub browser_protected: Vec<String>,
+    #[serde(default)]
+    pub unprivileged_user: String,
+    #[serde(default)]
+    pub tags: BTreeMap<String, String>,
+}
+
+impl Default for BackendConfigV2 {
+    fn default() -> Self {
+        Self {
+            log_level: LevelFilter::Info,
+            frontend_addr: ([127, 0, 0, 1], 5253).into(),
+            nickname: String::new(),
+            secret: HexArray(rand::random()),
+            disks: vec!["/".into()],
+            discover_disks: false,
+            primary_sensor: String::new(),
+            network_interfaces: default_network_interfaces(),
+            ignored_interfaces: default_ignored_interfaces(),
+            command_policy: CommandPolicy::default(),
+            allowed_commands: default_allowed_commands(),
+            browser_roots: default_browser_roots(),
+            browser_read_only: default_browser_read_only(),
+            browser_protected: default_browser_protected(),
+            unprivileged_user: String::new(),
+            tags: BTreeMap::new(),
+        }
+    }
+}
+
+impl From<BackendConfigV1> for BackendConfigV2 {
+    fn from(val: BackendConfigV1) -> Self {
+        Self {
+            log_level: val.log_level,
+            frontend_addr: val.frontend_addr,
+            nickname: val.nickname,
+            secret: val.secret,
+            disks: val.disks,
+            discover_disks: false,
+            primary_sensor: String::new(),
+            network_interfaces: default_network_interfaces(),
+            ignored_interfaces: default_ignored_interfaces(),
+            command_policy: CommandPolicy::default(),
+            allowed_commands: default_allowed_commands(),
+            browser_roots: default_browser_roots(),
+            browser_read_only: default_browser_read_only(),
+            browser_protected: default_browser_protected(),
+            unprivileged_user: String::new(),
+            tags: BTreeMap::new(),
+        }
+    }
+}
 
 #[derive(Deserialize)]
 pub struct BackendConfigV1 {
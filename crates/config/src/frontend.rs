@@ -3,13 +3,13 @@ use std::path::PathBuf;
 
 use anyhow::Result;
 use log::LevelFilter;
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
 use toml_migrate::build_migration_chain;
 
 use crate::custom_serde::HexArray;
 use crate::generate_config_file;
 
-pub type FrontendConfig = FrontendConfigV1;
+pub type FrontendConfig = FrontendConfigV2;
 
 pub fn get_config() -> Result<FrontendConfig> {
     crate::read_config("config-frontend.toml", generate_config_file)
@@ -28,11 +28,224 @@ fn generate_config_file(config: &FrontendConfig) -> String {
         cert_path = config.cert_path,
         enable_login = config.enable_login,
         hash = config.hash,
-        secret = config.secret
+        session_idle_timeout = config.session_idle_timeout,
+        session_absolute_timeout = config.session_absolute_timeout,
+        session_remember_timeout = config.session_remember_timeout,
+        session_remember_max_age = config.session_remember_max_age,
+        secret = config.secret,
+        backend_group_tag = config.backend_group_tag,
+        audit_log_files = config.audit_log_files,
+        alerts = AlertTables {
+            alert_rules: &config.alert_rules,
+            alert_channels: &config.alert_channels,
+        }
     )
 }
 
-build_migration_chain!(FrontendConfigV0 = 0, FrontendConfigV1 = 1);
+// Arrays of tables have to come after every plain key, so they're written together at the end
+#[derive(Serialize)]
+struct AlertTables<'a> {
+    #[serde(skip_serializing_if = "<[_]>::is_empty")]
+    alert_rules: &'a [AlertRule],
+    #[serde(skip_serializing_if = "<[_]>::is_empty")]
+    alert_channels: &'a [AlertChannel],
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AlertMetric {
+    Cpu,
+    Temperature,
+    Ram,
+    Swap,
+    Disk,
+    ServiceFailed,
+    BackendOffline,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AlertRule {
+    pub name: String,
+    pub metric: AlertMetric,
+    #[serde(default)]
+    pub threshold: f32,
+    #[serde(default)]
+    pub duration: u64,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub backends: Vec<IpAddr>,
+}
+
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SmtpEncryption {
+    #[default]
+    Starttls,
+    Tls,
+    None,
+}
+
+fn default_smtp_port() -> u16 {
+    587
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum AlertChannel {
+    Webhook {
+        url: String,
+    },
+    Smtp {
+        server: String,
+        #[serde(default = "default_smtp_port")]
+        port: u16,
+        #[serde(default)]
+        encryption: SmtpEncryption,
+        #[serde(default)]
+        username: String,
+        #[serde(default)]
+        password: String,
+        from: String,
+        to: Vec<String>,
+    },
+    Command {
+        command: String,
+        #[serde(default)]
+        args: Vec<String>,
+    },
+}
+
+fn default_alert_rules() -> Vec<AlertRule> {
+    vec![
+        AlertRule {
+            name: "High temperature".into(),
+            metric: AlertMetric::Temperature,
+            threshold: 75.,
+            duration: 300,
+            backends: Vec::new(),
+        },
+        AlertRule {
+            name: "Disk almost full".into(),
+            metric: AlertMetric::Disk,
+            threshold: 90.,
+            duration: 0,
+            backends: Vec::new(),
+        },
+        AlertRule {
+            name: "Service failed".into(),
+            metric: AlertMetric::ServiceFailed,
+            threshold: 0.,
+            duration: 0,
+            backends: Vec::new(),
+        },
+        AlertRule {
+            name: "Backend disconnected".into(),
+            metric: AlertMetric::BackendOffline,
+            threshold: 0.,
+            duration: 60,
+            backends: Vec::new(),
+        },
+    ]
+}
+
+fn default_backend_group_tag() -> String {
+    "site".into()
+}
+
+fn default_session_remember_max_age() -> u64 {
+    7776000
+}
+
+fn default_audit_log_files() -> usize {
+    10
+}
+
+build_migration_chain!(
+    FrontendConfigV0 = 0,
+    FrontendConfigV1 = 1,
+    FrontendConfigV2 = 2
+);
+
+#[derive(Deserialize)]
+pub struct FrontendConfigV2 {
+    pub http_port: u16,
+    pub http_subnet: IpAddr,
+    pub backend_port: u16,
+    pub backend_subnet: IpAddr,
+    pub log_level: LevelFilter,
+    pub enable_tls: bool,
+    pub cert_path: PathBuf,
+    pub key_path: PathBuf,
+    pub enable_login: bool,
+    pub hash: String,
+    pub session_idle_timeout: u64,
+    pub session_absolute_timeout: u64,
+    pub session_remember_timeout: u64,
+    #[serde(default = "default_session_remember_max_age")]
+    pub session_remember_max_age: u64,
+    pub secret: HexArray<32>,
+    #[serde(default = "default_backend_group_tag")]
+    pub backend_group_tag: String,
+    #[serde(default = "default_audit_log_files")]
+    pub audit_log_files: usize,
+    #[serde(default)]
+    pub alert_rules: Vec<AlertRule>,
+    #[serde(default)]
+    pub alert_channels: Vec<AlertChannel>,
+}
+
+impl Default for FrontendConfigV2 {
+    fn default() -> Self {
+        Self {
+            http_port: 5252,
+            http_subnet: IpAddr::V6(Ipv6Addr::UNSPECIFIED),
+            backend_port: 5253,
+            backend_subnet: IpAddr::V6(Ipv6Addr::UNSPECIFIED),
+            log_level: LevelFilter::Info,
+            enable_tls: false,
+            cert_path: PathBuf::new(),
+            key_path: PathBuf::new(),
+            enable_login: false,
+            hash: String::new(),
+            session_idle_timeout: 3600,
+            session_absolute_timeout: 86400,
+            session_remember_timeout: 2592000,
+            session_remember_max_age: default_session_remember_max_age(),
+            secret: HexArray(rand::random()),
+            backend_group_tag: default_backend_group_tag(),
+            audit_log_files: default_audit_log_files(),
+            alert_rules: default_alert_rules(),
+            alert_channels: Vec::new(),
+        }
+    }
+}
+
+impl From<FrontendConfigV1> for FrontendConfigV2 {
+    fn from(val: FrontendConfigV1) -> Self {
+        let default = Self::default();
+
+        Self {
+            http_port: val.http_port,
+            http_subnet: val.http_subnet,
+            backend_port: val.backend_port,
+            backend_subnet: val.backend_subnet,
+            log_level: val.log_level,
+            enable_tls: val.enable_tls,
+            cert_path: val.cert_path,
+            key_path: val.key_path,
+            enable_login: val.enable_login,
+            hash: val.hash,
+            session_idle_timeout: default.session_idle_timeout,
+            session_absolute_timeout: default.session_absolute_timeout,
+            session_remember_timeout: default.session_remember_timeout,
+            session_remember_max_age: default.session_remember_max_age,
+            secret: val.secret,
+            backend_group_tag: default.backend_group_tag,
+            audit_log_files: default.audit_log_files,
+            alert_rules: default.alert_rules,
+            alert_channels: default.alert_channels,
+        }
+    }
+}
 
 #[derive(Deserialize)]
 pub struct FrontendConfigV1 {
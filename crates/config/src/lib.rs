@@ -11,7 +11,7 @@ pub mod frontend;
 mod custom_serde;
 
 pub const APP_VERSION: &str = env!("CARGO_PKG_VERSION");
-pub const PROTOCOL_VERSION: u32 = 1;
+pub const PROTOCOL_VERSION: u32 = 2;
 
 macro_rules! generate_config_file {
     ($template:literal, $($key:ident = $val:expr),*) => {{
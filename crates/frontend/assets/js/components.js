@@ -67,6 +67,31 @@ async function getUpdateMessage(oldVersion) {
         }
     );
 
+    // Read-only terminal for output from long-running commands, so progress bars and colors show up properly
+    customElements.define(
+        "command-output",
+        class extends HTMLElement {
+            connectedCallback() {
+                const term = new Terminal({ convertEol: true, disableStdin: true });
+
+                const fitAddon = new FitAddon.FitAddon();
+                term.loadAddon(fitAddon);
+
+                term.open(this);
+
+                fitAddon.fit();
+                window.addEventListener("resize", () => fitAddon.fit(), 50);
+
+                fetch(this.dataset.src).then(async (res) => {
+                    for await (const chunk of res.body) {
+                        term.write(chunk);
+                    }
+                    this.dispatchEvent(new Event("done"));
+                });
+            }
+        }
+    );
+
     customElements.define(
         "code-editor",
         class extends HTMLElement {
@@ -125,7 +150,6 @@ async function getUpdateMessage(oldVersion) {
             memory_label: "Memory",
             swap_label: "Swap",
             peak_disk: "Peak Disk",
-            pressure_monitor: "Pressure monitor",
             cpu_statistics: "CPU Statistics",
             memory_usage: "Memory Usage",
             disk_usage: "Disk Usage",
@@ -211,6 +235,166 @@ async function getUpdateMessage(oldVersion) {
             paused: "Paused",
             sleeping: "Sleeping",
             other: "Other",
+            remember_me: "Remember me",
+            active_sessions: "Active Sessions",
+            session: "Session",
+            source_address: "Source Address",
+            user_agent: "User Agent",
+            created: "Created",
+            last_active: "Last Active",
+            current_session: "Current",
+            remembered: "Remembered",
+            revoke: "Revoke",
+            nav_audit_log: "Audit Log",
+            audit_log_title: "Audit Log",
+            action: "Action",
+            all: "All",
+            search: "Search",
+            filter: "Filter",
+            export_jsonl: "Export JSON Lines",
+            time: "Time",
+            arguments: "Arguments",
+            result: "Result",
+            no_audit_entries: "No audit entries",
+            api_tokens: "API Tokens",
+            scope: "Scope",
+            scope_read: "Read",
+            scope_write: "Read and write",
+            last_used: "Last Used",
+            never: "Never",
+            no_api_tokens: "No API tokens",
+            create_token: "Create Token",
+            api_token_created: "API Token Created",
+            api_token_notice: "Copy this token now, it won't be shown again.",
+            back: "Back",
+            time_range: "Range",
+            nav_fleet: "Fleet",
+            fleet_overview: "Fleet Overview",
+            online: "Online",
+            offline: "Offline",
+            unreachable: "Unreachable",
+            forget: "Forget",
+            update_available: "Update Available",
+            sort_by: "Sort By",
+            reverse_order: "Reverse",
+            no_backends: "No backends",
+            tag: "Tag",
+            nav_commands: "Commands",
+            command_runner: "Command Runner",
+            backends: "Backends",
+            none: "None",
+            command: "Command",
+            timeout_seconds: "Timeout (seconds)",
+            run: "Run",
+            running_command: "Running...",
+            succeeded: "Succeeded",
+            timed_out: "Timed Out",
+            command_results: "Results",
+            command_history: "History",
+            no_command_runs: "No commands have been run",
+            view: "View",
+            queued: "Queued",
+            command_queued: "Backend was offline, the command will run when it reconnects",
+            command_skipped: "Backend was offline, the command was skipped",
+            blocked: "Blocked by Policy",
+            command_blocked: "The backend's command_policy doesn't allow this command",
+            nav_jobs: "Jobs",
+            scheduled_jobs: "Scheduled Jobs",
+            schedule: "Schedule",
+            next_run: "Next Run",
+            last_run: "Last Run",
+            enabled: "Enabled",
+            disabled: "Disabled",
+            enable: "Enable",
+            disable: "Disable",
+            run_now: "Run Now",
+            no_jobs: "No jobs have been scheduled",
+            new_job: "New Job",
+            when_offline: "When Offline",
+            skip_run: "Skip",
+            queue_run: "Run on reconnect",
+            create_job: "Create Job",
+            schedule_hint:
+                "Schedules use cron syntax (minute, hour, day of month, month, day of week) in the dashboard's local time.",
+            queued_backends: "Waiting for reconnect:",
+            no_job_runs: "This job hasn't run yet",
+            recent_operations: "Recent Operations",
+            no_operations: "No operations have been run",
+            error_output: "Error Output",
+            temperature_sensors: "Temperature Sensors",
+            primary_sensor: "Primary",
+            network_interfaces: "Network Interfaces",
+            interface: "Interface",
+            state: "State",
+            mac_address: "MAC Address",
+            addresses: "Addresses",
+            rate: "Rate",
+            total: "Total",
+            packets: "Packets",
+            errors: "Errors",
+            drops: "Drops",
+            no_interfaces: "No network interfaces match this backend's filter",
+            disk_io: "Disk I/O",
+            device: "Device",
+            read: "Read",
+            written: "Written",
+            iops: "IOPS",
+            busy: "Busy",
+            no_disk_io: "No block devices found",
+            removable: "Removable",
+            read_only: "Read-only",
+            pressure_stall: "Pressure Stall",
+            pressure_hint: "Share of time tasks were stalled over 10s / 60s / 300s",
+            available: "Available",
+            free: "Free",
+            buffers: "Buffers",
+            cached: "Cached",
+            shared: "Shared",
+            dirty: "Dirty",
+            swap_in_out: "Swap In / Out",
+            apps: "Apps",
+            memory_breakdown_graph: "Memory Breakdown",
+            hardware_health: "Hardware Health",
+            board_model: "Board Model",
+            serial_number: "Serial Number",
+            board_revision: "Revision",
+            arm_memory: "ARM Memory",
+            gpu_memory: "GPU Memory",
+            no_throttling: "No under-voltage or throttling since boot",
+            under_voltage_now: "Under-voltage detected",
+            throttled_now: "CPU is throttled",
+            freq_capped_now: "ARM frequency is capped",
+            soft_temp_limit_now: "Soft temperature limit is active",
+            under_voltage_occurred: "Under-voltage has occurred since boot",
+            throttled_occurred: "Throttling has occurred since boot",
+            freq_capped_occurred: "ARM frequency capping has occurred since boot",
+            soft_temp_limit_occurred: "Soft temperature limit has been reached since boot",
+            command_line: "Command Line",
+            executable: "Executable",
+            working_directory: "Working Directory",
+            user: "User",
+            group: "Group",
+            parent_pid: "Parent PID",
+            threads: "Threads",
+            nice_value: "Nice Value",
+            open_fds: "Open File Descriptors",
+            cgroup: "Cgroup",
+            listening_sockets: "Listening Sockets",
+            no_listening_sockets: "No listening sockets",
+            protocol: "Protocol",
+            address: "Address",
+            environment: "Environment",
+            show_environment: "Show Environment",
+            environment_needs_login: "The environment is only shown when login is enabled",
+            not_readable: "Not readable",
+            view_flat: "Flat",
+            view_tree: "Tree",
+            toggle_children: "Collapse or expand children",
+            including_children: "Including child processes",
+            process_search_placeholder: "Name or command line",
+            regex: "Regex",
+            invalid_regex: "Invalid regex",
+            io: "I/O",
         },
         zh: {
             app_name: "DietPi 仪表盘",
@@ -240,7 +424,6 @@ async function getUpdateMessage(oldVersion) {
             memory_label: "内存",
             swap_label: "交换分区",
             peak_disk: "峰值磁盘",
-            pressure_monitor: "压力监测",
             cpu_statistics: "CPU 统计",
             memory_usage: "内存使用",
             disk_usage: "磁盘使用",
@@ -326,6 +509,165 @@ async function getUpdateMessage(oldVersion) {
             paused: "已暂停",
             sleeping: "休眠",
             other: "其他",
+            remember_me: "记住我",
+            active_sessions: "活动会话",
+            session: "会话",
+            source_address: "来源地址",
+            user_agent: "用户代理",
+            created: "创建时间",
+            last_active: "最近活动",
+            current_session: "当前",
+            remembered: "已记住",
+            revoke: "撤销",
+            nav_audit_log: "审计日志",
+            audit_log_title: "审计日志",
+            action: "操作",
+            all: "全部",
+            search: "搜索",
+            filter: "筛选",
+            export_jsonl: "导出 JSON Lines",
+            time: "时间",
+            arguments: "参数",
+            result: "结果",
+            no_audit_entries: "暂无审计记录",
+            api_tokens: "API 令牌",
+            scope: "权限范围",
+            scope_read: "只读",
+            scope_write: "读写",
+            last_used: "最后使用",
+            never: "从未",
+            no_api_tokens: "暂无 API 令牌",
+            create_token: "创建令牌",
+            api_token_created: "API 令牌已创建",
+            api_token_notice: "请立即复制此令牌，之后将不再显示。",
+            back: "返回",
+            time_range: "时间范围",
+            nav_fleet: "设备群",
+            fleet_overview: "设备总览",
+            online: "在线",
+            offline: "离线",
+            unreachable: "无响应",
+            forget: "移除",
+            update_available: "有可用更新",
+            sort_by: "排序方式",
+            reverse_order: "倒序",
+            no_backends: "暂无后端",
+            tag: "标签",
+            nav_commands: "命令",
+            command_runner: "批量命令",
+            backends: "后端",
+            none: "无",
+            command: "命令",
+            timeout_seconds: "超时（秒）",
+            run: "运行",
+            running_command: "运行中...",
+            succeeded: "成功",
+            timed_out: "超时",
+            command_results: "结果",
+            command_history: "历史记录",
+            no_command_runs: "尚未运行过命令",
+            view: "查看",
+            queued: "已排队",
+            command_queued: "后端离线，命令将在其重新连接后运行",
+            command_skipped: "后端离线，已跳过该命令",
+            blocked: "被策略阻止",
+            command_blocked: "后端的 command_policy 不允许此命令",
+            nav_jobs: "计划任务",
+            scheduled_jobs: "计划任务",
+            schedule: "计划",
+            next_run: "下次运行",
+            last_run: "上次运行",
+            enabled: "已启用",
+            disabled: "已禁用",
+            enable: "启用",
+            disable: "禁用",
+            run_now: "立即运行",
+            no_jobs: "尚未创建计划任务",
+            new_job: "新建任务",
+            when_offline: "离线时",
+            skip_run: "跳过",
+            queue_run: "重新连接后运行",
+            create_job: "创建任务",
+            schedule_hint: "计划使用 cron 语法（分钟、小时、日、月、星期），按仪表盘所在机器的本地时间执行。",
+            queued_backends: "等待重新连接：",
+            no_job_runs: "该任务尚未运行",
+            recent_operations: "最近的操作",
+            no_operations: "尚未运行过任何操作",
+            error_output: "错误输出",
+            temperature_sensors: "温度传感器",
+            primary_sensor: "主传感器",
+            network_interfaces: "网络接口",
+            interface: "接口",
+            state: "状态",
+            mac_address: "MAC 地址",
+            addresses: "地址",
+            rate: "速率",
+            total: "总计",
+            packets: "数据包",
+            errors: "错误",
+            drops: "丢包",
+            no_interfaces: "没有符合此后端过滤条件的网络接口",
+            disk_io: "磁盘 I/O",
+            device: "设备",
+            read: "读取",
+            written: "写入",
+            iops: "IOPS",
+            busy: "繁忙",
+            no_disk_io: "未找到块设备",
+            removable: "可移动",
+            read_only: "只读",
+            pressure_stall: "压力阻塞",
+            pressure_hint: "任务在 10 秒 / 60 秒 / 300 秒内被阻塞的时间占比",
+            available: "可用",
+            free: "空闲",
+            buffers: "缓冲区",
+            cached: "缓存",
+            shared: "共享",
+            dirty: "脏页",
+            swap_in_out: "换入 / 换出",
+            apps: "应用",
+            memory_breakdown_graph: "内存构成",
+            hardware_health: "硬件健康",
+            board_model: "主板型号",
+            serial_number: "序列号",
+            board_revision: "版本",
+            arm_memory: "ARM 内存",
+            gpu_memory: "GPU 内存",
+            no_throttling: "自启动以来未出现欠压或降频",
+            under_voltage_now: "检测到欠压",
+            throttled_now: "CPU 正在降频",
+            freq_capped_now: "ARM 频率受限",
+            soft_temp_limit_now: "已触发软温度限制",
+            under_voltage_occurred: "自启动以来曾出现欠压",
+            throttled_occurred: "自启动以来曾出现降频",
+            freq_capped_occurred: "自启动以来 ARM 频率曾受限",
+            soft_temp_limit_occurred: "自启动以来曾触发软温度限制",
+            command_line: "命令行",
+            executable: "可执行文件",
+            working_directory: "工作目录",
+            user: "用户",
+            group: "用户组",
+            parent_pid: "父进程号",
+            threads: "线程数",
+            nice_value: "Nice 值",
+            open_fds: "打开的文件描述符",
+            cgroup: "Cgroup",
+            listening_sockets: "监听套接字",
+            no_listening_sockets: "没有监听套接字",
+            protocol: "协议",
+            address: "地址",
+            environment: "环境变量",
+            show_environment: "显示环境变量",
+            environment_needs_login: "仅在启用登录时显示环境变量",
+            not_readable: "无法读取",
+            view_flat: "列表",
+            view_tree: "树形",
+            toggle_children: "折叠或展开子进程",
+            including_children: "包含子进程",
+            process_search_placeholder: "名称或命令行",
+            regex: "正则表达式",
+            invalid_regex: "无效的正则表达式",
+            io: "I/O",
         },
     };
 
@@ -344,6 +686,23 @@ async function getUpdateMessage(oldVersion) {
             disk_usage_value: ({ name = "", mount = "", value = "--" }) =>
                 `${name} (${mount}): ${value}`,
             new_version_available: ({ version = "" }) => `New version available: ${version}`,
+            time_ago: ({ value = "" }) => `${value} ago`,
+            alert_firing: ({ rule = "", target = "", value = "" }) =>
+                `Alert: ${rule} on ${target} ${value}`,
+            fleet_summary: ({ online = 0, total = 0 }) => `${online} of ${total} backends online`,
+            last_seen: ({ time = "" }) => `Last seen ${time}`,
+            command_run_summary: ({ succeeded = 0, total = 0 }) => `${succeeded} of ${total} succeeded`,
+            exit_code_value: ({ value = "--" }) => `Exit code: ${value}`,
+            output_variant: ({ variant = "" }) => `Output ${variant}`,
+            command_timed_out: ({ seconds = 0 }) => `No response within ${seconds} seconds`,
+            killed_by_signal: ({ signal = "--" }) => `Killed by signal ${signal}`,
+            sensor_limits: ({ max = "--", critical = "--" }) => `Max: ${max}, Critical: ${critical}`,
+            interface_graph: ({ name = "" }) => `Network Graph: ${name}`,
+            disk_io_graph: ({ name = "" }) => `Disk I/O Graph: ${name}`,
+            inode_usage: ({ value = "--" }) => `Inodes: ${value}`,
+            load_average_value: ({ value = "--" }) => `Load Average: ${value}`,
+            pressure_full: ({ value = "--" }) => `(full: ${value})`,
+            memory_pressure_value: ({ value = "--" }) => `Memory pressure: ${value}`,
         },
         zh: {
             process_summary: ({ start = 0, end = 0, total = 0 }) =>
@@ -359,6 +718,23 @@ async function getUpdateMessage(oldVersion) {
             disk_usage_value: ({ name = "", mount = "", value = "--" }) =>
                 `${name}（${mount}）：${value}`,
             new_version_available: ({ version = "" }) => `发现新版本：${version}`,
+            time_ago: ({ value = "" }) => `${value}前`,
+            alert_firing: ({ rule = "", target = "", value = "" }) =>
+                `告警：${target} ${rule} ${value}`,
+            fleet_summary: ({ online = 0, total = 0 }) => `${total} 个后端中 ${online} 个在线`,
+            last_seen: ({ time = "" }) => `最后在线 ${time}`,
+            command_run_summary: ({ succeeded = 0, total = 0 }) => `${total} 个中 ${succeeded} 个成功`,
+            exit_code_value: ({ value = "--" }) => `退出码：${value}`,
+            output_variant: ({ variant = "" }) => `输出 ${variant}`,
+            command_timed_out: ({ seconds = 0 }) => `${seconds} 秒内无响应`,
+            killed_by_signal: ({ signal = "--" }) => `被信号 ${signal} 终止`,
+            sensor_limits: ({ max = "--", critical = "--" }) => `最高：${max}，临界：${critical}`,
+            interface_graph: ({ name = "" }) => `网络图表：${name}`,
+            disk_io_graph: ({ name = "" }) => `磁盘 I/O 图表：${name}`,
+            inode_usage: ({ value = "--" }) => `索引节点：${value}`,
+            load_average_value: ({ value = "--" }) => `平均负载：${value}`,
+            pressure_full: ({ value = "--" }) => `（完全：${value}）`,
+            memory_pressure_value: ({ value = "--" }) => `内存压力：${value}`,
         },
     };
 
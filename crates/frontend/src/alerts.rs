@@ -0,0 +1,667 @@
+use std::{
+    collections::{HashMap, HashSet},
+    net::IpAddr,
+    ops::DerefMut,
+    sync::{Arc, LazyLock, Mutex},
+    time::Duration,
+};
+
+use anyhow::{Context, Result, anyhow};
+use config::frontend::{AlertChannel, AlertMetric, AlertRule, SmtpEncryption};
+use futures_util::future::join_all;
+use http_body_util::Full;
+use hyper::{Request, StatusCode, Uri, body::Bytes, header};
+use hyper_util::rt::TokioIo;
+use lettre::{
+    AsyncSmtpTransport, AsyncTransport, Message, Tokio1Executor, message::header::ContentType,
+    transport::smtp::authentication::Credentials,
+};
+use log::{debug, error, info};
+use proto::{
+    backend::{
+        CpuResponse, DiskResponse, MemResponse, ResponseBackendMessage, ServiceResponse,
+        ServiceStatus, TempResponse,
+    },
+    frontend::RequestFrontendMessage,
+};
+use serde::Serialize;
+use tokio::{
+    io::{AsyncRead, AsyncWrite},
+    net::TcpStream,
+    process::Command,
+    time::MissedTickBehavior,
+};
+use tokio_rustls::{
+    TlsConnector,
+    rustls::{self, ClientConfig, RootCertStore, pki_types::ServerName},
+};
+
+use crate::{
+    SharedConfig,
+    backend::{BackendHandle, SharedBackendRegistry},
+    http::auth::now_secs,
+};
+
+const CHECK_INTERVAL: Duration = Duration::from_secs(10);
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(5);
+const NOTIFY_TIMEOUT: Duration = Duration::from_secs(30);
+
+fn format_value(metric: AlertMetric, value: Option<f32>) -> String {
+    match (metric, value) {
+        (AlertMetric::Temperature, Some(value)) => format!("{value:.1}ºC"),
+        (_, Some(value)) => format!("{value:.1}%"),
+        (_, None) => String::new(),
+    }
+}
+
+fn percent(used: u64, total: u64) -> f32 {
+    if total == 0 {
+        0.
+    } else {
+        used as f32 / total as f32 * 100.
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Alert {
+    pub rule: String,
+    pub metric: AlertMetric,
+    pub threshold: f32,
+    pub backend: IpAddr,
+    pub nickname: String,
+    // Disk mount point or service name, for rules that can match more than once per backend
+    pub subject: String,
+    pub value: Option<f32>,
+    pub since: u64,
+    pub firing: bool,
+}
+
+impl Alert {
+    pub fn target(&self) -> String {
+        if self.subject.is_empty() {
+            self.nickname.clone()
+        } else {
+            format!("{} ({})", self.nickname, self.subject)
+        }
+    }
+
+    pub fn value_display(&self) -> String {
+        format_value(self.metric, self.value)
+    }
+}
+
+#[derive(Debug, Clone, Copy, Serialize)]
+#[serde(rename_all = "lowercase")]
+enum AlertStatus {
+    Firing,
+    Resolved,
+}
+
+#[derive(Debug, Clone, Serialize)]
+struct Notification {
+    status: AlertStatus,
+    rule: String,
+    metric: AlertMetric,
+    threshold: f32,
+    backend: IpAddr,
+    nickname: String,
+    subject: String,
+    value: Option<f32>,
+    since: u64,
+    time: u64,
+}
+
+impl Notification {
+    fn new(status: AlertStatus, alert: &Alert, time: u64) -> Self {
+        Self {
+            status,
+            rule: alert.rule.clone(),
+            metric: alert.metric,
+            threshold: alert.threshold,
+            backend: alert.backend,
+            nickname: alert.nickname.clone(),
+            subject: alert.subject.clone(),
+            value: alert.value,
+            since: alert.since,
+            time,
+        }
+    }
+
+    fn status_str(&self) -> &'static str {
+        match self.status {
+            AlertStatus::Firing => "FIRING",
+            AlertStatus::Resolved => "RESOLVED",
+        }
+    }
+
+    fn summary(&self) -> String {
+        let mut summary = format!(
+            "[{}] {} on {} ({})",
+            self.status_str(),
+            self.rule,
+            self.nickname,
+            self.backend
+        );
+
+        if !self.subject.is_empty() {
+            summary.push_str(&format!(": {}", self.subject));
+        }
+
+        let value = format_value(self.metric, self.value);
+        if !value.is_empty() {
+            summary.push_str(&format!(" is {value}"));
+        }
+
+        summary
+    }
+}
+
+// A rule's condition holding for a backend at the time of the check
+struct Observation {
+    rule: usize,
+    backend: IpAddr,
+    nickname: String,
+    subject: String,
+    value: Option<f32>,
+}
+
+#[derive(Default)]
+struct BackendSnapshot {
+    cpu: Option<CpuResponse>,
+    temp: Option<TempResponse>,
+    mem: Option<MemResponse>,
+    disk: Option<DiskResponse>,
+    services: Option<ServiceResponse>,
+}
+
+fn applies_to(rule: &AlertRule, addr: IpAddr) -> bool {
+    rule.backends.is_empty() || rule.backends.contains(&addr)
+}
+
+// Returns the subject and value of everything matching the rule
+fn evaluate(rule: &AlertRule, data: &BackendSnapshot) -> Vec<(String, Option<f32>)> {
+    let above = |value: f32| value > rule.threshold;
+
+    match rule.metric {
+        AlertMetric::Cpu => data
+            .cpu
+            .iter()
+            .map(|x| x.global_cpu)
+            .filter(|&x| above(x))
+            .map(|x| (String::new(), Some(x)))
+            .collect(),
+        AlertMetric::Temperature => data
+            .temp
+            .iter()
+            .filter_map(|x| x.temp)
+            .filter(|&x| above(x))
+            .map(|x| (String::new(), Some(x)))
+            .collect(),
+        AlertMetric::Ram => data
+            .mem
+            .iter()
+            .map(|x| percent(x.ram.used, x.ram.total))
+            .filter(|&x| above(x))
+            .map(|x| (String::new(), Some(x)))
+            .collect(),
+        AlertMetric::Swap => data
+            .mem
+            .iter()
+            .map(|x| percent(x.swap.used, x.swap.total))
+            .filter(|&x| above(x))
+            .map(|x| (String::new(), Some(x)))
+            .collect(),
+        AlertMetric::Disk => data
+            .disk
+            .iter()
+            .flat_map(|x| &x.disks)
+            .map(|x| (x.mnt_point.clone(), percent(x.usage.used, x.usage.total)))
+            .filter(|&(_, x)| above(x))
+            .map(|(mnt, x)| (mnt, Some(x)))
+            .collect(),
+        AlertMetric::ServiceFailed => data
+            .services
+            .iter()
+            .flat_map(|x| &x.services)
+            .filter(|x| matches!(x.status, ServiceStatus::Failed))
+            .map(|x| (x.name.clone(), None))
+            .collect(),
+        // Checked separately, since there's no data to go off of
+        AlertMetric::BackendOffline => Vec::new(),
+    }
+}
+
+async fn fetch(handle: &BackendHandle, metrics: &HashSet<AlertMetric>) -> Result<BackendSnapshot> {
+    let mut data = BackendSnapshot::default();
+
+    let needs = |metric| metrics.contains(&metric);
+
+    let requests = [
+        (needs(AlertMetric::Cpu), RequestFrontendMessage::Cpu),
+        (
+            needs(AlertMetric::Temperature),
+            RequestFrontendMessage::Temp,
+        ),
+        (
+            needs(AlertMetric::Ram) || needs(AlertMetric::Swap),
+            RequestFrontendMessage::Mem,
+        ),
+        (needs(AlertMetric::Disk), RequestFrontendMessage::Disk),
+        (
+            needs(AlertMetric::ServiceFailed),
+            RequestFrontendMessage::Services,
+        ),
+    ];
+
+    for (needed, req) in requests {
+        if !needed {
+            continue;
+        }
+
+        let resp = tokio::time::timeout(REQUEST_TIMEOUT, handle.send_req(req))
+            .await
+            .context("backend took too long to respond")??;
+
+        match resp {
+            ResponseBackendMessage::Cpu(resp) => data.cpu = Some(resp),
+            ResponseBackendMessage::Temp(resp) => data.temp = Some(resp),
+            ResponseBackendMessage::Mem(resp) => data.mem = Some(resp),
+            ResponseBackendMessage::Disk(resp) => data.disk = Some(resp),
+            ResponseBackendMessage::Services(resp) => data.services = Some(resp),
+            _ => return Err(anyhow!("backend sent mismatched response")),
+        }
+    }
+
+    Ok(data)
+}
+
+#[derive(Default)]
+pub struct AlertState {
+    alerts: HashMap<(usize, IpAddr, String), Alert>,
+}
+
+impl AlertState {
+    fn update(
+        &mut self,
+        rules: &[AlertRule],
+        now: u64,
+        observations: Vec<Observation>,
+        checked: &HashSet<(usize, IpAddr)>,
+    ) -> Vec<Notification> {
+        let mut notifications = Vec::new();
+        let mut active = HashSet::new();
+
+        for obs in observations {
+            let rule = &rules[obs.rule];
+            let key = (obs.rule, obs.backend, obs.subject.clone());
+
+            let alert = self.alerts.entry(key.clone()).or_insert_with(|| Alert {
+                rule: rule.name.clone(),
+                metric: rule.metric,
+                threshold: rule.threshold,
+                backend: obs.backend,
+                nickname: obs.nickname.clone(),
+                subject: obs.subject,
+                value: None,
+                since: now,
+                firing: false,
+            });
+
+            alert.value = obs.value;
+            alert.nickname = obs.nickname;
+
+            if !alert.firing && now.saturating_sub(alert.since) >= rule.duration {
+                alert.firing = true;
+                notifications.push(Notification::new(AlertStatus::Firing, alert, now));
+            }
+
+            active.insert(key);
+        }
+
+        // Backends that couldn't be checked keep their alerts as they were
+        self.alerts.retain(|key, alert| {
+            let resolved = checked.contains(&(key.0, key.1)) && !active.contains(key);
+
+            if resolved && alert.firing {
+                notifications.push(Notification::new(AlertStatus::Resolved, alert, now));
+            }
+
+            !resolved
+        });
+
+        notifications
+    }
+
+    pub fn firing(&self) -> Vec<Alert> {
+        let mut firing: Vec<_> = self.alerts.values().filter(|x| x.firing).cloned().collect();
+        firing.sort_by_key(|x| x.since);
+
+        firing
+    }
+}
+
+#[derive(Clone)]
+pub struct SharedAlerts(Arc<Mutex<AlertState>>);
+
+impl SharedAlerts {
+    pub fn new() -> Self {
+        Self(Arc::new(Mutex::new(AlertState::default())))
+    }
+
+    pub fn get(&self) -> impl DerefMut<Target = AlertState> {
+        self.0.lock().unwrap()
+    }
+
+    pub async fn run(self, config: SharedConfig, registry: SharedBackendRegistry) {
+        let rules = &config.alert_rules;
+
+        if rules.is_empty() {
+            return;
+        }
+
+        let mut interval = tokio::time::interval(CHECK_INTERVAL);
+        interval.set_missed_tick_behavior(MissedTickBehavior::Skip);
+
+        loop {
+            interval.tick().await;
+
+            let (backends, offline) = {
+                let registry = registry.lock().unwrap();
+
+                let backends: Vec<_> = registry
+                    .connected()
+                    .iter()
+                    .map(|(addr, info)| (*addr, info.nickname.clone(), info.handle.clone()))
+                    .collect();
+
+                let offline: Vec<_> = registry
+                    .offline()
+                    .map(|(addr, known)| (*addr, known.nickname.clone()))
+                    .collect();
+
+                (backends, offline)
+            };
+
+            let snapshots = join_all(backends.iter().map(async |(addr, _, handle)| {
+                let metrics: HashSet<_> = rules
+                    .iter()
+                    .filter(|x| applies_to(x, *addr))
+                    .map(|x| x.metric)
+                    .collect();
+
+                fetch(handle, &metrics).await
+            }))
+            .await;
+
+            let mut observations = Vec::new();
+            let mut checked = HashSet::new();
+
+            for ((addr, nickname, _), snapshot) in backends.iter().zip(snapshots) {
+                let snapshot = match snapshot {
+                    Ok(snapshot) => snapshot,
+                    Err(err) => {
+                        debug!("Failed to check alerts for backend {addr}: {err:#}");
+                        continue;
+                    }
+                };
+
+                for (idx, rule) in rules.iter().enumerate() {
+                    if !applies_to(rule, *addr) || rule.metric == AlertMetric::BackendOffline {
+                        continue;
+                    }
+
+                    checked.insert((idx, *addr));
+
+                    for (subject, value) in evaluate(rule, &snapshot) {
+                        observations.push(Observation {
+                            rule: idx,
+                            backend: *addr,
+                            nickname: nickname.clone(),
+                            subject,
+                            value,
+                        });
+                    }
+                }
+            }
+
+            for (idx, rule) in rules.iter().enumerate() {
+                if rule.metric != AlertMetric::BackendOffline {
+                    continue;
+                }
+
+                // Connected backends are checked too, so their offline alerts resolve
+                for (addr, _, _) in &backends {
+                    if applies_to(rule, *addr) {
+                        checked.insert((idx, *addr));
+                    }
+                }
+
+                for (addr, nickname) in &offline {
+                    if !applies_to(rule, *addr) {
+                        continue;
+                    }
+
+                    checked.insert((idx, *addr));
+
+                    observations.push(Observation {
+                        rule: idx,
+                        backend: *addr,
+                        nickname: nickname.clone(),
+                        subject: String::new(),
+                        value: None,
+                    });
+                }
+            }
+
+            let mut state = self.get();
+
+            // Alerts for backends that were forgotten go away with them
+            state.alerts.retain(|key, _| {
+                backends.iter().any(|x| x.0 == key.1) || offline.iter().any(|x| x.0 == key.1)
+            });
+
+            let notifications = state.update(rules, now_secs(), observations, &checked);
+            drop(state);
+
+            for notification in notifications {
+                info!("Alert {}", notification.summary());
+
+                // Sending can take a while, and shouldn't hold up the next check
+                tokio::spawn(notify(config.clone(), notification));
+            }
+        }
+    }
+}
+
+async fn notify(config: SharedConfig, notification: Notification) {
+    for channel in &config.alert_channels {
+        let result = match channel {
+            AlertChannel::Webhook { url } => send_webhook(url, &notification).await,
+            AlertChannel::Smtp {
+                server,
+                port,
+                encryption,
+                username,
+                password,
+                from,
+                to,
+            } => {
+                let transport = match encryption {
+                    SmtpEncryption::Starttls => {
+                        AsyncSmtpTransport::<Tokio1Executor>::starttls_relay(server)
+                    }
+                    SmtpEncryption::Tls => AsyncSmtpTransport::<Tokio1Executor>::relay(server),
+                    SmtpEncryption::None => Ok(
+                        AsyncSmtpTransport::<Tokio1Executor>::builder_dangerous(server),
+                    ),
+                };
+
+                match transport {
+                    Ok(transport) => {
+                        let mut transport = transport.port(*port);
+                        if !username.is_empty() {
+                            transport = transport
+                                .credentials(Credentials::new(username.clone(), password.clone()));
+                        }
+
+                        send_email(transport.build(), from, to, &notification).await
+                    }
+                    Err(err) => Err(err).context("invalid smtp server"),
+                }
+            }
+            AlertChannel::Command { command, args } => {
+                run_command(command, args, &notification).await
+            }
+        };
+
+        if let Err(err) = result {
+            error!("Failed to send alert notification: {err:#}");
+        }
+    }
+}
+
+// Built once, loading the root certificates isn't free
+static TLS_CONNECTOR: LazyLock<TlsConnector> = LazyLock::new(|| {
+    let roots = RootCertStore {
+        roots: webpki_roots::TLS_SERVER_ROOTS.into(),
+    };
+    let config =
+        ClientConfig::builder_with_provider(Arc::new(rustls::crypto::ring::default_provider()))
+            .with_safe_default_protocol_versions()
+            .expect("ring supports the default protocol versions")
+            .with_root_certificates(roots)
+            .with_no_client_auth();
+
+    TlsConnector::from(Arc::new(config))
+});
+
+async fn send_webhook(url: &str, notification: &Notification) -> Result<()> {
+    let uri: Uri = url.parse().context("invalid webhook url")?;
+    let https = match uri.scheme_str() {
+        Some("https") => true,
+        Some("http") => false,
+        _ => return Err(anyhow!("webhook url must start with http:// or https://")),
+    };
+    let authority = uri.authority().context("webhook url has no host")?;
+    let host = authority
+        .host()
+        .trim_start_matches('[')
+        .trim_end_matches(']');
+    let port = authority.port_u16().unwrap_or(if https { 443 } else { 80 });
+
+    let body = serde_json::to_vec(notification).context("failed to encode webhook")?;
+    let request = Request::post(uri.path_and_query().map_or("/", |x| x.as_str()))
+        .header(header::HOST, authority.as_str())
+        .header(header::CONTENT_TYPE, "application/json")
+        .body(Full::new(Bytes::from(body)))
+        .context("failed to build webhook request")?;
+
+    let send = async {
+        let stream = TcpStream::connect((host, port))
+            .await
+            .context("failed to connect to webhook")?;
+
+        if https {
+            let name = ServerName::try_from(host.to_string()).context("invalid webhook host")?;
+            let stream = TLS_CONNECTOR
+                .connect(name, stream)
+                .await
+                .context("failed to connect to webhook")?;
+            send_request(stream, request).await
+        } else {
+            send_request(stream, request).await
+        }
+    };
+
+    let status = tokio::time::timeout(NOTIFY_TIMEOUT, send)
+        .await
+        .context("webhook took too long to respond")??;
+
+    if !status.is_success() {
+        return Err(anyhow!("webhook returned an error: {status}"));
+    }
+
+    Ok(())
+}
+
+async fn send_request<S>(stream: S, request: Request<Full<Bytes>>) -> Result<StatusCode>
+where
+    S: AsyncRead + AsyncWrite + Unpin + Send + 'static,
+{
+    let (mut sender, conn) = hyper::client::conn::http1::handshake(TokioIo::new(stream))
+        .await
+        .context("failed to send webhook")?;
+    tokio::spawn(conn);
+
+    let response = sender
+        .send_request(request)
+        .await
+        .context("failed to send webhook")?;
+
+    Ok(response.status())
+}
+
+async fn send_email(
+    transport: AsyncSmtpTransport<Tokio1Executor>,
+    from: &str,
+    to: &[String],
+    notification: &Notification,
+) -> Result<()> {
+    let mut message = Message::builder()
+        .from(from.parse().context("invalid from address")?)
+        .subject(notification.summary())
+        .header(ContentType::TEXT_PLAIN);
+
+    for addr in to {
+        message = message.to(addr.parse().context("invalid to address")?);
+    }
+
+    let body = format!(
+        "{}\n\nRule: {}\nBackend: {} ({})\nThreshold: {}\n",
+        notification.summary(),
+        notification.rule,
+        notification.nickname,
+        notification.backend,
+        notification.threshold,
+    );
+    let message = message.body(body).context("failed to build email")?;
+
+    tokio::time::timeout(NOTIFY_TIMEOUT, transport.send(message))
+        .await
+        .context("smtp server took too long to respond")?
+        .context("failed to send email")?;
+
+    Ok(())
+}
+
+async fn run_command(command: &str, args: &[String], notification: &Notification) -> Result<()> {
+    let value = notification
+        .value
+        .map(|x| x.to_string())
+        .unwrap_or_default();
+
+    let mut child = Command::new(command)
+        .args(args)
+        .env("DP_ALERT_STATUS", notification.status_str())
+        .env("DP_ALERT_RULE", &notification.rule)
+        .env("DP_ALERT_BACKEND", notification.backend.to_string())
+        .env("DP_ALERT_NICKNAME", &notification.nickname)
+        .env("DP_ALERT_SUBJECT", &notification.subject)
+        .env("DP_ALERT_VALUE", value)
+        .env("DP_ALERT_THRESHOLD", notification.threshold.to_string())
+        .env("DP_ALERT_MESSAGE", notification.summary())
+        .kill_on_drop(true)
+        .spawn()
+        .with_context(|| format!("failed to run {command}"))?;
+
+    let status = tokio::time::timeout(NOTIFY_TIMEOUT, child.wait())
+        .await
+        .context("command took too long to run")?
+        .context("failed to wait for command")?;
+
+    if !status.success() {
+        return Err(anyhow!("command exited with {status}"));
+    }
+
+    Ok(())
+}
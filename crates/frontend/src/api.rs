@@ -0,0 +1,298 @@
+use std::{collections::BTreeMap, net::IpAddr};
+
+use hyper::StatusCode;
+use proto::frontend::{CommandAction, ProcessDetailRequest, RenameAction, Signal, SignalAction};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    backend::matches_tag,
+    http::{request::ServerRequest, response::ServerResponse, tokens::ApiScope},
+    pages::template::{send_act, send_req},
+};
+
+// Routes are shaped like /api/v1/backends/{addr}/..., so the address is always the fourth segment
+fn select_backend(req: &mut ServerRequest, scope: ApiScope) -> Result<(), ServerResponse> {
+    req.check_token(scope)?;
+
+    let addr = req
+        .path_segments()
+        .nth(3)
+        .and_then(|x| x.parse::<IpAddr>().ok())
+        .ok_or_else(|| req.error(StatusCode::BAD_REQUEST, "invalid backend address"))?;
+
+    req.select_backend(addr);
+
+    Ok(())
+}
+
+fn no_content() -> ServerResponse {
+    ServerResponse::new().status(StatusCode::NO_CONTENT)
+}
+
+#[derive(Serialize)]
+struct BackendEntry {
+    addr: IpAddr,
+    nickname: String,
+    tags: BTreeMap<String, String>,
+}
+
+#[derive(Deserialize, Default)]
+#[serde(default)]
+struct BackendsQuery {
+    tag: String,
+}
+
+pub async fn backends(mut req: ServerRequest) -> Result<ServerResponse, ServerResponse> {
+    req.check_token(ApiScope::Read)?;
+
+    let query: BackendsQuery = req.extract_query()?;
+
+    let list: Vec<_> = req
+        .extract_all_backends()
+        .into_iter()
+        .filter(|x| query.tag.is_empty() || matches_tag(&x.tags, &query.tag))
+        .map(|x| BackendEntry {
+            addr: x.addr,
+            nickname: x.nickname,
+            tags: x.tags,
+        })
+        .collect();
+
+    Ok(ServerResponse::new().json(&list))
+}
+
+macro_rules! getter {
+    ($name:ident, $variant:ident) => {
+        pub async fn $name(mut req: ServerRequest) -> Result<ServerResponse, ServerResponse> {
+            select_backend(&mut req, ApiScope::Read)?;
+
+            let data = send_req!(req, $variant)?;
+
+            Ok(ServerResponse::new().json(&data))
+        }
+    };
+}
+
+getter!(cpu, Cpu);
+getter!(temp, Temp);
+getter!(memory, Mem);
+getter!(disks, Disk);
+getter!(network, NetIO);
+getter!(disk_io, DiskIO);
+getter!(processes, Processes);
+getter!(host, Host);
+getter!(software, Software);
+getter!(services, Services);
+
+#[derive(Deserialize)]
+struct DirectoryQuery {
+    path: String,
+}
+
+pub async fn directory(mut req: ServerRequest) -> Result<ServerResponse, ServerResponse> {
+    select_backend(&mut req, ApiScope::Read)?;
+
+    let query: DirectoryQuery = req.extract_query()?;
+
+    let data = send_req!(req, Directory(query.path))?;
+
+    Ok(ServerResponse::new().json(&data))
+}
+
+#[derive(Deserialize, Default)]
+#[serde(default)]
+struct ProcessQuery {
+    environment: bool,
+}
+
+pub async fn process(mut req: ServerRequest) -> Result<ServerResponse, ServerResponse> {
+    let query: ProcessQuery = req.extract_query()?;
+
+    // The environment can hold secrets, so it's kept to tokens that could run commands anyway
+    let scope = if query.environment {
+        ApiScope::Write
+    } else {
+        ApiScope::Read
+    };
+    select_backend(&mut req, scope)?;
+
+    let pid = req
+        .path_segments()
+        .nth(5)
+        .and_then(|x| x.parse::<u32>().ok())
+        .ok_or_else(|| req.error(StatusCode::BAD_REQUEST, "invalid pid"))?;
+
+    let data = send_req!(
+        req,
+        ProcessDetail(ProcessDetailRequest {
+            pid,
+            environment: query.environment,
+        })
+    )?
+    .ok_or_else(|| req.error(StatusCode::NOT_FOUND, "process not found"))?;
+
+    Ok(ServerResponse::new().json(&data))
+}
+
+#[derive(Deserialize)]
+struct SignalBody {
+    signal: Signal,
+}
+
+pub async fn signal(mut req: ServerRequest) -> Result<ServerResponse, ServerResponse> {
+    select_backend(&mut req, ApiScope::Write)?;
+
+    let pid = req
+        .path_segments()
+        .nth(5)
+        .and_then(|x| x.parse::<u32>().ok())
+        .ok_or_else(|| req.error(StatusCode::BAD_REQUEST, "invalid pid"))?;
+
+    let body: SignalBody = req.extract_json().await?;
+
+    send_act!(
+        req,
+        Signal(SignalAction {
+            pid,
+            signal: body.signal,
+        })
+    )?;
+
+    Ok(no_content())
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "lowercase")]
+enum SoftwareAction {
+    Install,
+    Uninstall,
+}
+
+#[derive(Deserialize)]
+struct SoftwareBody {
+    action: SoftwareAction,
+    software: Vec<u16>,
+}
+
+#[derive(Serialize)]
+struct SoftwareOutput {
+    output: String,
+    stderr: String,
+    exit_code: Option<i32>,
+    signal: Option<i32>,
+    duration_ms: u64,
+}
+
+pub async fn software_action(mut req: ServerRequest) -> Result<ServerResponse, ServerResponse> {
+    select_backend(&mut req, ApiScope::Write)?;
+
+    let body: SoftwareBody = req.extract_json().await?;
+
+    if body.software.is_empty() {
+        return Err(req.error(StatusCode::BAD_REQUEST, "no software selected"));
+    }
+
+    let action = match body.action {
+        SoftwareAction::Install => "install",
+        SoftwareAction::Uninstall => "uninstall",
+    }
+    .into();
+
+    let mut args = vec![action];
+    args.extend(body.software.iter().map(u16::to_string));
+
+    let msg = CommandAction {
+        cmd: "/boot/dietpi/dietpi-software".into(),
+        args,
+    };
+
+    let resp = send_req!(req, Command(msg))?;
+
+    if let Some(err) = resp.error {
+        let status = if resp.blocked {
+            StatusCode::FORBIDDEN
+        } else {
+            StatusCode::BAD_GATEWAY
+        };
+        return Err(req.error(status, err));
+    }
+
+    Ok(ServerResponse::new().json(&SoftwareOutput {
+        output: String::from_utf8_lossy(&resp.output).into_owned(),
+        stderr: String::from_utf8_lossy(&resp.stderr).into_owned(),
+        exit_code: resp.exit_code,
+        signal: resp.signal,
+        duration_ms: resp.duration_ms,
+    }))
+}
+
+#[derive(Deserialize)]
+struct PathBody {
+    path: String,
+}
+
+pub async fn new_file(mut req: ServerRequest) -> Result<ServerResponse, ServerResponse> {
+    select_backend(&mut req, ApiScope::Write)?;
+
+    let body: PathBody = req.extract_json().await?;
+
+    send_act!(req, NewFile(body.path))?;
+
+    Ok(no_content())
+}
+
+pub async fn new_folder(mut req: ServerRequest) -> Result<ServerResponse, ServerResponse> {
+    select_backend(&mut req, ApiScope::Write)?;
+
+    let body: PathBody = req.extract_json().await?;
+
+    send_act!(req, NewFolder(body.path))?;
+
+    Ok(no_content())
+}
+
+#[derive(Deserialize)]
+struct RenameBody {
+    from: String,
+    to: String,
+}
+
+pub async fn rename(mut req: ServerRequest) -> Result<ServerResponse, ServerResponse> {
+    select_backend(&mut req, ApiScope::Write)?;
+
+    let body: RenameBody = req.extract_json().await?;
+
+    send_act!(
+        req,
+        Rename(RenameAction {
+            from: body.from,
+            to: body.to,
+        })
+    )?;
+
+    Ok(no_content())
+}
+
+pub async fn delete_file(mut req: ServerRequest) -> Result<ServerResponse, ServerResponse> {
+    select_backend(&mut req, ApiScope::Write)?;
+
+    let body: PathBody = req.extract_json().await?;
+
+    send_act!(req, DeleteFile(body.path))?;
+
+    Ok(no_content())
+}
+
+pub async fn delete_folder(mut req: ServerRequest) -> Result<ServerResponse, ServerResponse> {
+    select_backend(&mut req, ApiScope::Write)?;
+
+    let body: PathBody = req.extract_json().await?;
+
+    send_act!(req, DeleteFolder(body.path))?;
+
+    Ok(no_content())
+}
+
+pub async fn not_found(req: ServerRequest) -> Result<ServerResponse, ServerResponse> {
+    Err(req.error(StatusCode::NOT_FOUND, "unknown api endpoint"))
+}
@@ -0,0 +1,337 @@
+use std::{
+    fs::{self, OpenOptions},
+    io::{self, BufRead, BufReader, Read, Seek, SeekFrom, Write},
+    net::IpAddr,
+    path::{Path, PathBuf},
+};
+
+use anyhow::{Context, Result};
+use log::error;
+use proto::frontend::{ActionFrontendMessage, CommandAction, RequestFrontendMessage};
+use serde::{Deserialize, Serialize};
+use tokio::sync::mpsc;
+use tokio_stream::wrappers::ReceiverStream;
+
+use crate::{SharedConfig, storage};
+
+const AUDIT_FILE: &str = "audit.jsonl";
+
+// Once the log gets this big, it's moved to audit.jsonl.1 and a new one is started
+const MAX_FILE_SIZE: u64 = 4 * 1024 * 1024;
+
+// The audit page only looks at this much of the end of the log, the export has all of it
+const MAX_READ_SIZE: u64 = 1024 * 1024;
+
+const EXPORT_CHUNK_SIZE: usize = 64 * 1024;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AuditEntry {
+    pub time: u64,
+    pub session: Option<String>,
+    pub source: Option<IpAddr>,
+    pub backend: IpAddr,
+    pub nickname: String,
+    pub action: String,
+    pub args: String,
+    pub result: String,
+}
+
+// Describes an action as a short name and its arguments, or None if it isn't worth recording
+pub fn describe_action(msg: &ActionFrontendMessage) -> Option<(&'static str, String)> {
+    let desc = match msg {
+        ActionFrontendMessage::Signal(action) => (
+            "signal",
+            format!("pid={} signal={:?}", action.pid, action.signal),
+        ),
+        ActionFrontendMessage::NewFile(path) => ("new_file", path.clone()),
+        ActionFrontendMessage::NewFolder(path) => ("new_folder", path.clone()),
+        ActionFrontendMessage::Rename(action) => {
+            ("rename", format!("{} -> {}", action.from, action.to))
+        }
+        ActionFrontendMessage::DeleteFile(path) => ("delete_file", path.clone()),
+        ActionFrontendMessage::DeleteFolder(path) => ("delete_folder", path.clone()),
+        ActionFrontendMessage::Upload(action) => (
+            "upload",
+            format!("{} ({} bytes)", action.path, action.data.len()),
+        ),
+        ActionFrontendMessage::StartCommand(start) => ("command", describe_command(&start.action)),
+        // The terminal page talks to the backend directly, and only records opening a session
+        ActionFrontendMessage::Terminal(_) | ActionFrontendMessage::ResizeTerminal(_) => {
+            return None;
+        }
+    };
+
+    Some(desc)
+}
+
+pub fn describe_command(action: &CommandAction) -> String {
+    let mut desc = action.cmd.clone();
+    for arg in &action.args {
+        desc.push(' ');
+        desc.push_str(arg);
+    }
+    desc
+}
+
+// Only requests that change something on the backend or read secrets get recorded
+pub fn describe_request(req: &RequestFrontendMessage) -> Option<(&'static str, String)> {
+    match req {
+        RequestFrontendMessage::Command(action) => Some(("command", describe_command(action))),
+        RequestFrontendMessage::ProcessDetail(detail) if detail.environment => {
+            Some(("process_environment", format!("pid={}", detail.pid)))
+        }
+        _ => None,
+    }
+}
+
+pub struct AuditLog {
+    file: Option<fs::File>,
+    size: u64,
+    // Number of rotated files kept, 0 keeps all of them
+    keep: usize,
+}
+
+fn rotated_path(path: &Path, n: usize) -> PathBuf {
+    path.with_extension(format!("jsonl.{n}"))
+}
+
+impl AuditLog {
+    fn open() -> Result<(fs::File, u64)> {
+        let file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(storage::data_path(AUDIT_FILE)?)
+            .context("failed to open audit log")?;
+        let size = file.metadata().map(|x| x.len()).unwrap_or_default();
+        Ok((file, size))
+    }
+
+    pub fn new(keep: usize) -> Self {
+        match Self::open() {
+            Ok((file, size)) => Self {
+                file: Some(file),
+                size,
+                keep,
+            },
+            Err(err) => {
+                error!("Audit log disabled: {err:#}");
+                Self {
+                    file: None,
+                    size: 0,
+                    keep,
+                }
+            }
+        }
+    }
+
+    // Older files are shifted up by one, so audit.jsonl.1 is always the most recent of them.
+    // Renaming onto the last kept file replaces it, which is how the oldest one gets dropped
+    fn rotate(&mut self) -> Result<()> {
+        let path = storage::data_path(AUDIT_FILE)?;
+
+        let mut free = 1;
+        while rotated_path(&path, free).exists() {
+            free += 1;
+        }
+        if self.keep > 0 {
+            free = free.min(self.keep);
+        }
+
+        for n in (1..free).rev() {
+            fs::rename(rotated_path(&path, n), rotated_path(&path, n + 1))
+                .context("failed to rotate audit log")?;
+        }
+        fs::rename(&path, rotated_path(&path, 1)).context("failed to rotate audit log")?;
+
+        // Lowering the limit leaves extra files behind
+        if self.keep > 0 {
+            let mut n = self.keep + 1;
+            while fs::remove_file(rotated_path(&path, n)).is_ok() {
+                n += 1;
+            }
+        }
+
+        let (file, size) = Self::open()?;
+        self.file = Some(file);
+        self.size = size;
+        Ok(())
+    }
+
+    pub fn record(&mut self, entry: &AuditEntry) {
+        if self.file.is_some()
+            && self.size >= MAX_FILE_SIZE
+            && let Err(err) = self.rotate()
+        {
+            error!("{err:#}");
+        }
+
+        let Some(file) = &mut self.file else {
+            return;
+        };
+
+        // Serializing this struct can't fail
+        let mut line = serde_json::to_vec(entry).unwrap();
+        line.push(b'\n');
+
+        match file.write_all(&line) {
+            Ok(()) => self.size += line.len() as u64,
+            Err(err) => error!("Failed to write to audit log: {err:#}"),
+        }
+    }
+}
+
+// Reads up to max bytes from the end of a file, starting at a line boundary
+fn read_tail(path: &Path, max: u64) -> Result<Vec<u8>> {
+    let mut file = match fs::File::open(path) {
+        Ok(file) => file,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(e).context("failed to read audit log"),
+    };
+
+    let len = file.metadata().context("failed to read audit log")?.len();
+    let start = len.saturating_sub(max);
+    file.seek(SeekFrom::Start(start))
+        .context("failed to read audit log")?;
+
+    let mut data = Vec::new();
+    file.read_to_end(&mut data)
+        .context("failed to read audit log")?;
+
+    // Starting partway through the file most likely lands in the middle of a line
+    if start > 0 {
+        let first_line = data
+            .iter()
+            .position(|&x| x == b'\n')
+            .map_or(data.len(), |x| x + 1);
+        data.drain(..first_line);
+    }
+
+    Ok(data)
+}
+
+// Only the end of the log is read, so a page load doesn't parse the whole history.
+// Right after a rotation, the rest comes from the previous file
+fn read_recent() -> Result<Vec<AuditEntry>> {
+    let path = storage::data_path(AUDIT_FILE)?;
+
+    let mut data = read_tail(&path, MAX_READ_SIZE)?;
+    let remaining = MAX_READ_SIZE.saturating_sub(data.len() as u64);
+    if remaining > 0 {
+        let mut previous = read_tail(&rotated_path(&path, 1), remaining)?;
+        previous.append(&mut data);
+        data = previous;
+    }
+
+    // Skip lines that can't be parsed (e.g. a partial write), rather than losing the whole log
+    let entries = data
+        .split(|&x| x == b'\n')
+        .filter_map(|line| serde_json::from_slice(line).ok())
+        .collect();
+
+    Ok(entries)
+}
+
+// Sends every matching line, oldest file first, in chunks so the whole log is never in memory
+fn export(filter: impl Fn(&AuditEntry) -> bool, tx: &mpsc::Sender<Vec<u8>>) -> Result<()> {
+    let path = storage::data_path(AUDIT_FILE)?;
+
+    let mut paths = vec![path.clone()];
+    let mut n = 1;
+    while rotated_path(&path, n).exists() {
+        paths.push(rotated_path(&path, n));
+        n += 1;
+    }
+
+    let mut chunk = Vec::new();
+    for path in paths.iter().rev() {
+        let file = match fs::File::open(path) {
+            Ok(file) => file,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => continue,
+            Err(e) => return Err(e).context("failed to read audit log"),
+        };
+
+        for line in BufReader::new(file).split(b'\n') {
+            let line = line.context("failed to read audit log")?;
+
+            if serde_json::from_slice(&line).is_ok_and(|entry| filter(&entry)) {
+                chunk.extend_from_slice(&line);
+                chunk.push(b'\n');
+            }
+
+            if chunk.len() >= EXPORT_CHUNK_SIZE {
+                // The client went away
+                if tx.blocking_send(std::mem::take(&mut chunk)).is_err() {
+                    return Ok(());
+                }
+            }
+        }
+    }
+
+    if !chunk.is_empty() {
+        let _ = tx.blocking_send(chunk);
+    }
+
+    Ok(())
+}
+
+#[derive(Clone)]
+pub struct SharedAuditLog(mpsc::UnboundedSender<AuditEntry>);
+
+impl SharedAuditLog {
+    // One task does all of the writing, so entries end up in the file in the order they were recorded
+    pub fn new(config: &SharedConfig) -> Self {
+        let (tx, mut rx) = mpsc::unbounded_channel();
+        let mut log = AuditLog::new(config.audit_log_files);
+
+        tokio::spawn(async move {
+            let mut entries = Vec::new();
+
+            while rx.recv_many(&mut entries, 64).await > 0 {
+                let batch = std::mem::take(&mut entries);
+
+                // Written off the async runtime, so a slow disk doesn't hold up requests
+                let written = tokio::task::spawn_blocking(move || {
+                    for entry in &batch {
+                        log.record(entry);
+                    }
+                    log
+                });
+
+                log = match written.await {
+                    Ok(log) => log,
+                    Err(err) => {
+                        error!("Audit log writer stopped: {err}");
+                        return;
+                    }
+                };
+            }
+        });
+
+        Self(tx)
+    }
+
+    pub fn record(&self, entry: AuditEntry) {
+        let _ = self.0.send(entry);
+    }
+
+    pub async fn read_recent(&self) -> Result<Vec<AuditEntry>> {
+        tokio::task::spawn_blocking(read_recent)
+            .await
+            .context("audit log reader panicked")?
+    }
+
+    pub fn export(
+        &self,
+        filter: impl Fn(&AuditEntry) -> bool + Send + 'static,
+    ) -> ReceiverStream<Vec<u8>> {
+        let (tx, rx) = mpsc::channel(4);
+
+        tokio::task::spawn_blocking(move || {
+            if let Err(err) = export(filter, &tx) {
+                error!("Failed to export audit log: {err:#}");
+            }
+        });
+
+        ReceiverStream::new(rx)
+    }
+}
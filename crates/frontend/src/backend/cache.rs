@@ -35,4 +35,4 @@ macro_rules! cache {
     };
 }
 
-cache!(BackendCache, [cpu: Cpu, temp: Temp, mem: Mem, disk: Disk, net_io: NetIO, processes: Processes]);
+cache!(BackendCache, [cpu: Cpu, temp: Temp, mem: Mem, disk: Disk, net_io: NetIO, disk_io: DiskIO, processes: Processes]);
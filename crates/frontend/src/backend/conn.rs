@@ -1,11 +1,17 @@
-use std::{collections::VecDeque, net::IpAddr};
+use std::{
+    collections::{BTreeMap, VecDeque},
+    net::IpAddr,
+};
 
 use anyhow::{Context, Result, anyhow};
 use config::PROTOCOL_VERSION;
 use log::{error, info, warn};
 use proto::{
     DashboardSocket,
-    backend::{ActionBackendMessage, BackendMessage, Handshake, ResponseBackendMessage},
+    backend::{
+        ActionBackendMessage, BackendMessage, CommandExit, CommandOutput, Handshake,
+        ResponseBackendMessage,
+    },
     frontend::{ActionFrontendMessage, FrontendMessage, RequestFrontendMessage},
 };
 use slab::Slab;
@@ -14,12 +20,24 @@ use tokio::{
     sync::{mpsc, oneshot},
 };
 
+use crate::{audit, http::auth::now_secs};
+
 use super::{SharedBackendRegistry, cache::BackendCache};
 
+// Output past this is dropped from the start, like the terminal buffer
+const MAX_JOB_OUTPUT: usize = 1_000_000;
+
+// Stderr is also kept separately for the summary, but there should be a lot less of it
+const MAX_JOB_STDERR: usize = 64_000;
+
+// Finished jobs are kept around so their output can still be viewed after navigating away
+const MAX_FINISHED_JOBS: usize = 10;
+
 #[derive(Debug)]
 pub struct BackendInfo {
     pub nickname: String,
     pub update: Option<String>,
+    pub tags: BTreeMap<String, String>,
     pub handle: BackendHandle,
 }
 
@@ -35,6 +53,54 @@ enum BackendRequest {
     PushTerminalHandle {
         term_tx: mpsc::UnboundedSender<Vec<u8>>,
     },
+    WatchCommand {
+        id: u32,
+        output_tx: mpsc::UnboundedSender<Vec<u8>>,
+    },
+    ListCommands {
+        resp_tx: oneshot::Sender<Vec<CommandJobInfo>>,
+    },
+}
+
+#[derive(Debug, Clone)]
+pub struct CommandJobInfo {
+    pub id: u32,
+    pub command: String,
+    pub started: u64,
+    pub stderr: Vec<u8>,
+    // Missing while the command is still running
+    pub exit: Option<CommandExit>,
+}
+
+struct CommandJob {
+    info: CommandJobInfo,
+    output: VecDeque<u8>,
+    watchers: Vec<mpsc::UnboundedSender<Vec<u8>>>,
+}
+
+impl CommandJob {
+    fn push_output(&mut self, stderr: bool, data: Vec<u8>) {
+        if stderr {
+            self.info.stderr.extend(&data);
+            let excess = self.info.stderr.len().saturating_sub(MAX_JOB_STDERR);
+            self.info.stderr.drain(..excess);
+        }
+
+        if self.output.len() + data.len() > MAX_JOB_OUTPUT {
+            let excess = (self.output.len() + data.len() - MAX_JOB_OUTPUT).min(self.output.len());
+            self.output.drain(..excess);
+        }
+        self.output.extend(&data);
+
+        self.watchers.retain(|tx| tx.send(data.clone()).is_ok());
+    }
+
+    fn finish(&mut self, exit: CommandExit) {
+        self.info.exit = Some(exit);
+
+        // Dropping the senders ends the streams
+        self.watchers.clear();
+    }
 }
 
 pub struct BackendConnection {
@@ -82,16 +148,17 @@ impl BackendConnection {
         let conn_info = BackendInfo {
             nickname,
             update: handshake.update,
+            tags: handshake.tags,
             handle: BackendHandle::new(tx),
         };
 
-        self.registry.lock().unwrap().insert(self.addr, conn_info);
+        self.registry.lock().unwrap().connect(self.addr, conn_info);
 
         if let Err(err) = self.handle_requests(rx).await {
             error!("Error handling requests for backend {}: {err:#}", self.addr)
         }
 
-        self.registry.lock().unwrap().remove(&self.addr);
+        self.registry.lock().unwrap().disconnect(self.addr);
     }
 
     async fn read_frame(&mut self) -> Result<Option<BackendMessage>> {
@@ -121,6 +188,8 @@ impl BackendConnection {
         let mut term_txs = Vec::new();
         let mut term_buf = VecDeque::with_capacity(10_000);
         let mut cache = BackendCache::new();
+        // Oldest first
+        let mut jobs: Vec<CommandJob> = Vec::new();
 
         loop {
             tokio::select! {
@@ -147,6 +216,27 @@ impl BackendConnection {
                                 .context("failed to write request frame")?;
                         },
                         BackendRequest::Action { msg } => {
+                            if let ActionFrontendMessage::StartCommand(start) = &msg {
+                                jobs.push(CommandJob {
+                                    info: CommandJobInfo {
+                                        id: start.id,
+                                        command: audit::describe_command(&start.action),
+                                        started: now_secs(),
+                                        stderr: Vec::new(),
+                                        exit: None,
+                                    },
+                                    output: VecDeque::new(),
+                                    watchers: Vec::new(),
+                                });
+
+                                let finished = jobs.iter().filter(|x| x.info.exit.is_some()).count();
+                                if finished > MAX_FINISHED_JOBS
+                                    && let Some(idx) = jobs.iter().position(|x| x.info.exit.is_some())
+                                {
+                                    jobs.remove(idx);
+                                }
+                            }
+
                             let msg = FrontendMessage::Action(msg);
 
                             self.socket
@@ -159,6 +249,19 @@ impl BackendConnection {
                                 term_txs.push(term_tx);
                             }
                         },
+                        BackendRequest::WatchCommand { id, output_tx } => {
+                            // An unknown job just drops the sender, which ends the stream right away
+                            let Some(job) = jobs.iter_mut().find(|x| x.info.id == id) else {
+                                continue;
+                            };
+
+                            if output_tx.send(job.output.make_contiguous().to_vec()).is_ok() && job.info.exit.is_none() {
+                                job.watchers.push(output_tx);
+                            }
+                        },
+                        BackendRequest::ListCommands { resp_tx } => {
+                            let _ = resp_tx.send(jobs.iter().map(|x| x.info.clone()).collect());
+                        },
                     }
                 }
                 resp_result = self.read_frame() => {
@@ -194,6 +297,16 @@ impl BackendConnection {
 
                                     term_txs.retain(|tx| tx.send(data.clone()).is_ok());
                                 }
+                                ActionBackendMessage::CommandOutput(CommandOutput { id, stderr, data }) => {
+                                    if let Some(job) = jobs.iter_mut().find(|x| x.info.id == id) {
+                                        job.push_output(stderr, data);
+                                    }
+                                }
+                                ActionBackendMessage::CommandExit(exit) => {
+                                    if let Some(job) = jobs.iter_mut().find(|x| x.info.id == exit.id) {
+                                        job.finish(exit);
+                                    }
+                                }
                             }
                         }
                     }
@@ -249,4 +362,31 @@ impl BackendHandle {
 
         Ok(term_rx)
     }
+
+    pub async fn watch_command(&self, id: u32) -> Result<mpsc::UnboundedReceiver<Vec<u8>>> {
+        let (output_tx, output_rx) = mpsc::unbounded_channel();
+
+        let msg = BackendRequest::WatchCommand { id, output_tx };
+
+        self.tx
+            .send(msg)
+            .context("failed to watch command, connection likely closed")?;
+
+        Ok(output_rx)
+    }
+
+    // Background commands started on this backend, oldest first
+    pub async fn command_jobs(&self) -> Result<Vec<CommandJobInfo>> {
+        let (resp_tx, resp_rx) = oneshot::channel();
+
+        let msg = BackendRequest::ListCommands { resp_tx };
+
+        self.tx
+            .send(msg)
+            .context("failed to list commands, connection likely closed")?;
+
+        resp_rx
+            .await
+            .context("failed to recv command list, connection likely closed")
+    }
 }
@@ -1,24 +1,136 @@
 use std::{
-    collections::HashMap,
+    collections::{BTreeMap, HashMap},
     net::{IpAddr, SocketAddr},
     sync::{Arc, Mutex},
 };
 
 use anyhow::{Context, Result};
+use bitcode::{Decode, Encode};
 use conn::{BackendConnection, BackendInfo};
-use log::{error, info};
+use log::{error, info, warn};
 use tokio::net::TcpListener;
 
 mod cache;
 mod conn;
 
-pub use conn::BackendHandle;
+pub use conn::{BackendHandle, CommandJobInfo};
 
-use crate::SharedConfig;
+use crate::{
+    SharedConfig,
+    http::auth::now_secs,
+    storage::{self, EncryptedWriter},
+};
+
+const KNOWN_BACKENDS_FILE: &str = "backends.bin";
+
+// Backends stay known after disconnecting, so pages can still show them as offline.
+// They're saved, so a backend that doesn't come back after a restart is still noticed
+#[derive(Debug, Clone, Encode, Decode)]
+pub struct KnownBackend {
+    pub nickname: String,
+    pub tags: BTreeMap<String, String>,
+    pub last_seen: u64,
+}
+
+pub struct BackendRegistry {
+    connected: HashMap<IpAddr, BackendInfo>,
+    known: HashMap<IpAddr, KnownBackend>,
+    writer: EncryptedWriter<HashMap<IpAddr, KnownBackend>>,
+}
+
+impl BackendRegistry {
+    pub fn new(config: &SharedConfig) -> Self {
+        let known = match storage::read_encrypted(KNOWN_BACKENDS_FILE, &config.secret.0) {
+            Ok(known) => known.unwrap_or_default(),
+            Err(err) => {
+                warn!("Discarding saved backends: {err:#}");
+                HashMap::new()
+            }
+        };
+
+        Self {
+            connected: HashMap::new(),
+            known,
+            writer: EncryptedWriter::new(KNOWN_BACKENDS_FILE, config.secret.0),
+        }
+    }
+
+    pub fn connected(&self) -> &HashMap<IpAddr, BackendInfo> {
+        &self.connected
+    }
+
+    // Backends that were connected earlier, but have since gone away
+    pub fn offline(&self) -> impl Iterator<Item = (&IpAddr, &KnownBackend)> {
+        self.known
+            .iter()
+            .filter(|(addr, _)| !self.connected.contains_key(addr))
+    }
+
+    pub(super) fn connect(&mut self, addr: IpAddr, info: BackendInfo) {
+        let known = KnownBackend {
+            nickname: info.nickname.clone(),
+            tags: info.tags.clone(),
+            last_seen: now_secs(),
+        };
+        self.known.insert(addr, known);
+        self.connected.insert(addr, info);
+        self.writer.save(self.known.clone());
+    }
+
+    pub(super) fn disconnect(&mut self, addr: IpAddr) {
+        self.connected.remove(&addr);
+        if let Some(known) = self.known.get_mut(&addr) {
+            known.last_seen = now_secs();
+        }
+        self.writer.save(self.known.clone());
+    }
+
+    // Stops showing and alerting on an offline backend, e.g. one that was removed for good.
+    // Connected backends can't be forgotten, they'd just be added back straight away
+    pub fn forget(&mut self, addr: IpAddr) -> bool {
+        if self.connected.contains_key(&addr) || self.known.remove(&addr).is_none() {
+            return false;
+        }
+
+        self.writer.save(self.known.clone());
+        true
+    }
+}
 
-pub type BackendRegistry = HashMap<IpAddr, BackendInfo>;
 pub type SharedBackendRegistry = Arc<Mutex<BackendRegistry>>;
 
+// Tags are targeted as "key=value", or just "key" to match any value
+pub fn matches_tag(tags: &BTreeMap<String, String>, target: &str) -> bool {
+    match target.split_once('=') {
+        Some((key, value)) => tags.get(key).is_some_and(|x| x == value),
+        None => tags.contains_key(target),
+    }
+}
+
+// Splits items up by the value of a tag, sorted by value, with untagged items last
+pub fn group_by_tag<T>(
+    items: impl IntoIterator<Item = T>,
+    tag: &str,
+    tags: impl Fn(&T) -> &BTreeMap<String, String>,
+) -> Vec<(Option<String>, Vec<T>)> {
+    let mut groups: BTreeMap<String, Vec<T>> = BTreeMap::new();
+    let mut untagged = Vec::new();
+
+    for item in items {
+        match tags(&item).get(tag) {
+            Some(value) => groups.entry(value.clone()).or_default().push(item),
+            None => untagged.push(item),
+        }
+    }
+
+    let mut groups: Vec<_> = groups.into_iter().map(|(k, v)| (Some(k), v)).collect();
+    if !untagged.is_empty() {
+        groups.push((None, untagged));
+    }
+
+    groups
+}
+
 pub struct BackendServer {
     listener: TcpListener,
     config: SharedConfig,
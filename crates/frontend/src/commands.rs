@@ -0,0 +1,354 @@
+use std::{
+    collections::VecDeque,
+    net::IpAddr,
+    ops::DerefMut,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use bitcode::{Decode, Encode};
+use futures_util::future::join_all;
+use log::warn;
+use proto::{
+    backend::ResponseBackendMessage,
+    frontend::{CommandAction, RequestFrontendMessage},
+};
+
+use crate::{
+    SharedConfig,
+    http::auth::now_secs,
+    http::request::CurrentBackendData,
+    storage::{self, EncryptedWriter},
+};
+
+const RUNS_FILE: &str = "command-runs.bin";
+
+// Older runs get dropped once there are more than this
+const MAX_RUNS: usize = 100;
+
+#[derive(Debug, Clone, Encode, Decode)]
+pub enum CommandOutcome {
+    Finished {
+        exit_code: Option<i32>,
+        stdout: String,
+        stderr: String,
+    },
+    // The command may still be running on the backend, it just stopped being waited on
+    TimedOut,
+    Failed(String),
+    // Scheduled jobs can target backends that aren't connected at the time
+    Offline {
+        queued: bool,
+    },
+    // Turned away by the backend's command_policy, so it never ran
+    Blocked(String),
+}
+
+impl CommandOutcome {
+    pub fn succeeded(&self) -> bool {
+        matches!(
+            self,
+            Self::Finished {
+                exit_code: Some(0),
+                ..
+            }
+        )
+    }
+
+    // Short description for the audit log
+    pub fn summary(&self) -> String {
+        match self {
+            Self::Finished {
+                exit_code: Some(code),
+                ..
+            } => format!("exit {code}"),
+            Self::Finished {
+                exit_code: None, ..
+            } => "killed by signal".into(),
+            Self::TimedOut => "timed out".into(),
+            Self::Failed(err) => format!("failed: {err}"),
+            Self::Offline { queued: true } => "offline, queued".into(),
+            Self::Offline { queued: false } => "offline, skipped".into(),
+            Self::Blocked(err) => format!("blocked: {err}"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Encode, Decode)]
+pub struct HostResult {
+    pub addr: IpAddr,
+    pub nickname: String,
+    pub duration_ms: u64,
+    pub outcome: CommandOutcome,
+}
+
+#[derive(Debug, Clone, Encode, Decode)]
+pub struct CommandRun {
+    pub id: u32,
+    pub time: u64,
+    pub command: String,
+    pub timeout: u64,
+    pub results: Vec<HostResult>,
+}
+
+impl CommandRun {
+    pub fn succeeded(&self) -> usize {
+        self.results
+            .iter()
+            .filter(|x| x.outcome.succeeded())
+            .count()
+    }
+}
+
+// Splits on whitespace, with quotes and backslashes working like in the shell
+// Returns None if the command uses anything else the shell would have to handle
+fn split_words(command: &str) -> Option<Vec<String>> {
+    let mut words = Vec::new();
+    let mut word: Option<String> = None;
+    let mut chars = command.chars();
+
+    while let Some(c) = chars.next() {
+        match c {
+            ' ' | '\t' => words.extend(word.take()),
+            '\'' => {
+                let word = word.get_or_insert_default();
+                loop {
+                    match chars.next()? {
+                        '\'' => break,
+                        c => word.push(c),
+                    }
+                }
+            }
+            '"' => {
+                let word = word.get_or_insert_default();
+                loop {
+                    match chars.next()? {
+                        '"' => break,
+                        '\\' => match chars.next()? {
+                            c @ ('"' | '\\' | '$' | '`') => word.push(c),
+                            c => {
+                                word.push('\\');
+                                word.push(c);
+                            }
+                        },
+                        '$' | '`' => return None,
+                        c => word.push(c),
+                    }
+                }
+            }
+            '\\' => word.get_or_insert_default().push(chars.next()?),
+            '|' | '&' | ';' | '<' | '>' | '(' | ')' | '$' | '`' | '*' | '?' | '[' | '~' | '{'
+            | '#' | '\n' => return None,
+            c => word.get_or_insert_default().push(c),
+        }
+    }
+    words.extend(word);
+
+    // A leading "NAME=value" sets an environment variable instead of being the program
+    match words.first() {
+        Some(first) if !first.contains('=') => Some(words),
+        _ => None,
+    }
+}
+
+// Simple commands are sent as-is, so they can be matched by a backend's allowed_commands
+// Anything else goes through the shell, so pipes and such work like in a terminal
+pub fn command_action(command: &str) -> CommandAction {
+    match split_words(command) {
+        Some(mut words) => CommandAction {
+            cmd: words.remove(0),
+            args: words,
+        },
+        None => CommandAction {
+            cmd: "sh".into(),
+            args: vec!["-c".into(), command.into()],
+        },
+    }
+}
+
+pub async fn run_on_backend(
+    backend: &CurrentBackendData,
+    command: &str,
+    timeout: Duration,
+) -> HostResult {
+    let req = RequestFrontendMessage::Command(command_action(command));
+    let start = Instant::now();
+
+    let outcome = match tokio::time::timeout(timeout, backend.handle.send_req(req)).await {
+        Ok(Ok(ResponseBackendMessage::Command(resp))) => match resp.error {
+            Some(err) if resp.blocked => CommandOutcome::Blocked(err),
+            // Couldn't be started
+            Some(err) => CommandOutcome::Failed(err),
+            None => CommandOutcome::Finished {
+                exit_code: resp.exit_code,
+                stdout: String::from_utf8_lossy(&resp.output).into(),
+                stderr: String::from_utf8_lossy(&resp.stderr).into(),
+            },
+        },
+        Ok(Ok(_)) => CommandOutcome::Failed("backend sent mismatched response".into()),
+        Ok(Err(err)) => CommandOutcome::Failed(format!("{err:#}")),
+        Err(_) => CommandOutcome::TimedOut,
+    };
+
+    HostResult {
+        addr: backend.addr,
+        nickname: backend.nickname.clone(),
+        duration_ms: start.elapsed().as_millis() as u64,
+        outcome,
+    }
+}
+
+pub async fn run(backends: &[CurrentBackendData], command: &str, timeout: Duration) -> CommandRun {
+    let time = now_secs();
+
+    let results = join_all(
+        backends
+            .iter()
+            .map(|backend| run_on_backend(backend, command, timeout)),
+    )
+    .await;
+
+    CommandRun {
+        id: rand::random(),
+        time,
+        command: command.into(),
+        timeout: timeout.as_secs(),
+        results,
+    }
+}
+
+pub struct CommandHistory {
+    runs: VecDeque<CommandRun>,
+    writer: EncryptedWriter<VecDeque<CommandRun>>,
+}
+
+impl CommandHistory {
+    pub fn new(config: SharedConfig) -> Self {
+        // Output can contain anything, so it's stored encrypted like the other secrets
+        let runs = match storage::read_encrypted(RUNS_FILE, &config.secret.0) {
+            Ok(runs) => runs.unwrap_or_default(),
+            Err(err) => {
+                warn!("Discarding saved command runs: {err:#}");
+                VecDeque::new()
+            }
+        };
+
+        Self {
+            runs,
+            writer: EncryptedWriter::new(RUNS_FILE, config.secret.0),
+        }
+    }
+
+    pub fn push(&mut self, run: CommandRun) {
+        if self.runs.len() == MAX_RUNS {
+            self.runs.pop_front();
+        }
+        self.runs.push_back(run);
+
+        self.writer.save(self.runs.clone());
+    }
+
+    pub fn get(&self, id: u32) -> Option<&CommandRun> {
+        self.runs.iter().find(|x| x.id == id)
+    }
+
+    // Newest first
+    pub fn runs(&self) -> impl Iterator<Item = &CommandRun> {
+        self.runs.iter().rev()
+    }
+}
+
+#[derive(Clone)]
+pub struct SharedCommandHistory(Arc<Mutex<CommandHistory>>);
+
+impl SharedCommandHistory {
+    pub fn new(config: SharedConfig) -> Self {
+        Self(Arc::new(Mutex::new(CommandHistory::new(config))))
+    }
+
+    pub fn get(&self) -> impl DerefMut<Target = CommandHistory> {
+        self.0.lock().unwrap()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn expect(command: &str, expected: &[&str]) {
+        assert_eq!(
+            split_words(command),
+            Some(expected.iter().map(|&x| x.into()).collect()),
+            "{command}"
+        );
+    }
+
+    #[test]
+    fn plain_words() {
+        expect("uptime", &["uptime"]);
+        expect(
+            "systemctl restart nginx",
+            &["systemctl", "restart", "nginx"],
+        );
+        expect("  ls \t -l  /tmp ", &["ls", "-l", "/tmp"]);
+    }
+
+    #[test]
+    fn quotes() {
+        expect("echo 'hello world'", &["echo", "hello world"]);
+        expect(r#"echo "hello world""#, &["echo", "hello world"]);
+        expect("echo ''", &["echo", ""]);
+        expect(r#"echo a"b c"d"#, &["echo", "ab cd"]);
+        // Nothing is special inside single quotes
+        expect(r#"echo '$HOME | "x"'"#, &["echo", r#"$HOME | "x""#]);
+    }
+
+    #[test]
+    fn backslashes() {
+        expect(r"echo hello\ world", &["echo", "hello world"]);
+        expect(r"echo \*", &["echo", "*"]);
+        expect(r#"echo "a\"b""#, &["echo", r#"a"b"#]);
+        expect(r#"echo "a\nb""#, &["echo", r"a\nb"]);
+        expect(r"echo '\n'", &["echo", r"\n"]);
+    }
+
+    #[test]
+    fn unterminated() {
+        assert_eq!(split_words("echo 'hello"), None);
+        assert_eq!(split_words(r#"echo "hello"#), None);
+        assert_eq!(split_words(r"echo \"), None);
+    }
+
+    #[test]
+    fn needs_a_shell() {
+        for command in [
+            "ls | grep x",
+            "a && b",
+            "a; b",
+            "echo > file",
+            "cat < file",
+            "echo $HOME",
+            "echo `id`",
+            r#"echo "$HOME""#,
+            "ls *.conf",
+            "ls ?",
+            "ls [ab]",
+            "ls ~",
+            "echo {a,b}",
+            "echo # comment",
+            "(ls)",
+            "a\nb",
+        ] {
+            assert_eq!(split_words(command), None, "{command}");
+        }
+    }
+
+    #[test]
+    fn environment_assignments() {
+        assert_eq!(split_words("FOO=bar ls"), None);
+        assert_eq!(split_words(""), None);
+        assert_eq!(split_words("   "), None);
+        // Only the program is checked, arguments can have an "="
+        expect("dd if=/dev/zero", &["dd", "if=/dev/zero"]);
+    }
+}
@@ -0,0 +1,467 @@
+use std::{
+    collections::{BTreeMap, HashMap, VecDeque},
+    fmt::Display,
+    net::IpAddr,
+    ops::DerefMut,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use anyhow::{Context, Result, anyhow};
+use bitcode::{Decode, Encode};
+use futures_util::future::join_all;
+use log::{debug, error, warn};
+use proto::{
+    backend::{HistorySampleResponse, ResponseBackendMessage},
+    frontend::RequestFrontendMessage,
+};
+use serde::{Deserialize, Serialize};
+use tokio::time::MissedTickBehavior;
+
+use crate::{
+    backend::{BackendHandle, SharedBackendRegistry},
+    http::auth::now_secs,
+    storage,
+};
+
+const HISTORY_FILE: &str = "history.bin";
+
+// Backends average everything since the previous sample, so nothing is missed in between
+const SAMPLE_INTERVAL: Duration = Duration::from_secs(1);
+const FLUSH_INTERVAL: Duration = Duration::from_secs(60);
+
+// 1 hour of seconds, 1 day of minutes and 30 days of hours
+const SECOND_CAPACITY: usize = 60 * 60;
+const MINUTE_CAPACITY: usize = 24 * 60;
+const HOUR_CAPACITY: usize = 30 * 24;
+
+// Ranges get averaged down to at most this many points, so the page stays small
+const MAX_POINTS: u64 = 180;
+
+#[derive(Debug, Clone, Copy, Default, Encode, Decode)]
+pub struct Sample {
+    pub time: u64,
+    pub cpu: f32,
+    pub temp: Option<f32>,
+    pub ram: f32,
+    pub swap: f32,
+    pub sent: f32,
+    pub recv: f32,
+    // Memory breakdown as percentages of total RAM, apps is everything that isn't free, buffers or cache
+    pub apps: f32,
+    pub buffers: f32,
+    pub cached: f32,
+}
+
+// Keeps a running total of samples, so they can be averaged down for a coarser tier
+#[derive(Debug, Default, Clone, Encode, Decode)]
+struct Accumulator {
+    count: u32,
+    temp_count: u32,
+    sum: Sample,
+}
+
+impl Accumulator {
+    fn add(&mut self, sample: &Sample) {
+        self.count += 1;
+        self.sum.cpu += sample.cpu;
+        self.sum.ram += sample.ram;
+        self.sum.swap += sample.swap;
+        self.sum.sent += sample.sent;
+        self.sum.recv += sample.recv;
+        self.sum.apps += sample.apps;
+        self.sum.buffers += sample.buffers;
+        self.sum.cached += sample.cached;
+
+        if let Some(temp) = sample.temp {
+            self.temp_count += 1;
+            *self.sum.temp.get_or_insert(0.) += temp;
+        }
+    }
+
+    fn average(&self, time: u64) -> Option<Sample> {
+        if self.count == 0 {
+            return None;
+        }
+
+        let count = self.count as f32;
+
+        Some(Sample {
+            time,
+            cpu: self.sum.cpu / count,
+            temp: self.sum.temp.map(|x| x / self.temp_count as f32),
+            ram: self.sum.ram / count,
+            swap: self.sum.swap / count,
+            sent: self.sum.sent / count,
+            recv: self.sum.recv / count,
+            apps: self.sum.apps / count,
+            buffers: self.sum.buffers / count,
+            cached: self.sum.cached / count,
+        })
+    }
+}
+
+#[derive(Debug, Clone, Encode, Decode)]
+struct Tier {
+    resolution: u64,
+    capacity: usize,
+    samples: VecDeque<Sample>,
+    // Samples for the bucket that's still in progress
+    bucket: u64,
+    pending: Accumulator,
+}
+
+impl Tier {
+    fn new(resolution: u64, capacity: usize) -> Self {
+        Self {
+            resolution,
+            capacity,
+            samples: VecDeque::with_capacity(capacity),
+            bucket: 0,
+            pending: Accumulator::default(),
+        }
+    }
+
+    // Returns the averaged sample once a bucket is complete, so it can be passed on to the next tier
+    fn add(&mut self, sample: Sample) -> Option<Sample> {
+        let bucket = sample.time / self.resolution;
+        let mut finished = None;
+
+        if bucket != self.bucket {
+            finished = self.pending.average(self.bucket * self.resolution);
+            if let Some(finished) = finished {
+                if self.samples.len() == self.capacity {
+                    self.samples.pop_front();
+                }
+                self.samples.push_back(finished);
+            }
+
+            self.bucket = bucket;
+            self.pending = Accumulator::default();
+        }
+
+        self.pending.add(&sample);
+
+        finished
+    }
+
+    fn samples(&self) -> impl Iterator<Item = Sample> {
+        let pending = self.pending.average(self.bucket * self.resolution);
+        self.samples.iter().copied().chain(pending)
+    }
+
+    fn latest(&self) -> u64 {
+        self.samples().last().map(|x| x.time).unwrap_or_default()
+    }
+}
+
+#[derive(Debug, Clone, Encode, Decode)]
+struct TieredHistory {
+    seconds: Tier,
+    minutes: Tier,
+    hours: Tier,
+}
+
+impl Default for TieredHistory {
+    fn default() -> Self {
+        Self {
+            seconds: Tier::new(1, SECOND_CAPACITY),
+            minutes: Tier::new(60, MINUTE_CAPACITY),
+            hours: Tier::new(60 * 60, HOUR_CAPACITY),
+        }
+    }
+}
+
+impl TieredHistory {
+    fn push(&mut self, sample: Sample) {
+        self.seconds.add(sample);
+
+        if let Some(minute) = self.minutes.add(sample) {
+            self.hours.add(minute);
+        }
+    }
+
+    fn latest(&self) -> u64 {
+        self.hours.latest().max(self.seconds.latest())
+    }
+
+    // Forget anything that hasn't been seen for longer than the history goes back
+    fn is_recent(&self) -> bool {
+        self.latest() >= now_secs().saturating_sub(HistoryRange::Month.secs())
+    }
+
+    fn range(&self, range: HistoryRange) -> Vec<Option<Sample>> {
+        let range_secs = range.secs();
+
+        let tier = match range {
+            HistoryRange::FiveMinutes | HistoryRange::Hour => &self.seconds,
+            HistoryRange::Day => &self.minutes,
+            HistoryRange::Week | HistoryRange::Month => &self.hours,
+        };
+
+        let step = tier.resolution.max(range_secs.div_ceil(MAX_POINTS));
+        let num_points = range_secs.div_ceil(step) as usize;
+
+        let end = now_secs() + 1;
+        let start = end.saturating_sub(range_secs);
+
+        let mut buckets = vec![Accumulator::default(); num_points];
+        for sample in tier.samples() {
+            if sample.time < start || sample.time >= end {
+                continue;
+            }
+
+            let idx = ((end - 1 - sample.time) / step) as usize;
+            if let Some(bucket) = buckets.get_mut(idx) {
+                bucket.add(&sample);
+            }
+        }
+
+        buckets
+            .iter()
+            .enumerate()
+            .map(|(i, bucket)| bucket.average(end - 1 - i as u64 * step))
+            .collect()
+    }
+}
+
+// Everything recorded for a backend. Network interfaces and disks are keyed by name,
+// and only have the sent and recv fields filled in, which are bytes written and read for disks.
+#[derive(Debug, Clone, Default, Encode, Decode)]
+struct BackendHistory {
+    metrics: TieredHistory,
+    interfaces: BTreeMap<String, TieredHistory>,
+    disks: BTreeMap<String, TieredHistory>,
+}
+
+impl BackendHistory {
+    fn prune(&mut self) -> bool {
+        self.interfaces.retain(|_, history| history.is_recent());
+        self.disks.retain(|_, history| history.is_recent());
+
+        self.metrics.is_recent()
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum HistoryRange {
+    #[default]
+    #[serde(rename = "5m")]
+    FiveMinutes,
+    #[serde(rename = "1h")]
+    Hour,
+    #[serde(rename = "24h")]
+    Day,
+    #[serde(rename = "7d")]
+    Week,
+    #[serde(rename = "30d")]
+    Month,
+}
+
+impl HistoryRange {
+    pub const ALL: [Self; 5] = [
+        Self::FiveMinutes,
+        Self::Hour,
+        Self::Day,
+        Self::Week,
+        Self::Month,
+    ];
+
+    fn secs(self) -> u64 {
+        match self {
+            Self::FiveMinutes => 5 * 60,
+            Self::Hour => 60 * 60,
+            Self::Day => 24 * 60 * 60,
+            Self::Week => 7 * 24 * 60 * 60,
+            Self::Month => 30 * 24 * 60 * 60,
+        }
+    }
+}
+
+impl Display for HistoryRange {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        // Serializing a unit variant can't fail
+        f.write_str(&serde_plain::to_string(self).unwrap())
+    }
+}
+
+fn percent(used: u64, total: u64) -> f32 {
+    if total == 0 {
+        0.
+    } else {
+        used as f32 / total as f32 * 100.
+    }
+}
+
+pub struct HistoryStore {
+    backends: HashMap<IpAddr, BackendHistory>,
+    last_flush: Instant,
+}
+
+impl HistoryStore {
+    pub fn new() -> Self {
+        let mut backends: HashMap<IpAddr, BackendHistory> = match storage::read(HISTORY_FILE) {
+            Ok(backends) => backends.unwrap_or_default(),
+            Err(err) => {
+                warn!("Discarding saved metrics history: {err:#}");
+                HashMap::new()
+            }
+        };
+        backends.retain(|_, history| history.prune());
+
+        Self {
+            backends,
+            last_flush: Instant::now(),
+        }
+    }
+
+    fn push(&mut self, addr: IpAddr, time: u64, raw: HistorySampleResponse) {
+        let total = raw.ram.total;
+        let apps = total
+            .saturating_sub(raw.free)
+            .saturating_sub(raw.buffers)
+            .saturating_sub(raw.cached);
+
+        let history = self.backends.entry(addr).or_default();
+
+        history.metrics.push(Sample {
+            time,
+            cpu: raw.cpu,
+            temp: raw.temp,
+            ram: percent(raw.ram.used, total),
+            swap: percent(raw.swap.used, raw.swap.total),
+            sent: raw.sent as f32,
+            recv: raw.recv as f32,
+            apps: percent(apps, total),
+            buffers: percent(raw.buffers, total),
+            cached: percent(raw.cached, total),
+        });
+
+        for (devices, rates) in [
+            (&mut history.interfaces, raw.interfaces),
+            (&mut history.disks, raw.disks),
+        ] {
+            for rate in rates {
+                devices.entry(rate.name).or_default().push(Sample {
+                    time,
+                    sent: rate.sent as f32,
+                    recv: rate.recv as f32,
+                    ..Default::default()
+                });
+            }
+        }
+    }
+
+    // Returns a copy to save once FLUSH_INTERVAL has passed since the last one,
+    // so the file can be written without holding the lock
+    fn snapshot(&mut self) -> Option<HashMap<IpAddr, BackendHistory>> {
+        if self.last_flush.elapsed() < FLUSH_INTERVAL {
+            return None;
+        }
+        self.last_flush = Instant::now();
+
+        Some(self.backends.clone())
+    }
+
+    // Returns the history of a backend averaged into evenly spaced points, newest first
+    pub fn range(&self, addr: IpAddr, range: HistoryRange) -> Vec<Option<Sample>> {
+        self.backends
+            .get(&addr)
+            .map(|x| x.metrics.range(range))
+            .unwrap_or_default()
+    }
+
+    pub fn interface_range(
+        &self,
+        addr: IpAddr,
+        interface: &str,
+        range: HistoryRange,
+    ) -> Vec<Option<Sample>> {
+        self.backends
+            .get(&addr)
+            .and_then(|x| x.interfaces.get(interface))
+            .map(|x| x.range(range))
+            .unwrap_or_default()
+    }
+
+    pub fn disk_range(&self, addr: IpAddr, disk: &str, range: HistoryRange) -> Vec<Option<Sample>> {
+        self.backends
+            .get(&addr)
+            .and_then(|x| x.disks.get(disk))
+            .map(|x| x.range(range))
+            .unwrap_or_default()
+    }
+}
+
+#[derive(Clone)]
+pub struct SharedHistory(Arc<Mutex<HistoryStore>>);
+
+impl SharedHistory {
+    pub fn new() -> Self {
+        Self(Arc::new(Mutex::new(HistoryStore::new())))
+    }
+
+    pub fn get(&self) -> impl DerefMut<Target = HistoryStore> {
+        self.0.lock().unwrap()
+    }
+
+    pub async fn run(self, registry: SharedBackendRegistry) {
+        let mut interval = tokio::time::interval(SAMPLE_INTERVAL);
+        interval.set_missed_tick_behavior(MissedTickBehavior::Skip);
+
+        loop {
+            interval.tick().await;
+
+            let handles: Vec<_> = registry
+                .lock()
+                .unwrap()
+                .connected()
+                .iter()
+                .map(|(addr, info)| (*addr, info.handle.clone()))
+                .collect();
+
+            let samples = join_all(
+                handles
+                    .into_iter()
+                    .map(async |(addr, handle)| (addr, sample_backend(&handle).await)),
+            )
+            .await;
+
+            let time = now_secs();
+            let snapshot = {
+                let mut store = self.get();
+
+                for (addr, sample) in samples {
+                    match sample {
+                        Ok(raw) => store.push(addr, time, raw),
+                        Err(err) => debug!("Failed to sample metrics from backend {addr}: {err:#}"),
+                    }
+                }
+
+                store.snapshot()
+            };
+
+            if let Some(snapshot) = snapshot {
+                tokio::task::spawn_blocking(move || {
+                    if let Err(err) = storage::write(HISTORY_FILE, &snapshot) {
+                        error!("Failed to save metrics history: {err:#}");
+                    }
+                });
+            }
+        }
+    }
+}
+
+async fn sample_backend(handle: &BackendHandle) -> Result<HistorySampleResponse> {
+    let response = handle.send_req(RequestFrontendMessage::HistorySample);
+
+    // A backend that doesn't respond in time just misses this sample
+    let response = tokio::time::timeout(SAMPLE_INTERVAL, response)
+        .await
+        .context("backend took too long to respond")??;
+
+    match response {
+        ResponseBackendMessage::HistorySample(sample) => Ok(sample),
+        _ => Err(anyhow!("backend sent mismatched response")),
+    }
+}
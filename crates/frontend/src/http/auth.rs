@@ -1,50 +1,180 @@
 use std::{
-    collections::HashMap,
+    net::IpAddr,
     ops::DerefMut,
     sync::{Arc, Mutex},
-    time::{Duration, Instant},
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
 };
 
-pub struct LoginMap(HashMap<[u8; 12], Instant>);
+use bitcode::{Decode, Encode};
+use config::frontend::FrontendConfig;
+use log::warn;
 
-impl LoginMap {
-    pub fn new() -> Self {
-        Self(HashMap::new())
-    }
+use crate::{
+    SharedConfig,
+    storage::{self, EncryptedWriter},
+};
 
-    pub fn new_token(&mut self) -> String {
-        let now = Instant::now();
-        let bytes: [u8; 12] = rand::random();
+const SESSIONS_FILE: &str = "sessions.bin";
 
-        self.0.insert(bytes, now);
+// Activity only moves the idle deadline forward, so it doesn't need to hit the disk on every request
+const FLUSH_INTERVAL: Duration = Duration::from_secs(60);
+
+pub fn now_secs() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs()
+}
 
-        data_encoding::HEXLOWER.encode(&bytes)
+fn is_expired(config: &FrontendConfig, session: &Session, now: u64) -> bool {
+    if session.remember {
+        now.saturating_sub(session.last_seen) >= config.session_remember_timeout
+            || now.saturating_sub(session.created) >= config.session_remember_max_age
+    } else {
+        now.saturating_sub(session.last_seen) >= config.session_idle_timeout
+            || now.saturating_sub(session.created) >= config.session_absolute_timeout
     }
+}
 
-    pub fn contains_token(&mut self, token: &str) -> bool {
-        let now = Instant::now();
-        self.0
-            .retain(|_, earlier| now.duration_since(*earlier) < Duration::from_secs(3600));
+#[derive(Debug, Clone, Encode, Decode)]
+pub struct Session {
+    token: [u8; 12],
+    pub id: u32,
+    pub created: u64,
+    pub last_seen: u64,
+    pub remember: bool,
+    pub addr: Option<IpAddr>,
+    pub user_agent: String,
+}
 
-        let Ok(bytes) = data_encoding::HEXLOWER.decode(token.as_bytes()) else {
-            return false;
+pub struct LoginMap {
+    sessions: Vec<Session>,
+    config: SharedConfig,
+    writer: EncryptedWriter<Vec<Session>>,
+    dirty: bool,
+    last_flush: Instant,
+}
+
+impl LoginMap {
+    pub fn new(config: SharedConfig) -> Self {
+        let sessions = match storage::read_encrypted(SESSIONS_FILE, &config.secret.0) {
+            Ok(sessions) => sessions.unwrap_or_default(),
+            Err(err) => {
+                warn!("Discarding saved sessions: {err:#}");
+                Vec::new()
+            }
         };
-        let Ok(bytes) = <[u8; 12]>::try_from(bytes) else {
-            return false;
+
+        let mut map = Self {
+            sessions,
+            writer: EncryptedWriter::new(SESSIONS_FILE, config.secret.0),
+            config,
+            dirty: false,
+            last_flush: Instant::now(),
         };
+        map.remove_expired(now_secs());
 
-        self.0.contains_key(&bytes)
+        map
     }
 
-    pub fn delete_token(&mut self, token: &str) {
-        let Ok(bytes) = data_encoding::HEXLOWER.decode(token.as_bytes()) else {
+    fn remove_expired(&mut self, now: u64) {
+        let len = self.sessions.len();
+
+        let config = &self.config;
+        self.sessions
+            .retain(|session| !is_expired(config, session, now));
+
+        if self.sessions.len() != len {
+            self.dirty = true;
+        }
+    }
+
+    fn flush(&mut self, force: bool) {
+        if !self.dirty || (!force && self.last_flush.elapsed() < FLUSH_INTERVAL) {
             return;
-        };
-        let Ok(bytes) = <[u8; 12]>::try_from(bytes) else {
+        }
+
+        self.writer.save(self.sessions.clone());
+        self.dirty = false;
+        self.last_flush = Instant::now();
+    }
+
+    fn decode_token(token: &str) -> Option<[u8; 12]> {
+        let bytes = data_encoding::HEXLOWER.decode(token.as_bytes()).ok()?;
+        bytes.try_into().ok()
+    }
+
+    pub fn new_token(
+        &mut self,
+        remember: bool,
+        addr: Option<IpAddr>,
+        user_agent: String,
+    ) -> String {
+        let now = now_secs();
+        let token: [u8; 12] = rand::random();
+
+        self.sessions.push(Session {
+            token,
+            id: rand::random(),
+            created: now,
+            last_seen: now,
+            remember,
+            addr,
+            user_agent,
+        });
+        self.dirty = true;
+        self.flush(true);
+
+        data_encoding::HEXLOWER.encode(&token)
+    }
+
+    // Checks that the token belongs to a live session, and extends its idle deadline if so
+    pub fn touch_token(&mut self, token: &str) -> Option<u32> {
+        let now = now_secs();
+        self.remove_expired(now);
+
+        let token = Self::decode_token(token)?;
+        let session = self.sessions.iter_mut().find(|x| x.token == token)?;
+
+        session.last_seen = now;
+        let id = session.id;
+
+        self.dirty = true;
+        self.flush(false);
+
+        Some(id)
+    }
+
+    // Same as touch_token, but without extending the session
+    pub fn token_session(&mut self, token: &str) -> Option<u32> {
+        self.remove_expired(now_secs());
+
+        let token = Self::decode_token(token)?;
+        self.sessions
+            .iter()
+            .find(|x| x.token == token)
+            .map(|x| x.id)
+    }
+
+    pub fn delete_token(&mut self, token: &str) {
+        let Some(token) = Self::decode_token(token) else {
             return;
         };
 
-        self.0.remove(&bytes);
+        self.sessions.retain(|x| x.token != token);
+        self.dirty = true;
+        self.flush(true);
+    }
+
+    pub fn revoke(&mut self, id: u32) {
+        self.sessions.retain(|x| x.id != id);
+        self.dirty = true;
+        self.flush(true);
+    }
+
+    pub fn sessions(&mut self) -> &[Session] {
+        self.remove_expired(now_secs());
+        &self.sessions
     }
 }
 
@@ -52,8 +182,8 @@ impl LoginMap {
 pub struct SharedLoginMap(Arc<Mutex<LoginMap>>);
 
 impl SharedLoginMap {
-    pub fn new() -> Self {
-        Self(Arc::new(Mutex::new(LoginMap::new())))
+    pub fn new(config: SharedConfig) -> Self {
+        Self(Arc::new(Mutex::new(LoginMap::new(config))))
     }
 
     pub fn get(&self) -> impl DerefMut<Target = LoginMap> {
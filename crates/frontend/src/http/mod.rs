@@ -1,4 +1,7 @@
-use std::net::SocketAddr;
+use std::{
+    net::SocketAddr,
+    sync::{Arc, OnceLock},
+};
 
 use anyhow::{Context, Result, anyhow};
 use auth::SharedLoginMap;
@@ -7,9 +10,13 @@ use hyper::service::service_fn;
 use log::{error, info};
 use request::ServerRequest;
 use router::router;
+use tokens::SharedApiTokens;
 use tokio::net::TcpListener;
 
-use crate::{SharedConfig, backend::SharedBackendRegistry};
+use crate::{
+    SharedConfig, alerts::SharedAlerts, audit::SharedAuditLog, backend::SharedBackendRegistry,
+    commands::SharedCommandHistory, history::SharedHistory, jobs::SharedJobs,
+};
 
 pub mod auth;
 pub mod query_array;
@@ -17,12 +24,19 @@ pub mod request;
 pub mod response;
 mod router;
 mod statics;
+pub mod tokens;
 
 #[derive(Clone)]
 pub struct FrontendContext {
     backends: SharedBackendRegistry,
     config: SharedConfig,
     logins: SharedLoginMap,
+    tokens: SharedApiTokens,
+    audit: SharedAuditLog,
+    history: SharedHistory,
+    alerts: SharedAlerts,
+    commands: SharedCommandHistory,
+    jobs: SharedJobs,
 }
 
 pub struct HttpServer {
@@ -31,7 +45,14 @@ pub struct HttpServer {
 }
 
 impl HttpServer {
-    pub async fn new(config: SharedConfig, backends: SharedBackendRegistry) -> Result<Self> {
+    pub async fn new(
+        config: SharedConfig,
+        backends: SharedBackendRegistry,
+        history: SharedHistory,
+        alerts: SharedAlerts,
+        audit: SharedAuditLog,
+        jobs: SharedJobs,
+    ) -> Result<Self> {
         info!("Starting web server on port {}", config.http_port);
 
         let addr = SocketAddr::from((config.http_subnet, config.http_port));
@@ -50,14 +71,22 @@ impl HttpServer {
             acceptor = acceptor.with_tls(tls, true);
         }
 
-        let logins = SharedLoginMap::new();
+        let logins = SharedLoginMap::new(config.clone());
+        let tokens = SharedApiTokens::new(config.clone());
+        let commands = SharedCommandHistory::new(config.clone());
 
         Ok(Self {
             acceptor,
             context: FrontendContext {
                 config,
                 logins,
+                tokens,
                 backends,
+                audit,
+                history,
+                alerts,
+                commands,
+                jobs,
             },
         })
     }
@@ -66,12 +95,19 @@ impl HttpServer {
         loop {
             let ctx = self.context.clone();
 
+            // The service has to be built before the connection is accepted, so the peer address gets filled in afterwards
+            let peer_addr = Arc::new(OnceLock::new());
+            let service_peer_addr = peer_addr.clone();
+
             let service = service_fn(move |req| {
-                let req = ServerRequest::new(req, ctx.clone());
+                let peer_ip = service_peer_addr.get().copied();
+                let req = ServerRequest::new(req, ctx.clone(), peer_ip);
                 async move { router(req).await }
             });
 
-            if let Ok((_, conn_fut)) = self.acceptor.accept(service).await {
+            if let Ok((addr, conn_fut)) = self.acceptor.accept(service).await {
+                let _ = peer_addr.set(addr.ip().to_canonical());
+
                 tokio::spawn(async move {
                     if let Err(err) = conn_fut.await
                         && !err.ignorable()
@@ -1,28 +1,31 @@
 use std::{
-    collections::HashMap,
+    collections::{BTreeMap, HashMap},
     net::IpAddr,
     ops::{Deref, DerefMut},
 };
 
 use config::frontend::FrontendConfig;
 use http_body_util::BodyExt;
-use hyper::{
-    StatusCode,
-    body::{Bytes, Incoming},
-    header,
-    http::request::Parts as RequestParts,
-};
+use hyper::{StatusCode, body::Incoming, header, http::request::Parts as RequestParts};
 use proto::{
     backend::ResponseBackendMessage,
     frontend::{ActionFrontendMessage, RequestFrontendMessage},
 };
 
-use crate::backend::BackendHandle;
+use crate::{
+    alerts::SharedAlerts,
+    audit::{self, AuditEntry, SharedAuditLog},
+    backend::{BackendHandle, SharedBackendRegistry},
+    commands::SharedCommandHistory,
+    history::SharedHistory,
+    jobs::SharedJobs,
+};
 
 use super::{
     FrontendContext,
-    auth::SharedLoginMap,
+    auth::{SharedLoginMap, now_secs},
     response::{RedirectType, ServerResponse},
+    tokens::{ApiScope, SharedApiTokens},
 };
 
 pub type HyperRequest = hyper::Request<Incoming>;
@@ -42,14 +45,15 @@ fn get_cookies(parts: &RequestParts) -> HashMap<String, String> {
 }
 
 pub struct BackendData {
-    pub backend_list: Vec<(IpAddr, String)>,
     pub current_backend: CurrentBackendData,
 }
 
 pub struct CurrentBackendData {
     pub addr: IpAddr,
+    pub nickname: String,
     pub handle: BackendHandle,
     pub update: Option<String>,
+    pub tags: BTreeMap<String, String>,
 }
 
 pub struct ServerRequest {
@@ -57,10 +61,13 @@ pub struct ServerRequest {
     body: Option<Incoming>,
     cookies: HashMap<String, String>,
     context: FrontendContext,
+    peer_ip: Option<IpAddr>,
+    backend_override: Option<IpAddr>,
+    api_token: Option<String>,
 }
 
 impl ServerRequest {
-    pub fn new(req: HyperRequest, context: FrontendContext) -> Self {
+    pub fn new(req: HyperRequest, context: FrontendContext, peer_ip: Option<IpAddr>) -> Self {
         let (parts, body) = req.into_parts();
 
         let cookies = get_cookies(&parts);
@@ -70,9 +77,45 @@ impl ServerRequest {
             body: Some(body),
             cookies,
             context,
+            peer_ip,
+            backend_override: None,
+            api_token: None,
+        }
+    }
+
+    pub fn is_api(&self) -> bool {
+        self.path_segments().next() == Some("api")
+    }
+
+    // API clients expect errors as JSON, while pages just show the text
+    pub fn error(&self, status: StatusCode, msg: impl Into<String>) -> ServerResponse {
+        let msg = msg.into();
+        let resp = ServerResponse::new().status(status);
+
+        if self.is_api() {
+            resp.json(&serde_json::json!({ "error": msg }))
+        } else {
+            resp.body(msg)
         }
     }
 
+    // Makes further backend requests go to this backend, instead of the one chosen with the cookie
+    pub fn select_backend(&mut self, addr: IpAddr) {
+        self.backend_override = Some(addr);
+    }
+
+    pub fn peer_ip(&self) -> Option<IpAddr> {
+        self.peer_ip
+    }
+
+    pub fn user_agent(&self) -> String {
+        self.headers
+            .get(header::USER_AGENT)
+            .and_then(|x| x.to_str().ok())
+            .unwrap_or_default()
+            .into()
+    }
+
     pub fn path_segments(&self) -> impl Iterator<Item = &str> {
         self.uri.path().split('/').filter(|x| !x.is_empty())
     }
@@ -82,52 +125,84 @@ impl ServerRequest {
     }
 
     pub fn extract_backends(&self) -> Result<BackendData, ServerResponse> {
-        let backends = self.context.backends.lock().unwrap();
-        let backend_list: Vec<_> = backends
-            .iter()
-            .map(|(addr, info)| (*addr, info.nickname.clone()))
-            .collect();
-
-        if backend_list.is_empty() {
-            return Err(ServerResponse::new()
-                .status(StatusCode::SERVICE_UNAVAILABLE)
-                .body("no connected backends"));
+        let registry = self.context.backends.lock().unwrap();
+        let backends = registry.connected();
+        if backends.is_empty() {
+            return Err(self.error(StatusCode::SERVICE_UNAVAILABLE, "no connected backends"));
         }
 
         let current_backend = {
-            let cookie_ip = self
-                .cookies
-                .get("backend")
-                .and_then(|x| x.parse::<IpAddr>().ok());
-
-            let (&addr, backend_info) = cookie_ip
-                .and_then(|x| backends.get_key_value(&x))
-                .or_else(|| backends.get_key_value(&backend_list[0].0))
-                .unwrap();
+            let (&addr, backend_info) = if let Some(addr) = self.backend_override {
+                backends.get_key_value(&addr).ok_or_else(|| {
+                    self.error(
+                        StatusCode::NOT_FOUND,
+                        format!("backend {addr} is not connected"),
+                    )
+                })?
+            } else {
+                let cookie_ip = self
+                    .cookies
+                    .get("backend")
+                    .and_then(|x| x.parse::<IpAddr>().ok());
+
+                cookie_ip
+                    .and_then(|x| backends.get_key_value(&x))
+                    .or_else(|| backends.iter().next())
+                    .unwrap()
+            };
 
             CurrentBackendData {
                 addr,
+                nickname: backend_info.nickname.clone(),
                 handle: backend_info.handle.clone(),
                 update: backend_info.update.clone(),
+                tags: backend_info.tags.clone(),
             }
         };
 
-        Ok(BackendData {
-            backend_list,
-            current_backend,
-        })
+        Ok(BackendData { current_backend })
+    }
+
+    // For pages that talk to every backend at once, instead of just the selected one
+    pub fn extract_all_backends(&self) -> Vec<CurrentBackendData> {
+        self.context
+            .backends
+            .lock()
+            .unwrap()
+            .connected()
+            .iter()
+            .map(|(addr, info)| CurrentBackendData {
+                addr: *addr,
+                nickname: info.nickname.clone(),
+                handle: info.handle.clone(),
+                update: info.update.clone(),
+                tags: info.tags.clone(),
+            })
+            .collect()
     }
 
     pub async fn send_backend_req(
         &self,
         req: RequestFrontendMessage,
     ) -> Result<ResponseBackendMessage, ServerResponse> {
-        let backend_handle = self.extract_backends()?.current_backend.handle;
+        let backend = self.extract_backends()?.current_backend;
+        let audit = audit::describe_request(&req);
+
+        let result = backend.handle.send_req(req).await;
 
-        backend_handle.send_req(req).await.map_err(|err| {
-            ServerResponse::new()
-                .status(StatusCode::BAD_GATEWAY)
-                .body(format!("backend request failed: {err}"))
+        if let Some((action, args)) = audit {
+            let outcome = match &result {
+                Ok(_) => "ok".into(),
+                Err(err) => format!("failed: {err}"),
+            };
+            self.record_audit(&backend, action, args, outcome);
+        }
+
+        result.map_err(|err| {
+            self.error(
+                StatusCode::BAD_GATEWAY,
+                format!("backend request failed: {err}"),
+            )
         })
     }
 
@@ -135,22 +210,65 @@ impl ServerRequest {
         &self,
         msg: ActionFrontendMessage,
     ) -> Result<(), ServerResponse> {
-        let backend_handle = self.extract_backends()?.current_backend.handle;
+        let backend = self.extract_backends()?.current_backend;
+        let audit = audit::describe_action(&msg);
 
-        backend_handle.send_action(msg).await.map_err(|err| {
-            ServerResponse::new()
-                .status(StatusCode::BAD_GATEWAY)
-                .body(format!("backend action failed: {err}"))
+        let result = backend.handle.send_action(msg).await;
+
+        if let Some((action, args)) = audit {
+            let outcome = match &result {
+                Ok(()) => "sent".into(),
+                Err(err) => format!("failed: {err}"),
+            };
+            self.record_audit(&backend, action, args, outcome);
+        }
+
+        result.map_err(|err| {
+            self.error(
+                StatusCode::BAD_GATEWAY,
+                format!("backend action failed: {err}"),
+            )
         })
     }
 
+    pub fn record_audit(
+        &self,
+        backend: &CurrentBackendData,
+        action: &str,
+        args: String,
+        result: String,
+    ) {
+        let session = match &self.api_token {
+            Some(name) => Some(format!("token:{name}")),
+            None => self.session_id().map(|id| format!("{id:08x}")),
+        };
+
+        let entry = AuditEntry {
+            time: now_secs(),
+            session,
+            source: self.peer_ip,
+            backend: backend.addr,
+            nickname: backend.nickname.clone(),
+            action: action.into(),
+            args,
+            result,
+        };
+
+        self.context.audit.record(entry);
+    }
+
+    pub fn extract_audit(&self) -> SharedAuditLog {
+        self.context.audit.clone()
+    }
+
     pub fn extract_query<Qu: serde::de::DeserializeOwned>(&self) -> Result<Qu, ServerResponse> {
         let query = self.uri.query().unwrap_or_default();
 
         serde_urlencoded::from_str(query).map_err(|err| {
-            ServerResponse::new()
-                .status(StatusCode::BAD_REQUEST)
-                .body(format!("invalid query params: {err}"))
+            self.error(
+                StatusCode::BAD_REQUEST,
+                format!("invalid query params: {err}"),
+            )
         })
     }
 
@@ -159,24 +277,32 @@ impl ServerRequest {
     ) -> Result<T, ServerResponse> {
         let body = self.extract_body().await?;
 
-        let body = body.collect().await.map_err(|_| {
-            ServerResponse::new()
-                .status(StatusCode::BAD_REQUEST)
-                .body("needs body")
-        })?;
+        let body = body
+            .collect()
+            .await
+            .map_err(|_| self.error(StatusCode::BAD_REQUEST, "needs body"))?;
 
-        serde_urlencoded::from_bytes(&body.to_bytes()).map_err(|_| {
-            ServerResponse::new()
-                .status(StatusCode::BAD_REQUEST)
-                .body("invalid form body")
-        })
+        serde_urlencoded::from_bytes(&body.to_bytes())
+            .map_err(|_| self.error(StatusCode::BAD_REQUEST, "invalid form body"))
+    }
+
+    pub async fn extract_json<T: serde::de::DeserializeOwned>(
+        &mut self,
+    ) -> Result<T, ServerResponse> {
+        let body = self.extract_body().await?;
+
+        let body = body
+            .collect()
+            .await
+            .map_err(|_| self.error(StatusCode::BAD_REQUEST, "needs body"))?;
+
+        serde_json::from_slice(&body.to_bytes())
+            .map_err(|err| self.error(StatusCode::BAD_REQUEST, format!("invalid json body: {err}")))
     }
 
     pub async fn extract_body(&mut self) -> Result<Incoming, ServerResponse> {
         let Some(body) = self.body.take() else {
-            return Err(ServerResponse::new()
-                .status(StatusCode::INTERNAL_SERVER_ERROR)
-                .body("form already extracted"));
+            return Err(self.error(StatusCode::INTERNAL_SERVER_ERROR, "body already extracted"));
         };
 
         Ok(body)
@@ -199,7 +325,7 @@ impl ServerRequest {
                 return err_resp;
             };
 
-            if !self.context.logins.get().contains_token(token) {
+            if self.context.logins.get().touch_token(token).is_none() {
                 return err_resp;
             }
         }
@@ -207,6 +333,58 @@ impl ServerRequest {
         Ok(())
     }
 
+    // API requests are authenticated with a bearer token instead of the login cookie
+    pub fn check_token(&mut self, scope: ApiScope) -> Result<(), ServerResponse> {
+        let token = self
+            .headers
+            .get(header::AUTHORIZATION)
+            .and_then(|x| x.to_str().ok())
+            .and_then(|x| x.strip_prefix("Bearer "));
+
+        let Some(token) = token else {
+            return Err(self.error(StatusCode::UNAUTHORIZED, "missing bearer token"));
+        };
+
+        let Some(token) = self.context.tokens.get().verify(token) else {
+            return Err(self.error(StatusCode::UNAUTHORIZED, "invalid or revoked token"));
+        };
+
+        if !token.scope.allows(scope) {
+            return Err(self.error(
+                StatusCode::FORBIDDEN,
+                format!("token doesn't have the {scope} scope"),
+            ));
+        }
+
+        self.api_token = Some(token.name);
+
+        Ok(())
+    }
+
+    pub fn extract_alerts(&self) -> SharedAlerts {
+        self.context.alerts.clone()
+    }
+
+    pub fn extract_commands(&self) -> SharedCommandHistory {
+        self.context.commands.clone()
+    }
+
+    pub fn extract_history(&self) -> SharedHistory {
+        self.context.history.clone()
+    }
+
+    pub fn extract_jobs(&self) -> SharedJobs {
+        self.context.jobs.clone()
+    }
+
+    pub fn extract_backend_registry(&self) -> SharedBackendRegistry {
+        self.context.backends.clone()
+    }
+
+    pub fn extract_tokens(&self) -> SharedApiTokens {
+        self.context.tokens.clone()
+    }
+
     pub fn delete_login(&self) {
         if self.config().enable_login
             && let Some(token) = self.cookies.get("token")
@@ -215,6 +393,17 @@ impl ServerRequest {
         }
     }
 
+    // Returns the ID of the session making this request, if login is enabled
+    pub fn session_id(&self) -> Option<u32> {
+        if !self.config().enable_login {
+            return None;
+        }
+
+        // check_login has already touched the session for this request
+        let token = self.cookies.get("token")?;
+        self.context.logins.get().token_session(token)
+    }
+
     pub fn extract_logins(&self) -> SharedLoginMap {
         self.context.logins.clone()
     }
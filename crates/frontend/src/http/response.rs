@@ -58,6 +58,14 @@ impl ServerResponse {
         self
     }
 
+    pub fn json<T: serde::Serialize>(self, val: &T) -> Self {
+        // All of the types sent through here serialize infallibly
+        let body = serde_json::to_vec(val).unwrap();
+
+        self.header(header::CONTENT_TYPE, "application/json")
+            .body(body)
+    }
+
     pub fn stream_body<T: Stream<Item = I> + Send + Sync + 'static, I: Into<Bytes>>(
         mut self,
         stream: T,
@@ -1,6 +1,6 @@
 use hyper::{Method, StatusCode, header};
 
-use crate::pages::*;
+use crate::{api, metrics, pages::*};
 
 use super::response::{BuiltResponse, RedirectType, ServerResponse};
 use super::{request::ServerRequest, statics};
@@ -41,21 +41,67 @@ pub async fn router(req: ServerRequest) -> Result<BuiltResponse, std::convert::I
 
         (GET, ["system"]) => system::page,
 
+        (GET, ["fleet"]) => fleet::page,
+        (GET, ["fleet", "select"]) => fleet::select,
+        (POST, ["fleet", "forget"]) => fleet::forget,
+
+        (GET, ["commands"]) => commands::page,
+        (POST, ["commands", "run"]) => commands::run,
+
+        (GET, ["jobs"]) => jobs::page,
+        (POST, ["jobs", "create"]) => jobs::create,
+        (POST, ["jobs", "toggle"]) => jobs::toggle,
+        (POST, ["jobs", "run"]) => jobs::run,
+        (POST, ["jobs", "delete"]) => jobs::delete,
+
         (GET, ["process"]) => process::page,
+        (GET, ["process", "detail"]) => process::detail,
         (POST, ["process", "signal"]) => process::signal,
 
         (GET, ["software"]) => software::page,
         (POST, ["software"]) => software::form,
+        (GET, ["software", "stream"]) => software::stream,
+        (GET, ["software", "status"]) => software::status,
 
         (GET, ["service"]) => service::page,
 
         (GET, ["management"]) => management::page,
+        (POST, ["management", "sessions", "revoke"]) => management::revoke_session,
+        (POST, ["management", "tokens", "create"]) => management::create_token,
+        (POST, ["management", "tokens", "revoke"]) => management::revoke_token,
 
         (GET, ["terminal"]) => terminal::page,
         (GET, ["terminal", "stream"]) => terminal::stream,
         (POST, ["terminal", "write"]) => terminal::write,
         (POST, ["terminal", "resize"]) => terminal::resize,
 
+        (GET, ["audit"]) => audit::page,
+        (GET, ["audit", "export"]) => audit::export,
+
+        (GET, ["metrics"]) => metrics::export,
+
+        (GET, ["api", "v1", "backends"]) => api::backends,
+        (GET, ["api", "v1", "backends", _, "cpu"]) => api::cpu,
+        (GET, ["api", "v1", "backends", _, "temp"]) => api::temp,
+        (GET, ["api", "v1", "backends", _, "memory"]) => api::memory,
+        (GET, ["api", "v1", "backends", _, "disks"]) => api::disks,
+        (GET, ["api", "v1", "backends", _, "network"]) => api::network,
+        (GET, ["api", "v1", "backends", _, "disk-io"]) => api::disk_io,
+        (GET, ["api", "v1", "backends", _, "processes"]) => api::processes,
+        (GET, ["api", "v1", "backends", _, "processes", _]) => api::process,
+        (POST, ["api", "v1", "backends", _, "processes", _, "signal"]) => api::signal,
+        (GET, ["api", "v1", "backends", _, "host"]) => api::host,
+        (GET, ["api", "v1", "backends", _, "services"]) => api::services,
+        (GET, ["api", "v1", "backends", _, "software"]) => api::software,
+        (POST, ["api", "v1", "backends", _, "software"]) => api::software_action,
+        (GET, ["api", "v1", "backends", _, "directory"]) => api::directory,
+        (POST, ["api", "v1", "backends", _, "files", "new-file"]) => api::new_file,
+        (POST, ["api", "v1", "backends", _, "files", "new-folder"]) => api::new_folder,
+        (POST, ["api", "v1", "backends", _, "files", "rename"]) => api::rename,
+        (POST, ["api", "v1", "backends", _, "files", "delete-file"]) => api::delete_file,
+        (POST, ["api", "v1", "backends", _, "files", "delete-folder"]) => api::delete_folder,
+        (_, ["api", ..]) => api::not_found,
+
         (GET, ["browser"]) => browser::page,
         (GET, ["browser", "file"]) => browser::file,
         (POST, ["browser", "file", "save"]) => browser::save,
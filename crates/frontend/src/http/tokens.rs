@@ -0,0 +1,157 @@
+use std::{
+    fmt::Display,
+    ops::DerefMut,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use bitcode::{Decode, Encode};
+use log::warn;
+use ring::digest::{SHA256, digest};
+use serde::Deserialize;
+
+use crate::{
+    SharedConfig,
+    storage::{self, EncryptedWriter},
+};
+
+use super::auth::now_secs;
+
+const TOKENS_FILE: &str = "api-tokens.bin";
+
+// Same as sessions, the last used time is only informational so it can be saved lazily
+const FLUSH_INTERVAL: Duration = Duration::from_secs(60);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Encode, Decode, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ApiScope {
+    Read,
+    Write,
+}
+
+impl ApiScope {
+    // Write tokens can do anything read tokens can
+    pub fn allows(self, scope: ApiScope) -> bool {
+        self == ApiScope::Write || scope == ApiScope::Read
+    }
+}
+
+impl Display for ApiScope {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ApiScope::Read => f.write_str("read"),
+            ApiScope::Write => f.write_str("write"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Encode, Decode)]
+pub struct ApiToken {
+    // Only the hash is stored, the token itself is shown once when it's created
+    hash: [u8; 32],
+    pub id: u32,
+    pub name: String,
+    pub scope: ApiScope,
+    pub created: u64,
+    pub last_used: Option<u64>,
+}
+
+fn hash_token(token: &str) -> [u8; 32] {
+    // SHA-256 always outputs 32 bytes
+    digest(&SHA256, token.as_bytes())
+        .as_ref()
+        .try_into()
+        .unwrap()
+}
+
+pub struct ApiTokenMap {
+    tokens: Vec<ApiToken>,
+    writer: EncryptedWriter<Vec<ApiToken>>,
+    dirty: bool,
+    last_flush: Instant,
+}
+
+impl ApiTokenMap {
+    pub fn new(config: SharedConfig) -> Self {
+        let tokens = match storage::read_encrypted(TOKENS_FILE, &config.secret.0) {
+            Ok(tokens) => tokens.unwrap_or_default(),
+            Err(err) => {
+                warn!("Discarding saved API tokens: {err:#}");
+                Vec::new()
+            }
+        };
+
+        Self {
+            tokens,
+            writer: EncryptedWriter::new(TOKENS_FILE, config.secret.0),
+            dirty: false,
+            last_flush: Instant::now(),
+        }
+    }
+
+    fn flush(&mut self, force: bool) {
+        if !self.dirty || (!force && self.last_flush.elapsed() < FLUSH_INTERVAL) {
+            return;
+        }
+
+        self.writer.save(self.tokens.clone());
+        self.dirty = false;
+        self.last_flush = Instant::now();
+    }
+
+    pub fn create(&mut self, name: String, scope: ApiScope) -> String {
+        let bytes: [u8; 24] = rand::random();
+        let token = format!("dp_{}", data_encoding::HEXLOWER.encode(&bytes));
+
+        self.tokens.push(ApiToken {
+            hash: hash_token(&token),
+            id: rand::random(),
+            name,
+            scope,
+            created: now_secs(),
+            last_used: None,
+        });
+        self.dirty = true;
+        self.flush(true);
+
+        token
+    }
+
+    // Returns the matching token, and marks it as used
+    pub fn verify(&mut self, token: &str) -> Option<ApiToken> {
+        let hash = hash_token(token);
+        let found = self.tokens.iter_mut().find(|x| x.hash == hash)?;
+
+        // Only written out once a minute at most, verifying a token shouldn't wait on the disk
+        found.last_used = Some(now_secs());
+        let found = found.clone();
+
+        self.dirty = true;
+        self.flush(false);
+
+        Some(found)
+    }
+
+    pub fn revoke(&mut self, id: u32) {
+        self.tokens.retain(|x| x.id != id);
+        self.dirty = true;
+        self.flush(true);
+    }
+
+    pub fn tokens(&self) -> &[ApiToken] {
+        &self.tokens
+    }
+}
+
+#[derive(Clone)]
+pub struct SharedApiTokens(Arc<Mutex<ApiTokenMap>>);
+
+impl SharedApiTokens {
+    pub fn new(config: SharedConfig) -> Self {
+        Self(Arc::new(Mutex::new(ApiTokenMap::new(config))))
+    }
+
+    pub fn get(&self) -> impl DerefMut<Target = ApiTokenMap> {
+        self.0.lock().unwrap()
+    }
+}
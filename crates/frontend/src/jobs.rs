@@ -0,0 +1,425 @@
+use std::{
+    collections::{HashSet, VecDeque},
+    net::IpAddr,
+    ops::DerefMut,
+    str::FromStr,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use anyhow::{Context, Result};
+use bitcode::{Decode, Encode};
+use chrono::{Local, TimeZone};
+use croner::Cron;
+use log::{info, warn};
+use serde::Deserialize;
+use tokio::time::MissedTickBehavior;
+
+use crate::{
+    SharedConfig,
+    audit::{self, AuditEntry, SharedAuditLog},
+    backend::{SharedBackendRegistry, matches_tag},
+    commands::{self, CommandOutcome, CommandRun, HostResult},
+    http::{auth::now_secs, request::CurrentBackendData},
+    storage::{self, EncryptedWriter},
+};
+
+const JOBS_FILE: &str = "jobs.bin";
+
+const CHECK_INTERVAL: Duration = Duration::from_secs(5);
+
+// Runs kept per job, older ones get dropped
+const MAX_RUNS: usize = 20;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Encode, Decode, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum OfflinePolicy {
+    Skip,
+    Queue,
+}
+
+#[derive(Debug, Clone, Encode, Decode)]
+pub struct Job {
+    pub id: u32,
+    pub name: String,
+    pub schedule: String,
+    pub command: String,
+    pub backends: Vec<IpAddr>,
+    pub tag: String,
+    pub timeout: u64,
+    pub offline: OfflinePolicy,
+    pub enabled: bool,
+    pub next_run: Option<u64>,
+    // Backends that were offline when the job was due, waiting to reconnect
+    pub queued: Vec<IpAddr>,
+    // Newest last
+    pub runs: VecDeque<CommandRun>,
+}
+
+pub struct NewJob {
+    pub name: String,
+    pub schedule: String,
+    pub command: String,
+    pub backends: Vec<IpAddr>,
+    pub tag: String,
+    pub timeout: u64,
+    pub offline: OfflinePolicy,
+}
+
+// Schedules use the standard 5 field cron syntax, in the frontend's local time
+pub fn parse_schedule(schedule: &str) -> Result<Cron> {
+    Cron::from_str(schedule).context("invalid schedule")
+}
+
+fn next_run(schedule: &str, after: u64) -> Option<u64> {
+    let cron = parse_schedule(schedule).ok()?;
+    let after = Local.timestamp_opt(after as i64, 0).single()?;
+
+    cron.find_next_occurrence(&after, false)
+        .ok()
+        .map(|x| x.timestamp() as u64)
+}
+
+impl Job {
+    fn push_run(&mut self, run: CommandRun) {
+        if self.runs.len() == MAX_RUNS {
+            self.runs.pop_front();
+        }
+        self.runs.push_back(run);
+    }
+
+    fn targets(&self, connected: &[CurrentBackendData]) -> Vec<IpAddr> {
+        let mut targets = self.backends.clone();
+
+        for backend in connected {
+            if !self.tag.is_empty()
+                && matches_tag(&backend.tags, &self.tag)
+                && !targets.contains(&backend.addr)
+            {
+                targets.push(backend.addr);
+            }
+        }
+
+        targets
+    }
+}
+
+pub struct JobStore {
+    jobs: Vec<Job>,
+    // Jobs with a run in progress, so a slow job doesn't pile up overlapping runs
+    running: HashSet<u32>,
+    writer: EncryptedWriter<Vec<Job>>,
+}
+
+impl JobStore {
+    pub fn new(config: SharedConfig) -> Self {
+        let mut jobs: Vec<Job> = match storage::read_encrypted(JOBS_FILE, &config.secret.0) {
+            Ok(jobs) => jobs.unwrap_or_default(),
+            Err(err) => {
+                warn!("Discarding saved jobs: {err:#}");
+                Vec::new()
+            }
+        };
+
+        // Runs that were missed while the frontend was down are skipped, rather than all running at once
+        let now = now_secs();
+        for job in &mut jobs {
+            if job.next_run.is_some_and(|x| x < now) {
+                job.next_run = next_run(&job.schedule, now);
+            }
+        }
+
+        Self {
+            jobs,
+            running: HashSet::new(),
+            writer: EncryptedWriter::new(JOBS_FILE, config.secret.0),
+        }
+    }
+
+    fn save(&self) {
+        self.writer.save(self.jobs.clone());
+    }
+
+    pub fn jobs(&self) -> &[Job] {
+        &self.jobs
+    }
+
+    pub fn get(&self, id: u32) -> Option<&Job> {
+        self.jobs.iter().find(|x| x.id == id)
+    }
+
+    pub fn is_running(&self, id: u32) -> bool {
+        self.running.contains(&id)
+    }
+
+    pub fn create(&mut self, job: NewJob) -> Result<()> {
+        parse_schedule(&job.schedule)?;
+
+        self.jobs.push(Job {
+            id: rand::random(),
+            next_run: next_run(&job.schedule, now_secs()),
+            name: job.name,
+            schedule: job.schedule,
+            command: job.command,
+            backends: job.backends,
+            tag: job.tag,
+            timeout: job.timeout,
+            offline: job.offline,
+            enabled: true,
+            queued: Vec::new(),
+            runs: VecDeque::new(),
+        });
+        self.save();
+
+        Ok(())
+    }
+
+    pub fn delete(&mut self, id: u32) {
+        self.jobs.retain(|x| x.id != id);
+        self.save();
+    }
+
+    pub fn toggle(&mut self, id: u32) {
+        if let Some(job) = self.jobs.iter_mut().find(|x| x.id == id) {
+            job.enabled = !job.enabled;
+            job.next_run = next_run(&job.schedule, now_secs());
+            job.queued.clear();
+        }
+        self.save();
+    }
+
+    fn record(&mut self, id: u32, run: CommandRun) {
+        // The job could have been deleted while it was running
+        if let Some(job) = self.jobs.iter_mut().find(|x| x.id == id) {
+            job.push_run(run);
+        }
+        self.save();
+    }
+}
+
+// Everything needed to run a job, taken out of the store so the lock isn't held while it runs
+struct Dispatch {
+    id: u32,
+    name: String,
+    command: String,
+    timeout: Duration,
+    targets: Vec<CurrentBackendData>,
+    offline: Vec<HostResult>,
+}
+
+// Clears a job's running flag once its run is over, even if the run panicked
+struct RunningGuard {
+    jobs: SharedJobs,
+    id: u32,
+}
+
+impl Drop for RunningGuard {
+    fn drop(&mut self) {
+        // The lock could be poisoned by that same panic
+        if let Ok(mut store) = self.jobs.0.lock() {
+            store.running.remove(&self.id);
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct SharedJobs(Arc<Mutex<JobStore>>);
+
+impl SharedJobs {
+    pub fn new(config: SharedConfig) -> Self {
+        Self(Arc::new(Mutex::new(JobStore::new(config))))
+    }
+
+    pub fn get(&self) -> impl DerefMut<Target = JobStore> {
+        self.0.lock().unwrap()
+    }
+
+    fn connected(registry: &SharedBackendRegistry) -> Vec<CurrentBackendData> {
+        registry
+            .lock()
+            .unwrap()
+            .connected()
+            .iter()
+            .map(|(addr, info)| CurrentBackendData {
+                addr: *addr,
+                nickname: info.nickname.clone(),
+                handle: info.handle.clone(),
+                update: info.update.clone(),
+                tags: info.tags.clone(),
+            })
+            .collect()
+    }
+
+    // Works out which backends a job runs on now, queueing or skipping the ones that are offline
+    fn prepare(job: &mut Job, targets: Vec<IpAddr>, connected: &[CurrentBackendData]) -> Dispatch {
+        let mut online = Vec::new();
+        let mut offline = Vec::new();
+
+        for addr in targets {
+            if let Some(backend) = connected.iter().find(|x| x.addr == addr) {
+                online.push(CurrentBackendData {
+                    addr,
+                    nickname: backend.nickname.clone(),
+                    handle: backend.handle.clone(),
+                    update: backend.update.clone(),
+                    tags: backend.tags.clone(),
+                });
+                continue;
+            }
+
+            let queued = job.offline == OfflinePolicy::Queue;
+            if queued && !job.queued.contains(&addr) {
+                job.queued.push(addr);
+            }
+
+            offline.push(HostResult {
+                addr,
+                nickname: addr.to_string(),
+                duration_ms: 0,
+                outcome: CommandOutcome::Offline { queued },
+            });
+        }
+
+        Dispatch {
+            id: job.id,
+            name: job.name.clone(),
+            command: job.command.clone(),
+            timeout: Duration::from_secs(job.timeout),
+            targets: online,
+            offline,
+        }
+    }
+
+    // Runs a job right away on all of its targets, regardless of its schedule
+    pub fn run_now(&self, id: u32, registry: &SharedBackendRegistry, audit: &SharedAuditLog) {
+        let connected = Self::connected(registry);
+
+        let dispatch = {
+            let mut store = self.get();
+            let store = &mut *store;
+            let Some(job) = store.jobs.iter_mut().find(|x| x.id == id) else {
+                return;
+            };
+            if !store.running.insert(id) {
+                info!(
+                    "Job \"{}\" is already running, not starting it again",
+                    job.name
+                );
+                return;
+            }
+            let targets = job.targets(&connected);
+            Self::prepare(job, targets, &connected)
+        };
+
+        tokio::spawn(self.clone().execute(dispatch, audit.clone()));
+    }
+
+    async fn execute(self, dispatch: Dispatch, audit: SharedAuditLog) {
+        let Dispatch {
+            id,
+            name,
+            command,
+            timeout,
+            targets,
+            offline,
+        } = dispatch;
+
+        let _guard = RunningGuard {
+            jobs: self.clone(),
+            id,
+        };
+
+        let mut run = commands::run(&targets, &command, timeout).await;
+
+        let args = audit::describe_command(&commands::command_action(&command));
+        for result in &run.results {
+            audit.record(AuditEntry {
+                time: run.time,
+                session: Some(format!("job:{name}")),
+                source: None,
+                backend: result.addr,
+                nickname: result.nickname.clone(),
+                action: "command".into(),
+                args: args.clone(),
+                result: result.outcome.summary(),
+            });
+        }
+
+        info!(
+            "Job \"{name}\" finished, {} of {} backends succeeded",
+            run.succeeded(),
+            run.results.len() + offline.len()
+        );
+
+        run.results.extend(offline);
+        self.get().record(id, run);
+    }
+
+    pub async fn run(self, registry: SharedBackendRegistry, audit: SharedAuditLog) {
+        let mut interval = tokio::time::interval(CHECK_INTERVAL);
+        interval.set_missed_tick_behavior(MissedTickBehavior::Skip);
+
+        loop {
+            interval.tick().await;
+
+            let now = now_secs();
+            let connected = Self::connected(&registry);
+            let mut dispatches = Vec::new();
+
+            {
+                let mut store = self.get();
+                let store = &mut *store;
+
+                for job in store.jobs.iter_mut().filter(|x| x.enabled) {
+                    // A run still in progress is left to finish. Queued backends wait for the
+                    // next check, while a scheduled run that comes due meanwhile is skipped
+                    if store.running.contains(&job.id) {
+                        if job.next_run.is_some_and(|x| x <= now) {
+                            job.next_run = next_run(&job.schedule, now);
+                            warn!("Job \"{}\" is still running, skipping this run", job.name);
+                        }
+                        continue;
+                    }
+
+                    // Queued backends that have come back get their missed run
+                    let returned: Vec<_> = job
+                        .queued
+                        .iter()
+                        .copied()
+                        .filter(|addr| connected.iter().any(|x| x.addr == *addr))
+                        .collect();
+                    let due = job.next_run.is_some_and(|x| x <= now);
+
+                    // Both are folded into one run, so the job never runs twice at once
+                    let mut targets = Vec::new();
+                    if due {
+                        job.next_run = next_run(&job.schedule, now);
+                        targets = job.targets(&connected);
+                    }
+                    if !returned.is_empty() {
+                        job.queued.retain(|x| !returned.contains(x));
+                        for addr in returned {
+                            if !targets.contains(&addr) {
+                                targets.push(addr);
+                            }
+                        }
+                    }
+
+                    if !targets.is_empty() || due {
+                        store.running.insert(job.id);
+                        dispatches.push(Self::prepare(job, targets, &connected));
+                    }
+                }
+
+                if !dispatches.is_empty() {
+                    store.save();
+                }
+            }
+
+            for dispatch in dispatches {
+                // Jobs can take a while, and shouldn't hold up the next check
+                tokio::spawn(self.clone().execute(dispatch, audit.clone()));
+            }
+        }
+    }
+}
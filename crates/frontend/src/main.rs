@@ -1,18 +1,30 @@
 use std::sync::{Arc, Mutex};
 
+use alerts::SharedAlerts;
 use anyhow::{Context, Result};
+use audit::SharedAuditLog;
 use backend::{BackendRegistry, BackendServer};
 use config::{
     APP_VERSION,
     frontend::{FrontendConfig, get_config},
 };
+use history::SharedHistory;
 use http::HttpServer;
+use jobs::SharedJobs;
 use log::info;
 use simple_logger::SimpleLogger;
 
+mod alerts;
+mod api;
+mod audit;
 mod backend;
+mod commands;
+mod history;
 mod http;
+mod jobs;
+mod metrics;
 mod pages;
+mod storage;
 
 pub type SharedConfig = Arc<FrontendConfig>;
 
@@ -27,13 +39,32 @@ async fn main() -> Result<()> {
 
     info!("Starting DietPi-Dashboard frontend v{APP_VERSION}...");
 
-    let backends = Arc::new(Mutex::new(BackendRegistry::new()));
+    let backends = Arc::new(Mutex::new(BackendRegistry::new(&config)));
 
     let backend_server = BackendServer::new(config.clone(), backends.clone()).await?;
 
-    let http_server = HttpServer::new(config, backends.clone()).await?;
+    let history = SharedHistory::new();
+    let alerts = SharedAlerts::new();
+    let audit = SharedAuditLog::new(&config);
+    let jobs = SharedJobs::new(config.clone());
 
-    tokio::join!(http_server.run(), backend_server.run());
+    let http_server = HttpServer::new(
+        config.clone(),
+        backends.clone(),
+        history.clone(),
+        alerts.clone(),
+        audit.clone(),
+        jobs.clone(),
+    )
+    .await?;
+
+    tokio::join!(
+        http_server.run(),
+        backend_server.run(),
+        history.run(backends.clone()),
+        jobs.run(backends.clone(), audit),
+        alerts.run(config, backends)
+    );
 
     Ok(())
 }
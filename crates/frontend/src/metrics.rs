@@ -0,0 +1,288 @@
+use std::{
+    fmt::{Display, Write},
+    net::IpAddr,
+    time::Duration,
+};
+
+use futures_util::future::join_all;
+use hyper::header;
+use log::warn;
+use proto::{
+    backend::{
+        CpuResponse, DiskInfo, DiskResponse, MemResponse, NetworkResponse, ResponseBackendMessage,
+        TempResponse,
+    },
+    frontend::RequestFrontendMessage,
+};
+
+use crate::http::{
+    request::{CurrentBackendData, ServerRequest},
+    response::ServerResponse,
+    tokens::ApiScope,
+};
+
+// One slow backend shouldn't hold up the whole scrape
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(3);
+
+struct BackendMetrics {
+    addr: String,
+    nickname: String,
+    // Connected, and answered at least one request in time
+    up: bool,
+    cpu: Option<CpuResponse>,
+    temp: Option<TempResponse>,
+    mem: Option<MemResponse>,
+    disk: Option<DiskResponse>,
+    net: Option<NetworkResponse>,
+}
+
+impl BackendMetrics {
+    // Disconnected, or didn't answer in time
+    fn down(addr: IpAddr, nickname: String) -> Self {
+        Self {
+            addr: addr.to_string(),
+            nickname,
+            up: false,
+            cpu: None,
+            temp: None,
+            mem: None,
+            disk: None,
+            net: None,
+        }
+    }
+
+    fn labels(&self) -> Vec<(&str, &str)> {
+        vec![("backend", &self.addr), ("nickname", &self.nickname)]
+    }
+}
+
+// Name, help text and how to get the value from a response
+type Family<T> = (&'static str, &'static str, fn(&T) -> u64);
+
+// Writes the Prometheus text exposition format
+struct MetricWriter(String);
+
+impl MetricWriter {
+    fn family(&mut self, name: &str, typ: &str, help: &str) {
+        // Writing to a string can't fail
+        writeln!(self.0, "# HELP {name} {help}").unwrap();
+        writeln!(self.0, "# TYPE {name} {typ}").unwrap();
+    }
+
+    fn sample(&mut self, name: &str, labels: &[(&str, &str)], value: impl Display) {
+        self.0.push_str(name);
+
+        if !labels.is_empty() {
+            self.0.push('{');
+            for (i, (key, val)) in labels.iter().enumerate() {
+                if i != 0 {
+                    self.0.push(',');
+                }
+                let val = val
+                    .replace('\\', r"\\")
+                    .replace('"', r#"\""#)
+                    .replace('\n', r"\n");
+                write!(self.0, r#"{key}="{val}""#).unwrap();
+            }
+            self.0.push('}');
+        }
+
+        writeln!(self.0, " {value}").unwrap();
+    }
+}
+
+async fn collect(backend: CurrentBackendData) -> BackendMetrics {
+    let handle = &backend.handle;
+    let addr = backend.addr;
+
+    // A request that fails is left out, rather than failing the whole scrape
+    macro_rules! get {
+        ($variant:ident) => {
+            async {
+                match handle.send_req(RequestFrontendMessage::$variant).await {
+                    Ok(ResponseBackendMessage::$variant(data)) => Some(data),
+                    _ => {
+                        warn!(
+                            "Failed to get {} metrics from backend {addr}",
+                            stringify!($variant)
+                        );
+                        None
+                    }
+                }
+            }
+        };
+    }
+
+    let responses =
+        async { tokio::join!(get!(Cpu), get!(Temp), get!(Mem), get!(Disk), get!(NetIO)) };
+
+    match tokio::time::timeout(REQUEST_TIMEOUT, responses).await {
+        Ok((cpu, temp, mem, disk, net)) => BackendMetrics {
+            addr: addr.to_string(),
+            nickname: backend.nickname,
+            up: cpu.is_some() || temp.is_some() || mem.is_some() || disk.is_some() || net.is_some(),
+            cpu,
+            temp,
+            mem,
+            disk,
+            net,
+        },
+        Err(_) => {
+            warn!("Backend {addr} took too long to respond to a metrics scrape");
+            BackendMetrics::down(addr, backend.nickname)
+        }
+    }
+}
+
+fn write_metrics(backends: &[BackendMetrics]) -> String {
+    let mut w = MetricWriter(String::new());
+
+    w.family(
+        "dietpi_backend_up",
+        "gauge",
+        "Whether the backend is connected to the frontend and responding.",
+    );
+    for b in backends {
+        w.sample("dietpi_backend_up", &b.labels(), u8::from(b.up));
+    }
+
+    w.family(
+        "dietpi_cpu_usage_percent",
+        "gauge",
+        "Total CPU usage of the backend.",
+    );
+    for b in backends {
+        if let Some(cpu) = &b.cpu {
+            w.sample("dietpi_cpu_usage_percent", &b.labels(), cpu.global_cpu);
+        }
+    }
+
+    w.family(
+        "dietpi_cpu_core_usage_percent",
+        "gauge",
+        "Usage of each CPU core of the backend.",
+    );
+    for b in backends {
+        if let Some(cpu) = &b.cpu {
+            for (i, usage) in cpu.cpus.iter().enumerate() {
+                let core = i.to_string();
+                let mut labels = b.labels();
+                labels.push(("core", &core));
+                w.sample("dietpi_cpu_core_usage_percent", &labels, usage);
+            }
+        }
+    }
+
+    w.family(
+        "dietpi_temperature_celsius",
+        "gauge",
+        "CPU temperature of the backend.",
+    );
+    for b in backends {
+        if let Some(temp) = b.temp.as_ref().and_then(|x| x.temp) {
+            w.sample("dietpi_temperature_celsius", &b.labels(), temp);
+        }
+    }
+
+    let mem_families: [Family<MemResponse>; 4] = [
+        (
+            "dietpi_memory_used_bytes",
+            "RAM in use on the backend.",
+            |x| x.ram.used,
+        ),
+        (
+            "dietpi_memory_total_bytes",
+            "Total RAM of the backend.",
+            |x| x.ram.total,
+        ),
+        (
+            "dietpi_swap_used_bytes",
+            "Swap in use on the backend.",
+            |x| x.swap.used,
+        ),
+        (
+            "dietpi_swap_total_bytes",
+            "Total swap of the backend.",
+            |x| x.swap.total,
+        ),
+    ];
+    for (name, help, value) in mem_families {
+        w.family(name, "gauge", help);
+        for b in backends {
+            if let Some(mem) = &b.mem {
+                w.sample(name, &b.labels(), value(mem));
+            }
+        }
+    }
+
+    let disk_families: [Family<DiskInfo>; 2] = [
+        (
+            "dietpi_disk_used_bytes",
+            "Space in use on each disk.",
+            |x| x.usage.used,
+        ),
+        (
+            "dietpi_disk_total_bytes",
+            "Total space of each disk.",
+            |x| x.usage.total,
+        ),
+    ];
+    for (name, help, value) in disk_families {
+        w.family(name, "gauge", help);
+        for b in backends {
+            let Some(disk) = &b.disk else {
+                continue;
+            };
+            for info in &disk.disks {
+                let mut labels = b.labels();
+                labels.push(("device", &info.name));
+                labels.push(("mountpoint", &info.mnt_point));
+                w.sample(name, &labels, value(info));
+            }
+        }
+    }
+
+    let net_families: [Family<NetworkResponse>; 2] = [
+        (
+            "dietpi_network_received_bytes_total",
+            "Bytes received on all interfaces since boot.",
+            |x| x.total_recv,
+        ),
+        (
+            "dietpi_network_transmitted_bytes_total",
+            "Bytes sent on all interfaces since boot.",
+            |x| x.total_sent,
+        ),
+    ];
+    for (name, help, value) in net_families {
+        w.family(name, "counter", help);
+        for b in backends {
+            if let Some(net) = &b.net {
+                w.sample(name, &b.labels(), value(net));
+            }
+        }
+    }
+
+    w.0
+}
+
+pub async fn export(mut req: ServerRequest) -> Result<ServerResponse, ServerResponse> {
+    // Prometheus authenticates with an API token, same as the rest of the API
+    req.check_token(ApiScope::Read)?;
+
+    let connected = req.extract_all_backends();
+    let offline: Vec<_> = req
+        .extract_backend_registry()
+        .lock()
+        .unwrap()
+        .offline()
+        .map(|(addr, known)| BackendMetrics::down(*addr, known.nickname.clone()))
+        .collect();
+
+    let mut backends = join_all(connected.into_iter().map(collect)).await;
+    backends.extend(offline);
+
+    Ok(ServerResponse::new()
+        .header(header::CONTENT_TYPE, "text/plain; version=0.0.4")
+        .body(write_metrics(&backends)))
+}
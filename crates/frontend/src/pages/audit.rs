@@ -0,0 +1,207 @@
+use std::{
+    collections::BTreeSet,
+    time::{Duration, UNIX_EPOCH},
+};
+
+use hyper::{StatusCode, header};
+use maud::html;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    audit::AuditEntry,
+    http::{request::ServerRequest, response::ServerResponse},
+};
+
+use super::template::template;
+
+const PER_PAGE: usize = 50;
+
+#[derive(Deserialize, Serialize, Clone)]
+#[serde(default)]
+pub struct AuditQuery {
+    action: String,
+    backend: String,
+    search: String,
+    page: usize,
+}
+
+impl Default for AuditQuery {
+    fn default() -> Self {
+        Self {
+            action: String::new(),
+            backend: String::new(),
+            search: String::new(),
+            page: 1,
+        }
+    }
+}
+
+impl AuditQuery {
+    fn matches(&self, entry: &AuditEntry) -> bool {
+        let search = self.search.to_lowercase();
+
+        (self.action.is_empty() || entry.action == self.action)
+            && (self.backend.is_empty() || entry.backend.to_string() == self.backend)
+            && (search.is_empty()
+                || entry.args.to_lowercase().contains(&search)
+                || entry.result.to_lowercase().contains(&search)
+                || entry
+                    .session
+                    .as_deref()
+                    .is_some_and(|x| x.contains(&search)))
+    }
+
+    fn link(&self, path: &str, page: usize) -> String {
+        let mut next = self.clone();
+        next.page = page;
+
+        let query = serde_urlencoded::to_string(next).unwrap();
+
+        format!("{path}?{query}")
+    }
+}
+
+async fn read_log(req: &ServerRequest) -> Result<Vec<AuditEntry>, ServerResponse> {
+    req.extract_audit().read_recent().await.map_err(|err| {
+        ServerResponse::new()
+            .status(StatusCode::INTERNAL_SERVER_ERROR)
+            .body(format!("{err:#}"))
+    })
+}
+
+pub fn format_time(time: u64) -> String {
+    let time = UNIX_EPOCH + Duration::from_secs(time);
+    humantime::format_rfc3339_seconds(time).to_string()
+}
+
+pub async fn page(req: ServerRequest) -> Result<ServerResponse, ServerResponse> {
+    req.check_login()?;
+
+    let mut query: AuditQuery = req.extract_query()?;
+
+    let entries = read_log(&req).await?;
+
+    let actions: BTreeSet<_> = entries.iter().map(|x| x.action.clone()).collect();
+    let backends: BTreeSet<_> = entries
+        .iter()
+        .map(|x| (x.backend, x.nickname.clone()))
+        .collect();
+
+    // Newest entries first
+    let entries: Vec<_> = entries
+        .into_iter()
+        .rev()
+        .filter(|x| query.matches(x))
+        .collect();
+
+    let total_items = entries.len();
+    let total_pages = total_items.div_ceil(PER_PAGE).max(1);
+    query.page = query.page.clamp(1, total_pages);
+
+    let page_items = entries
+        .iter()
+        .skip((query.page - 1) * PER_PAGE)
+        .take(PER_PAGE);
+
+    let prev_link = query.link("/audit", query.page.saturating_sub(1).max(1));
+    let next_link = query.link("/audit", (query.page + 1).min(total_pages));
+    let export_link = query.link("/audit/export", 1);
+
+    let content = html! {
+        section #audit-log {
+            h2 data-i18n="audit_log_title" { "Audit Log" }
+
+            form .audit-filters method="GET" action="/audit" {
+                label {
+                    span data-i18n="action" { "Action" }
+                    select name="action" {
+                        option value="" data-i18n="all" { "All" }
+                        @for action in &actions {
+                            option value=(action) selected[*action == query.action] { (action) }
+                        }
+                    }
+                }
+                label {
+                    span data-i18n="backend" { "Backend" }
+                    select name="backend" {
+                        option value="" data-i18n="all" { "All" }
+                        @for (addr, nickname) in &backends {
+                            @let addr = addr.to_string();
+                            option value=(addr) selected[addr == query.backend] {
+                                (nickname) " (" (addr) ")"
+                            }
+                        }
+                    }
+                }
+                label {
+                    span data-i18n="search" { "Search" }
+                    input type="search" name="search" value=(query.search);
+                }
+                button .primary-btn data-i18n="filter" { "Filter" }
+                a .pager-btn href=(export_link) data-i18n="export_jsonl" { "Export JSON Lines" }
+            }
+
+            .audit-table-wrap {
+                table .audit-table {
+                    tr {
+                        th data-i18n="time" { "Time" }
+                        th data-i18n="session" { "Session" }
+                        th data-i18n="source_address" { "Source Address" }
+                        th data-i18n="backend" { "Backend" }
+                        th data-i18n="action" { "Action" }
+                        th data-i18n="arguments" { "Arguments" }
+                        th data-i18n="result" { "Result" }
+                    }
+                    @if total_items == 0 {
+                        tr {
+                            td colspan="7" data-i18n="no_audit_entries" { "No audit entries" }
+                        }
+                    }
+                    @for entry in page_items {
+                        tr {
+                            td { (format_time(entry.time)) }
+                            td { code { (entry.session.as_deref().unwrap_or("--")) } }
+                            td {
+                                @if let Some(source) = entry.source {
+                                    (source)
+                                } @else {
+                                    "--"
+                                }
+                            }
+                            td { (entry.nickname) " (" (entry.backend) ")" }
+                            td { code { (entry.action) } }
+                            td { span .audit-args title=(entry.args) { (entry.args) } }
+                            td { (entry.result) }
+                        }
+                    }
+                }
+            }
+
+            .process-pagination {
+                a .pager-btn class=(if query.page == 1 { "disabled" } else { "" }) href=(prev_link) data-i18n="prev" { "Prev" }
+                p .pager-info data-i18n-template="page_of" data-page=(query.page) data-total-pages=(total_pages) { "Page " (query.page) " / " (total_pages) }
+                a .pager-btn class=(if query.page == total_pages { "disabled" } else { "" }) href=(next_link) data-i18n="next" { "Next" }
+            }
+        }
+    };
+
+    template(&req, content, "")
+}
+
+pub async fn export(req: ServerRequest) -> Result<ServerResponse, ServerResponse> {
+    req.check_login()?;
+
+    let query: AuditQuery = req.extract_query()?;
+
+    let stream = req
+        .extract_audit()
+        .export(move |entry| query.matches(entry));
+
+    Ok(ServerResponse::new()
+        .header(header::CONTENT_TYPE, "application/x-ndjson")
+        .header(
+            header::CONTENT_DISPOSITION,
+            r#"attachment; filename="audit.jsonl""#,
+        )
+        .stream_body(stream))
+}
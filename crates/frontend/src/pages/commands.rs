@@ -0,0 +1,332 @@
+use std::{collections::BTreeSet, time::Duration};
+
+use hyper::StatusCode;
+use maud::{Markup, html};
+use serde::Deserialize;
+
+use crate::{
+    audit,
+    backend::{group_by_tag, matches_tag},
+    commands::{self, CommandHistory, CommandOutcome, CommandRun},
+    http::{query_array::QueryArray, request::ServerRequest, response::ServerResponse},
+};
+
+use super::{audit::format_time, template::template};
+
+pub const DEFAULT_TIMEOUT: u64 = 30;
+pub const MAX_TIMEOUT: u64 = 600;
+
+#[derive(Deserialize, Default)]
+#[serde(default)]
+pub struct CommandsQuery {
+    run: String,
+}
+
+#[derive(Deserialize)]
+struct RunForm {
+    command: String,
+    timeout: u64,
+    #[serde(default)]
+    tag: String,
+    #[serde(default)]
+    backends: QueryArray,
+}
+
+fn run_link(run: &CommandRun) -> String {
+    format!("/commands?run={:08x}", run.id)
+}
+
+// Checkboxes that fill the `backends` array of the surrounding nm-data
+pub fn backend_checkboxes(req: &ServerRequest) -> Markup {
+    let mut backends = req.extract_all_backends();
+    backends.sort_by(|a, b| a.nickname.cmp(&b.nickname).then(a.addr.cmp(&b.addr)));
+
+    let group_tag = &req.config().backend_group_tag;
+    let groups = group_by_tag(backends, group_tag, |x| &x.tags);
+
+    html! {
+        fieldset .command-targets {
+            legend data-i18n="backends" { "Backends" }
+            @for (value, backends) in &groups {
+                @if let Some(value) = value {
+                    p .command-group { (group_tag) ": " (value) }
+                }
+                @for backend in backends {
+                    label {
+                        input type="checkbox" nm-bind={
+                            "onchange: () => backends = this.checked
+                                ? [...backends, '" (backend.addr) "']
+                                : backends.filter((x) => x !== '" (backend.addr) "')"
+                        };
+                        (backend.nickname) " (" (backend.addr) ")"
+                    }
+                }
+            }
+        }
+    }
+}
+
+// Select that fills the `tag` var of the surrounding nm-data
+pub fn tag_select(req: &ServerRequest) -> Markup {
+    let all_tags: BTreeSet<_> = req
+        .extract_all_backends()
+        .iter()
+        .flat_map(|x| x.tags.iter().map(|(k, v)| format!("{k}={v}")))
+        .collect();
+
+    html! {
+        label {
+            span data-i18n="tag" { "Tag" }
+            select nm-bind="onchange: () => tag = this.value" {
+                option value="" data-i18n="none" { "None" }
+                @for tag in &all_tags {
+                    option value=(tag) { (tag) }
+                }
+            }
+        }
+    }
+}
+
+fn runner(req: &ServerRequest) -> Markup {
+    html! {
+        section #command-runner nm-data={"command: '', timeout: " (DEFAULT_TIMEOUT) ", tag: '', backends: []"} {
+            h2 data-i18n="command_runner" { "Command Runner" }
+
+            (backend_checkboxes(req))
+
+            .audit-filters {
+                (tag_select(req))
+                label .command-input {
+                    span data-i18n="command" { "Command" }
+                    input type="text" nm-bind="oninput: () => command = this.value";
+                }
+                label {
+                    span data-i18n="timeout_seconds" { "Timeout (seconds)" }
+                    input type="number" min="1" max=(MAX_TIMEOUT) value=(DEFAULT_TIMEOUT) nm-bind="oninput: () => timeout = this.value";
+                }
+                button .primary-btn nm-bind="
+                    onclick: () => $post('/commands/run'),
+                    disabled: () => _nmFetching || !command || (!tag && backends.length === 0)
+                " {
+                    span data-i18n="run" nm-bind="hidden: () => _nmFetching" { "Run" }
+                    span data-i18n="running_command" nm-bind="hidden: () => !_nmFetching" { "Running..." }
+                }
+            }
+        }
+    }
+}
+
+pub fn outcome_status(outcome: &CommandOutcome) -> (&'static str, &'static str, &'static str) {
+    match outcome {
+        CommandOutcome::Finished {
+            exit_code: Some(0), ..
+        } => ("active", "succeeded", "Succeeded"),
+        CommandOutcome::Finished { .. } => ("failed", "failed", "Failed"),
+        CommandOutcome::TimedOut => ("unknown", "timed_out", "Timed Out"),
+        CommandOutcome::Failed(_) => ("failed", "failed", "Failed"),
+        CommandOutcome::Offline { queued: true } => ("unknown", "queued", "Queued"),
+        CommandOutcome::Offline { queued: false } => ("unknown", "offline", "Offline"),
+        CommandOutcome::Blocked(_) => ("failed", "blocked", "Blocked by Policy"),
+    }
+}
+
+pub fn results(run: Option<&CommandRun>) -> Markup {
+    let Some(run) = run else {
+        return html! { section #command-results {} };
+    };
+
+    // Hosts with identical output share a letter, so differences stand out
+    let mut variants = Vec::new();
+    let mut labels = Vec::with_capacity(run.results.len());
+    for result in &run.results {
+        let CommandOutcome::Finished {
+            exit_code,
+            stdout,
+            stderr,
+        } = &result.outcome
+        else {
+            labels.push(None);
+            continue;
+        };
+        let key = (exit_code, stdout, stderr);
+
+        let idx = variants.iter().position(|x| *x == key).unwrap_or_else(|| {
+            variants.push(key);
+            variants.len() - 1
+        });
+        labels.push(Some(char::from(b'A' + (idx % 26) as u8)));
+    }
+
+    // Not worth showing if every host gave the same output
+    if variants.len() < 2 {
+        labels.fill(None);
+    }
+
+    html! {
+        section #command-results nm-bind="oninit: () => this.scrollIntoView()" {
+            h2 data-i18n="command_results" { "Results" }
+            p .command-summary {
+                code { (run.command) }
+                " · " (format_time(run.time)) " · "
+                span data-i18n-template="command_run_summary" data-succeeded=(run.succeeded()) data-total=(run.results.len()) {
+                    (run.succeeded()) " of " (run.results.len()) " succeeded"
+                }
+            }
+
+            .command-results-grid {
+                @for (result, label) in run.results.iter().zip(labels) {
+                    @let (status, status_key, status_name) = outcome_status(&result.outcome);
+                    article .command-result {
+                        .fleet-card-header {
+                            div {
+                                h3 .fleet-name { (result.nickname) }
+                                p .fleet-addr { (result.addr) }
+                            }
+                            span .status-badge data-status=(status) data-i18n=(status_key) { (status_name) }
+                        }
+                        p .command-meta {
+                            @if let CommandOutcome::Finished { exit_code, .. } = &result.outcome {
+                                @let exit_code = exit_code.map(|x| x.to_string()).unwrap_or_else(|| "--".into());
+                                span data-i18n-template="exit_code_value" data-value=(exit_code) {
+                                    "Exit code: " (exit_code)
+                                }
+                                " · "
+                            }
+                            (format!("{:.2}s", result.duration_ms as f32 / 1000.))
+                            @if let Some(label) = label {
+                                " · "
+                                span .command-variant data-i18n-template="output_variant" data-variant=(label) {
+                                    "Output " (label)
+                                }
+                            }
+                        }
+                        @match &result.outcome {
+                            CommandOutcome::Finished { stdout, stderr, .. } => {
+                                @if !stdout.is_empty() {
+                                    pre { (stdout) }
+                                }
+                                @if !stderr.is_empty() {
+                                    pre .command-stderr { (stderr) }
+                                }
+                            }
+                            CommandOutcome::TimedOut => {
+                                p .command-error data-i18n-template="command_timed_out" data-seconds=(run.timeout) {
+                                    "No response within " (run.timeout) " seconds"
+                                }
+                            }
+                            CommandOutcome::Failed(err) => {
+                                p .command-error { (err) }
+                            }
+                            CommandOutcome::Blocked(err) => {
+                                p .command-error data-i18n="command_blocked" { "The backend's command_policy doesn't allow this command" }
+                                p .command-error { (err) }
+                            }
+                            CommandOutcome::Offline { queued } => {
+                                @if *queued {
+                                    p .command-error data-i18n="command_queued" { "Backend was offline, the command will run when it reconnects" }
+                                } @else {
+                                    p .command-error data-i18n="command_skipped" { "Backend was offline, the command was skipped" }
+                                }
+                            }
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+fn history(history: &CommandHistory) -> Markup {
+    html! {
+        section #command-history {
+            h2 data-i18n="command_history" { "History" }
+            .audit-table-wrap {
+                table .audit-table {
+                    tr {
+                        th data-i18n="time" { "Time" }
+                        th data-i18n="command" { "Command" }
+                        th data-i18n="backends" { "Backends" }
+                        th data-i18n="succeeded" { "Succeeded" }
+                        th {}
+                    }
+                    @let runs: Vec<_> = history.runs().collect();
+                    @if runs.is_empty() {
+                        tr {
+                            td colspan="5" data-i18n="no_command_runs" { "No commands have been run" }
+                        }
+                    }
+                    @for run in runs {
+                        tr {
+                            td { (format_time(run.time)) }
+                            td { span .audit-args title=(run.command) { (run.command) } }
+                            td { (run.results.len()) }
+                            td { (run.succeeded()) }
+                            td { a .pager-btn href=(run_link(run)) data-i18n="view" { "View" } }
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+pub async fn page(req: ServerRequest) -> Result<ServerResponse, ServerResponse> {
+    req.check_login()?;
+
+    let query: CommandsQuery = req.extract_query()?;
+    let selected = u32::from_str_radix(&query.run, 16).ok();
+
+    let store = req.extract_commands();
+    let store = store.get();
+
+    let content = html! {
+        (runner(&req))
+        (results(selected.and_then(|id| store.get(id))))
+        (history(&store))
+    };
+
+    template(&req, content, "")
+}
+
+pub async fn run(mut req: ServerRequest) -> Result<ServerResponse, ServerResponse> {
+    req.check_login()?;
+
+    let form: RunForm = req.extract_form().await?;
+
+    let command = form.command.trim();
+    if command.is_empty() {
+        return Err(req.error(StatusCode::BAD_REQUEST, "command is empty"));
+    }
+
+    let selected: Vec<_> = form.backends.iter().collect();
+    let targets: Vec<_> = req
+        .extract_all_backends()
+        .into_iter()
+        .filter(|x| {
+            selected.contains(&x.addr) || (!form.tag.is_empty() && matches_tag(&x.tags, &form.tag))
+        })
+        .collect();
+
+    if targets.is_empty() {
+        return Err(req.error(StatusCode::BAD_REQUEST, "no connected backends selected"));
+    }
+
+    let timeout = Duration::from_secs(form.timeout.clamp(1, MAX_TIMEOUT));
+    let run = commands::run(&targets, command, timeout).await;
+
+    let args = audit::describe_command(&commands::command_action(command));
+    for (backend, result) in targets.iter().zip(&run.results) {
+        req.record_audit(backend, "command", args.clone(), result.outcome.summary());
+    }
+
+    let store = req.extract_commands();
+    let mut store = store.get();
+    store.push(run.clone());
+
+    let content = html! {
+        (results(Some(&run)))
+        (history(&store))
+    };
+
+    template(&req, content, "")
+}
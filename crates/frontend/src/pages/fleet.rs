@@ -0,0 +1,454 @@
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    net::IpAddr,
+    time::Duration,
+};
+
+use anyhow::{Result, anyhow};
+use futures_util::future::join_all;
+use hyper::{StatusCode, header};
+use maud::{Markup, html};
+use pretty_bytes_typed::pretty_bytes_binary;
+use proto::{
+    backend::{
+        CpuResponse, DiskResponse, HostResponse, MemResponse, ResponseBackendMessage, TempResponse,
+    },
+    frontend::RequestFrontendMessage,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    backend::{BackendHandle, group_by_tag, matches_tag},
+    http::{
+        request::ServerRequest,
+        response::{RedirectType, ServerResponse},
+    },
+};
+
+use super::{audit::format_time, template::template};
+
+// One slow backend shouldn't hold up the whole page
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(3);
+
+#[derive(Deserialize, Serialize, PartialEq, Eq, Clone, Copy, Default)]
+#[serde(rename_all = "lowercase")]
+enum FleetSort {
+    #[default]
+    Name,
+    Cpu,
+    Ram,
+    Temp,
+    Disk,
+    Uptime,
+}
+
+#[derive(Deserialize, Serialize, PartialEq, Eq, Clone, Copy, Default)]
+#[serde(rename_all = "lowercase")]
+enum FleetStatus {
+    #[default]
+    All,
+    Online,
+    Offline,
+    Update,
+}
+
+#[derive(Deserialize, Serialize, Clone, Default)]
+#[serde(default)]
+pub struct FleetQuery {
+    sort: FleetSort,
+    reverse: bool,
+    status: FleetStatus,
+    tag: String,
+    search: String,
+}
+
+struct Stats {
+    cpu: f32,
+    ram: f32,
+    ram_used: u64,
+    ram_total: u64,
+    temp: Option<f32>,
+    // Name and usage of the fullest disk
+    disk: Option<(String, f32)>,
+    uptime: u64,
+}
+
+struct BackendCard {
+    addr: IpAddr,
+    nickname: String,
+    online: bool,
+    update: Option<String>,
+    tags: BTreeMap<String, String>,
+    // Only set for offline backends
+    last_seen: Option<u64>,
+    // Missing if the backend is offline or didn't respond in time
+    stats: Option<Stats>,
+}
+
+impl FleetQuery {
+    fn matches(&self, card: &BackendCard) -> bool {
+        let search = self.search.to_lowercase();
+
+        let status = match self.status {
+            FleetStatus::All => true,
+            FleetStatus::Online => card.online,
+            FleetStatus::Offline => !card.online,
+            FleetStatus::Update => card.update.is_some(),
+        };
+
+        status
+            && (self.tag.is_empty() || matches_tag(&card.tags, &self.tag))
+            && (search.is_empty()
+                || card.nickname.to_lowercase().contains(&search)
+                || card.addr.to_string().contains(&search))
+    }
+
+    fn sort(&self, cards: &mut [BackendCard]) {
+        // Backends without data always go last
+        let key = |card: &BackendCard| -> Option<f32> {
+            let stats = card.stats.as_ref()?;
+            match self.sort {
+                FleetSort::Name => None,
+                FleetSort::Cpu => Some(stats.cpu),
+                FleetSort::Ram => Some(stats.ram),
+                FleetSort::Temp => stats.temp,
+                FleetSort::Disk => stats.disk.as_ref().map(|x| x.1),
+                FleetSort::Uptime => Some(stats.uptime as f32),
+            }
+        };
+
+        cards.sort_by(|a, b| a.nickname.cmp(&b.nickname).then(a.addr.cmp(&b.addr)));
+        if self.sort != FleetSort::Name {
+            // Highest first, since that's usually what needs attention
+            cards.sort_by(|a, b| match (key(a), key(b)) {
+                (Some(a), Some(b)) => b.total_cmp(&a),
+                (a, b) => b.is_some().cmp(&a.is_some()),
+            });
+        }
+        if self.reverse {
+            cards.reverse();
+        }
+    }
+}
+
+fn percent(used: u64, total: u64) -> f32 {
+    if total == 0 {
+        0.
+    } else {
+        used as f32 / total as f32 * 100.
+    }
+}
+
+async fn fetch_stats(handle: &BackendHandle) -> Result<Stats> {
+    let responses = async {
+        tokio::try_join!(
+            handle.send_req(RequestFrontendMessage::Cpu),
+            handle.send_req(RequestFrontendMessage::Mem),
+            handle.send_req(RequestFrontendMessage::Temp),
+            handle.send_req(RequestFrontendMessage::Disk),
+            handle.send_req(RequestFrontendMessage::Host),
+        )
+    };
+
+    let responses = tokio::time::timeout(REQUEST_TIMEOUT, responses)
+        .await
+        .map_err(|_| anyhow!("backend took too long to respond"))??;
+
+    let (
+        ResponseBackendMessage::Cpu(CpuResponse { global_cpu, .. }),
+        ResponseBackendMessage::Mem(MemResponse { ram, .. }),
+        ResponseBackendMessage::Temp(TempResponse { temp, .. }),
+        ResponseBackendMessage::Disk(DiskResponse { disks }),
+        ResponseBackendMessage::Host(HostResponse { uptime, .. }),
+    ) = responses
+    else {
+        return Err(anyhow!("backend sent mismatched responses"));
+    };
+
+    let disk = disks
+        .into_iter()
+        .map(|x| (x.name, percent(x.usage.used, x.usage.total)))
+        .max_by(|a, b| a.1.total_cmp(&b.1));
+
+    Ok(Stats {
+        cpu: global_cpu,
+        ram: percent(ram.used, ram.total),
+        ram_used: ram.used,
+        ram_total: ram.total,
+        temp,
+        disk,
+        uptime,
+    })
+}
+
+fn card(card: &BackendCard) -> Markup {
+    let (status, status_key, status_name) = match (card.online, &card.stats) {
+        (true, Some(_)) => ("active", "online", "Online"),
+        (true, None) => ("unknown", "unreachable", "Unreachable"),
+        (false, _) => ("failed", "offline", "Offline"),
+    };
+
+    let content = html! {
+        .fleet-card-header {
+            div {
+                h3 .fleet-name { (card.nickname) }
+                p .fleet-addr { (card.addr) }
+            }
+            span .status-badge data-status=(status) data-i18n=(status_key) { (status_name) }
+        }
+        @if let Some(stats) = &card.stats {
+            dl .fleet-stats {
+                div {
+                    dt data-i18n="cpu_load" { "CPU Load" }
+                    dd { (format!("{:.1}%", stats.cpu)) }
+                }
+                div {
+                    dt data-i18n="memory_label" { "Memory" }
+                    dd title=(format!("{} / {}", pretty_bytes_binary(stats.ram_used, Some(1)), pretty_bytes_binary(stats.ram_total, Some(1)))) {
+                        (format!("{:.1}%", stats.ram))
+                    }
+                }
+                div {
+                    dt data-i18n="temperature" { "Temperature" }
+                    dd {
+                        @if let Some(temp) = stats.temp {
+                            (format!("{temp:.1}ºC"))
+                        } @else {
+                            "--"
+                        }
+                    }
+                }
+                div {
+                    dt data-i18n="peak_disk" { "Peak Disk" }
+                    dd {
+                        @if let Some((name, usage)) = &stats.disk {
+                            span title=(name) { (format!("{usage:.1}%")) }
+                        } @else {
+                            "--"
+                        }
+                    }
+                }
+                div {
+                    dt data-i18n="uptime" { "Uptime" }
+                    dd { (humantime::format_duration(Duration::from_secs(stats.uptime))) }
+                }
+            }
+        }
+        @if !card.tags.is_empty() {
+            ul .fleet-tags {
+                @for (key, value) in &card.tags {
+                    li { (key) "=" (value) }
+                }
+            }
+        }
+        @if let Some(last_seen) = card.last_seen {
+            @let last_seen = format_time(last_seen);
+            p .fleet-addr data-i18n-template="last_seen" data-time=(last_seen) {
+                "Last seen " (last_seen)
+            }
+        }
+        @if let Some(update) = &card.update {
+            p .fleet-update data-i18n-template="dietpi_update_available" data-version=(update) {
+                "DietPi Update Available: " (update)
+            }
+        }
+        @if !card.online {
+            form action="/fleet/forget" method="POST" {
+                input type="hidden" name="backend" value=(card.addr);
+                button .pager-btn data-i18n="forget" { "Forget" }
+            }
+        }
+    };
+
+    // Offline backends can't be switched to, so they aren't links
+    html! {
+        @if card.online {
+            a .fleet-card href=(format!("/fleet/select?backend={}", card.addr)) { (content) }
+        } @else {
+            article .fleet-card.offline { (content) }
+        }
+    }
+}
+
+fn card_grid(cards: &[BackendCard]) -> Markup {
+    html! {
+        .fleet-grid {
+            @for backend in cards {
+                (card(backend))
+            }
+        }
+    }
+}
+
+pub async fn page(req: ServerRequest) -> Result<ServerResponse, ServerResponse> {
+    req.check_login()?;
+
+    let query: FleetQuery = req.extract_query()?;
+
+    let backends = req.extract_all_backends();
+
+    let stats = join_all(backends.iter().map(|x| fetch_stats(&x.handle))).await;
+
+    let mut cards: Vec<_> = backends
+        .into_iter()
+        .zip(stats)
+        .map(|(backend, stats)| BackendCard {
+            addr: backend.addr,
+            nickname: backend.nickname,
+            online: true,
+            update: backend.update,
+            tags: backend.tags,
+            last_seen: None,
+            stats: stats.ok(),
+        })
+        .collect();
+
+    // Backends that were connected earlier but have since gone away
+    for (addr, known) in req.extract_backend_registry().lock().unwrap().offline() {
+        if !cards.iter().any(|x| x.addr == *addr) {
+            cards.push(BackendCard {
+                addr: *addr,
+                nickname: known.nickname.clone(),
+                online: false,
+                update: None,
+                tags: known.tags.clone(),
+                last_seen: Some(known.last_seen),
+                stats: None,
+            });
+        }
+    }
+
+    let total = cards.len();
+    let online = cards.iter().filter(|x| x.online).count();
+
+    let all_tags: BTreeSet<_> = cards
+        .iter()
+        .flat_map(|x| x.tags.iter().map(|(k, v)| format!("{k}={v}")))
+        .collect();
+
+    cards.retain(|x| query.matches(x));
+    query.sort(&mut cards);
+
+    let groups = group_by_tag(cards, &req.config().backend_group_tag, |x| &x.tags);
+
+    let content = html! {
+        section
+            #fleet-swap
+            nm-bind="oninit: () => $debounce(() => $get(window.location.pathname + window.location.search), 5000)"
+        {
+            h2 data-i18n="fleet_overview" { "Fleet Overview" }
+
+            form .audit-filters method="GET" action="/fleet" {
+                label {
+                    span data-i18n="search" { "Search" }
+                    input type="search" name="search" value=(query.search);
+                }
+                label {
+                    span data-i18n="status" { "Status" }
+                    select name="status" {
+                        option value="all" selected[query.status == FleetStatus::All] data-i18n="all" { "All" }
+                        option value="online" selected[query.status == FleetStatus::Online] data-i18n="online" { "Online" }
+                        option value="offline" selected[query.status == FleetStatus::Offline] data-i18n="offline" { "Offline" }
+                        option value="update" selected[query.status == FleetStatus::Update] data-i18n="update_available" { "Update Available" }
+                    }
+                }
+                label {
+                    span data-i18n="tag" { "Tag" }
+                    select name="tag" {
+                        option value="" data-i18n="all" { "All" }
+                        @for tag in &all_tags {
+                            option value=(tag) selected[*tag == query.tag] { (tag) }
+                        }
+                    }
+                }
+                label {
+                    span data-i18n="sort_by" { "Sort By" }
+                    select name="sort" {
+                        option value="name" selected[query.sort == FleetSort::Name] data-i18n="name" { "Name" }
+                        option value="cpu" selected[query.sort == FleetSort::Cpu] data-i18n="cpu_load" { "CPU Load" }
+                        option value="ram" selected[query.sort == FleetSort::Ram] data-i18n="memory_label" { "Memory" }
+                        option value="temp" selected[query.sort == FleetSort::Temp] data-i18n="temperature" { "Temperature" }
+                        option value="disk" selected[query.sort == FleetSort::Disk] data-i18n="peak_disk" { "Peak Disk" }
+                        option value="uptime" selected[query.sort == FleetSort::Uptime] data-i18n="uptime" { "Uptime" }
+                    }
+                }
+                label .fleet-reverse {
+                    input type="checkbox" name="reverse" value="true" checked[query.reverse];
+                    span data-i18n="reverse_order" { "Reverse" }
+                }
+                button .primary-btn data-i18n="filter" { "Filter" }
+            }
+
+            p .fleet-summary data-i18n-template="fleet_summary" data-online=(online) data-total=(total) {
+                (online) " of " (total) " backends online"
+            }
+
+            @if groups.is_empty() {
+                p data-i18n="no_backends" { "No backends" }
+            } @else if let [(None, cards)] = groups.as_slice() {
+                (card_grid(cards))
+            } @else {
+                @for (value, cards) in &groups {
+                    h3 .fleet-group {
+                        (req.config().backend_group_tag) ": " (value.as_deref().unwrap_or("--"))
+                    }
+                    (card_grid(cards))
+                }
+            }
+        }
+    };
+
+    template(&req, content, "")
+}
+
+#[derive(Deserialize)]
+pub struct SelectQuery {
+    backend: IpAddr,
+}
+
+pub async fn select(req: ServerRequest) -> Result<ServerResponse, ServerResponse> {
+    req.check_login()?;
+
+    let query: SelectQuery = req.extract_query()?;
+
+    if !req
+        .extract_all_backends()
+        .iter()
+        .any(|x| x.addr == query.backend)
+    {
+        return Err(ServerResponse::new()
+            .status(StatusCode::NOT_FOUND)
+            .body(format!("backend {} is not connected", query.backend)));
+    }
+
+    // Same cookie as the backend dropdown in the header sets
+    let cookie = format!("backend={}; Max-Age=999999999; Path=/", query.backend);
+
+    Ok(ServerResponse::new()
+        .header(header::SET_COOKIE, cookie)
+        .redirect(RedirectType::SeeOther, "/system"))
+}
+
+#[derive(Deserialize)]
+pub struct ForgetForm {
+    backend: IpAddr,
+}
+
+pub async fn forget(mut req: ServerRequest) -> Result<ServerResponse, ServerResponse> {
+    req.check_login()?;
+
+    let form: ForgetForm = req.extract_form().await?;
+
+    if !req
+        .extract_backend_registry()
+        .lock()
+        .unwrap()
+        .forget(form.backend)
+    {
+        return Err(ServerResponse::new()
+            .status(StatusCode::NOT_FOUND)
+            .body(format!("backend {} is not offline", form.backend)));
+    }
+
+    Ok(ServerResponse::new().redirect(RedirectType::SeeOther, "/fleet"))
+}
@@ -0,0 +1,318 @@
+use hyper::StatusCode;
+use maud::{Markup, html};
+use serde::Deserialize;
+
+use crate::{
+    http::{
+        query_array::QueryArray,
+        request::ServerRequest,
+        response::{RedirectType, ServerResponse},
+    },
+    jobs::{Job, JobStore, NewJob, OfflinePolicy, parse_schedule},
+};
+
+use super::{
+    audit::format_time,
+    commands::{self, DEFAULT_TIMEOUT, MAX_TIMEOUT},
+    template::template,
+};
+
+#[derive(Deserialize, Default)]
+#[serde(default)]
+pub struct JobsQuery {
+    job: String,
+    run: String,
+}
+
+#[derive(Deserialize)]
+struct CreateForm {
+    name: String,
+    schedule: String,
+    command: String,
+    timeout: u64,
+    offline: OfflinePolicy,
+    #[serde(default)]
+    tag: String,
+    #[serde(default)]
+    backends: QueryArray,
+}
+
+#[derive(Deserialize)]
+struct JobForm {
+    id: String,
+}
+
+fn job_link(job: &Job) -> String {
+    format!("/jobs?job={:08x}", job.id)
+}
+
+fn targets(job: &Job) -> String {
+    let mut targets: Vec<_> = job.backends.iter().map(|x| x.to_string()).collect();
+    if !job.tag.is_empty() {
+        targets.push(job.tag.clone());
+    }
+    targets.join(", ")
+}
+
+fn job_list(store: &JobStore) -> Markup {
+    html! {
+        section #job-list {
+            h2 data-i18n="scheduled_jobs" { "Scheduled Jobs" }
+            .audit-table-wrap {
+                table .audit-table {
+                    tr {
+                        th data-i18n="name" { "Name" }
+                        th data-i18n="schedule" { "Schedule" }
+                        th data-i18n="backends" { "Backends" }
+                        th data-i18n="command" { "Command" }
+                        th data-i18n="next_run" { "Next Run" }
+                        th data-i18n="last_run" { "Last Run" }
+                        th data-i18n="status" { "Status" }
+                        th {}
+                    }
+                    @if store.jobs().is_empty() {
+                        tr {
+                            td colspan="8" data-i18n="no_jobs" { "No jobs have been scheduled" }
+                        }
+                    }
+                    @for job in store.jobs() {
+                        @let id = format!("{:08x}", job.id);
+                        tr {
+                            td { (job.name) }
+                            td { code { (job.schedule) } }
+                            td { span .audit-args title=(targets(job)) { (targets(job)) } }
+                            td { span .audit-args title=(job.command) { (job.command) } }
+                            td {
+                                @match job.next_run.filter(|_| job.enabled) {
+                                    Some(time) => (format_time(time)),
+                                    None => "--",
+                                }
+                            }
+                            td {
+                                @if let Some(run) = job.runs.back() {
+                                    span data-i18n-template="command_run_summary" data-succeeded=(run.succeeded()) data-total=(run.results.len()) {
+                                        (run.succeeded()) " of " (run.results.len()) " succeeded"
+                                    }
+                                } @else {
+                                    "--"
+                                }
+                            }
+                            td {
+                                @if store.is_running(job.id) {
+                                    span .status-badge data-status="other" data-i18n="running" { "Running" }
+                                } @else if job.enabled {
+                                    span .status-badge data-status="active" data-i18n="enabled" { "Enabled" }
+                                } @else {
+                                    span .status-badge data-status="inactive" data-i18n="disabled" { "Disabled" }
+                                }
+                            }
+                            td .job-actions {
+                                a .pager-btn href=(job_link(job)) data-i18n="view" { "View" }
+                                form action="/jobs/run" method="POST" {
+                                    input type="hidden" name="id" value=(id);
+                                    button .pager-btn data-i18n="run_now" { "Run Now" }
+                                }
+                                form action="/jobs/toggle" method="POST" {
+                                    input type="hidden" name="id" value=(id);
+                                    @if job.enabled {
+                                        button .pager-btn data-i18n="disable" { "Disable" }
+                                    } @else {
+                                        button .pager-btn data-i18n="enable" { "Enable" }
+                                    }
+                                }
+                                form action="/jobs/delete" method="POST" {
+                                    input type="hidden" name="id" value=(id);
+                                    button .pager-btn data-i18n="delete" { "Delete" }
+                                }
+                            }
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+fn create_form(req: &ServerRequest) -> Markup {
+    html! {
+        section #job-create nm-data={
+            "name: '', schedule: '', command: '', timeout: " (DEFAULT_TIMEOUT) ", offline: 'skip', tag: '', backends: []"
+        } {
+            h2 data-i18n="new_job" { "New Job" }
+
+            (commands::backend_checkboxes(req))
+
+            .audit-filters {
+                label {
+                    span data-i18n="name" { "Name" }
+                    input type="text" nm-bind="oninput: () => name = this.value";
+                }
+                label {
+                    span data-i18n="schedule" { "Schedule" }
+                    input type="text" placeholder="0 3 * * *" nm-bind="oninput: () => schedule = this.value";
+                }
+                (commands::tag_select(req))
+                label {
+                    span data-i18n="when_offline" { "When Offline" }
+                    select nm-bind="onchange: () => offline = this.value" {
+                        option value="skip" data-i18n="skip_run" { "Skip" }
+                        option value="queue" data-i18n="queue_run" { "Run on reconnect" }
+                    }
+                }
+            }
+            .audit-filters {
+                label .command-input {
+                    span data-i18n="command" { "Command" }
+                    input type="text" nm-bind="oninput: () => command = this.value";
+                }
+                label {
+                    span data-i18n="timeout_seconds" { "Timeout (seconds)" }
+                    input type="number" min="1" max=(MAX_TIMEOUT) value=(DEFAULT_TIMEOUT) nm-bind="oninput: () => timeout = this.value";
+                }
+                button .primary-btn data-i18n="create_job" nm-bind="
+                    onclick: () => $post('/jobs/create'),
+                    disabled: () => _nmFetching || !name || !schedule || !command || (!tag && backends.length === 0)
+                " { "Create Job" }
+            }
+            p .job-hint data-i18n="schedule_hint" {
+                "Schedules use cron syntax (minute, hour, day of month, month, day of week) in the dashboard's local time."
+            }
+        }
+    }
+}
+
+fn job_runs(job: &Job, selected: Option<u32>) -> Markup {
+    html! {
+        section #job-runs {
+            h2 { (job.name) }
+            @if !job.queued.is_empty() {
+                p .command-summary {
+                    span data-i18n="queued_backends" { "Waiting for reconnect:" }
+                    " "
+                    (job.queued.iter().map(|x| x.to_string()).collect::<Vec<_>>().join(", "))
+                }
+            }
+            .audit-table-wrap {
+                table .audit-table {
+                    tr {
+                        th data-i18n="time" { "Time" }
+                        th data-i18n="backends" { "Backends" }
+                        th data-i18n="succeeded" { "Succeeded" }
+                        th {}
+                    }
+                    @if job.runs.is_empty() {
+                        tr {
+                            td colspan="4" data-i18n="no_job_runs" { "This job hasn't run yet" }
+                        }
+                    }
+                    @for run in job.runs.iter().rev() {
+                        tr .active[selected == Some(run.id)] {
+                            td { (format_time(run.time)) }
+                            td { (run.results.len()) }
+                            td { (run.succeeded()) }
+                            td {
+                                a .pager-btn href={ (job_link(job)) "&run=" (format!("{:08x}", run.id)) } data-i18n="view" { "View" }
+                            }
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+pub async fn page(req: ServerRequest) -> Result<ServerResponse, ServerResponse> {
+    req.check_login()?;
+
+    let query: JobsQuery = req.extract_query()?;
+    let selected_job = u32::from_str_radix(&query.job, 16).ok();
+    let selected_run = u32::from_str_radix(&query.run, 16).ok();
+
+    let jobs = req.extract_jobs();
+    let store = jobs.get();
+    let job = selected_job.and_then(|id| store.get(id));
+
+    let content = html! {
+        (job_list(&store))
+        @if let Some(job) = job {
+            (job_runs(job, selected_run))
+            (commands::results(selected_run.and_then(|id| job.runs.iter().find(|x| x.id == id))))
+        }
+        (create_form(&req))
+    };
+
+    template(&req, content, "")
+}
+
+pub async fn create(mut req: ServerRequest) -> Result<ServerResponse, ServerResponse> {
+    req.check_login()?;
+
+    let form: CreateForm = req.extract_form().await?;
+
+    let name = form.name.trim();
+    let schedule = form.schedule.trim();
+    let command = form.command.trim();
+    if name.is_empty() || command.is_empty() {
+        return Err(req.error(StatusCode::BAD_REQUEST, "name and command can't be empty"));
+    }
+
+    if let Err(err) = parse_schedule(schedule) {
+        return Err(req.error(StatusCode::BAD_REQUEST, format!("{err:#}")));
+    }
+
+    let backends: Vec<_> = form.backends.iter().collect();
+    if backends.is_empty() && form.tag.is_empty() {
+        return Err(req.error(StatusCode::BAD_REQUEST, "no backends selected"));
+    }
+
+    let jobs = req.extract_jobs();
+    let mut store = jobs.get();
+    store
+        .create(NewJob {
+            name: name.into(),
+            schedule: schedule.into(),
+            command: command.into(),
+            backends,
+            tag: form.tag,
+            timeout: form.timeout.clamp(1, MAX_TIMEOUT),
+            offline: form.offline,
+        })
+        .map_err(|err| req.error(StatusCode::BAD_REQUEST, format!("{err:#}")))?;
+
+    template(&req, job_list(&store), "")
+}
+
+async fn extract_id(req: &mut ServerRequest) -> Result<u32, ServerResponse> {
+    let form: JobForm = req.extract_form().await?;
+
+    u32::from_str_radix(&form.id, 16)
+        .map_err(|_| req.error(StatusCode::BAD_REQUEST, "invalid job id"))
+}
+
+pub async fn toggle(mut req: ServerRequest) -> Result<ServerResponse, ServerResponse> {
+    req.check_login()?;
+
+    let id = extract_id(&mut req).await?;
+    req.extract_jobs().get().toggle(id);
+
+    Ok(ServerResponse::new().redirect(RedirectType::SeeOther, "/jobs"))
+}
+
+pub async fn run(mut req: ServerRequest) -> Result<ServerResponse, ServerResponse> {
+    req.check_login()?;
+
+    let id = extract_id(&mut req).await?;
+    req.extract_jobs()
+        .run_now(id, &req.extract_backend_registry(), &req.extract_audit());
+
+    Ok(ServerResponse::new().redirect(RedirectType::SeeOther, &format!("/jobs?job={id:08x}")))
+}
+
+pub async fn delete(mut req: ServerRequest) -> Result<ServerResponse, ServerResponse> {
+    req.check_login()?;
+
+    let id = extract_id(&mut req).await?;
+    req.extract_jobs().get().delete(id);
+
+    Ok(ServerResponse::new().redirect(RedirectType::SeeOther, "/jobs"))
+}
@@ -26,6 +26,10 @@ pub async fn page(req: ServerRequest) -> Result<ServerResponse, ServerResponse>
                     placeholder="Password"
                     data-i18n-placeholder="password_placeholder"
                 {}
+                label .remember-me {
+                    input name="remember" type="checkbox" value="true";
+                    span data-i18n="remember_me" { "Remember me" }
+                }
                 button .primary-btn data-i18n="login" { "Login" }
             }
         }
@@ -37,6 +41,8 @@ pub async fn page(req: ServerRequest) -> Result<ServerResponse, ServerResponse>
 #[derive(Deserialize)]
 pub struct LoginForm {
     pass: String,
+    #[serde(default)]
+    remember: bool,
 }
 
 pub async fn form(mut req: ServerRequest) -> Result<ServerResponse, ServerResponse> {
@@ -52,16 +58,22 @@ pub async fn form(mut req: ServerRequest) -> Result<ServerResponse, ServerRespon
 
     if form_hash == hash {
         let logins = req.extract_logins();
-        let mut logins = logins.get();
-
-        let token = logins.new_token();
+        let token = logins
+            .get()
+            .new_token(form.remember, req.peer_ip(), req.user_agent());
+
+        // Without "remember me" the cookie only lasts until the browser is closed.
+        // It isn't refreshed on activity, so it's kept for as long as the session could live
+        let cookie = if form.remember {
+            let max_age = req.config().session_remember_max_age;
+            format!("token={token}; Max-Age={max_age}; Path=/; HttpOnly; SameSite=Strict")
+        } else {
+            format!("token={token}; Path=/; HttpOnly; SameSite=Strict")
+        };
 
         Ok(ServerResponse::new()
             .redirect(RedirectType::SeeOther, "/")
-            .header(
-                header::SET_COOKIE,
-                format!("token={token}; Max-Age=3600; Path=/; HttpOnly"),
-            ))
+            .header(header::SET_COOKIE, cookie))
     } else {
         Err(ServerResponse::new().redirect(RedirectType::SeeOther, "/login"))
     }
@@ -1,12 +1,20 @@
 use std::time::Duration;
 
-use maud::html;
+use maud::{Markup, html};
+use pretty_bytes_typed::pretty_bytes_binary;
+use proto::backend::{BoardInfo, Throttling};
+use serde::Deserialize;
 
-use crate::http::{request::ServerRequest, response::ServerResponse};
+use crate::http::{
+    auth::now_secs,
+    request::ServerRequest,
+    response::{RedirectType, ServerResponse},
+    tokens::ApiScope,
+};
 
 use super::template::{send_req, template};
 
-use hyper::StatusCode;
+use hyper::{StatusCode, header};
 use tokio::fs;
 
 async fn read_config() -> Result<String, ServerResponse> {
@@ -24,6 +32,218 @@ async fn read_config() -> Result<String, ServerResponse> {
     })
 }
 
+fn format_ago(timestamp: u64, now: u64) -> String {
+    // Round to the minute, since seconds aren't useful here
+    let secs = now.saturating_sub(timestamp) / 60 * 60;
+    humantime::format_duration(Duration::from_secs(secs)).to_string()
+}
+
+fn sessions_table(req: &ServerRequest) -> Markup {
+    let current_id = req.session_id();
+    let now = now_secs();
+
+    let logins = req.extract_logins();
+    let mut logins = logins.get();
+
+    html! {
+        table .sessions-table {
+            tr {
+                th data-i18n="session" { "Session" }
+                th data-i18n="source_address" { "Source Address" }
+                th data-i18n="user_agent" { "User Agent" }
+                th data-i18n="created" { "Created" }
+                th data-i18n="last_active" { "Last Active" }
+                th data-i18n="actions" { "Actions" }
+            }
+            @for session in logins.sessions() {
+                @let id = format!("{:08x}", session.id);
+                tr {
+                    td {
+                        code { (id) }
+                        @if current_id == Some(session.id) {
+                            " "
+                            span .status-badge data-status="active" data-i18n="current_session" { "Current" }
+                        }
+                        @if session.remember {
+                            " "
+                            span .status-badge data-status="other" data-i18n="remembered" { "Remembered" }
+                        }
+                    }
+                    td {
+                        @if let Some(addr) = session.addr {
+                            (addr)
+                        } @else {
+                            "--"
+                        }
+                    }
+                    td { span .session-agent title=(session.user_agent) { (session.user_agent) } }
+                    td data-i18n-template="time_ago" data-value=(format_ago(session.created, now)) {
+                        (format_ago(session.created, now)) " ago"
+                    }
+                    td data-i18n-template="time_ago" data-value=(format_ago(session.last_seen, now)) {
+                        (format_ago(session.last_seen, now)) " ago"
+                    }
+                    td {
+                        form action="/management/sessions/revoke" method="POST" {
+                            input type="hidden" name="id" value=(id);
+                            button .logout data-i18n="revoke" { "Revoke" }
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+fn tokens_table(req: &ServerRequest) -> Markup {
+    let now = now_secs();
+
+    let tokens = req.extract_tokens();
+    let tokens = tokens.get();
+
+    html! {
+        table .sessions-table {
+            tr {
+                th data-i18n="name" { "Name" }
+                th data-i18n="scope" { "Scope" }
+                th data-i18n="created" { "Created" }
+                th data-i18n="last_used" { "Last Used" }
+                th data-i18n="actions" { "Actions" }
+            }
+            @if tokens.tokens().is_empty() {
+                tr {
+                    td colspan="5" data-i18n="no_api_tokens" { "No API tokens" }
+                }
+            }
+            @for token in tokens.tokens() {
+                tr {
+                    td { (token.name) }
+                    td {
+                        span .status-badge data-status=(if token.scope == ApiScope::Write { "active" } else { "other" }) {
+                            (token.scope)
+                        }
+                    }
+                    td data-i18n-template="time_ago" data-value=(format_ago(token.created, now)) {
+                        (format_ago(token.created, now)) " ago"
+                    }
+                    @if let Some(last_used) = token.last_used {
+                        td data-i18n-template="time_ago" data-value=(format_ago(last_used, now)) {
+                            (format_ago(last_used, now)) " ago"
+                        }
+                    } @else {
+                        td data-i18n="never" { "Never" }
+                    }
+                    td {
+                        form action="/management/tokens/revoke" method="POST" {
+                            input type="hidden" name="id" value=(format!("{:08x}", token.id));
+                            button .logout data-i18n="revoke" { "Revoke" }
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+// Problems happening right now come first, then ones that happened at some point since boot
+fn throttling_warnings(t: &Throttling) -> Vec<(&'static str, &'static str, &'static str)> {
+    let current = [
+        (
+            t.under_voltage,
+            "under_voltage_now",
+            "Under-voltage detected",
+        ),
+        (t.throttled, "throttled_now", "CPU is throttled"),
+        (t.freq_capped, "freq_capped_now", "ARM frequency is capped"),
+        (
+            t.soft_temp_limit,
+            "soft_temp_limit_now",
+            "Soft temperature limit is active",
+        ),
+    ];
+    let past = [
+        (
+            t.under_voltage_occurred,
+            "under_voltage_occurred",
+            "Under-voltage has occurred since boot",
+        ),
+        (
+            t.throttled_occurred,
+            "throttled_occurred",
+            "Throttling has occurred since boot",
+        ),
+        (
+            t.freq_capped_occurred,
+            "freq_capped_occurred",
+            "ARM frequency capping has occurred since boot",
+        ),
+        (
+            t.soft_temp_limit_occurred,
+            "soft_temp_limit_occurred",
+            "Soft temperature limit has been reached since boot",
+        ),
+    ];
+
+    let current = current.into_iter().map(|x| ("failed", x));
+    let past = past.into_iter().map(|x| ("warning", x));
+
+    current
+        .chain(past)
+        .filter(|(_, (active, ..))| *active)
+        .map(|(status, (_, key, text))| (status, key, text))
+        .collect()
+}
+
+fn board_section(board: &BoardInfo) -> Markup {
+    html! {
+        br;
+        section {
+            h2 data-i18n="hardware_health" { "Hardware Health" }
+
+            @if let Some(throttling) = &board.throttling {
+                @let warnings = throttling_warnings(throttling);
+                @if warnings.is_empty() {
+                    p .hardware-status data-status="ok" data-i18n="no_throttling" { "No under-voltage or throttling since boot" }
+                }
+                @for (status, key, text) in warnings {
+                    p .hardware-status data-status=(status) data-i18n=(key) { (text) }
+                }
+            }
+
+            table .management-table {
+                tr {
+                    td data-i18n="board_model" { "Board Model" }
+                    td { (board.model) }
+                }
+                @if let Some(serial) = &board.serial {
+                    tr {
+                        td data-i18n="serial_number" { "Serial Number" }
+                        td { code { (serial) } }
+                    }
+                }
+                @if let Some(revision) = &board.revision {
+                    tr {
+                        td data-i18n="board_revision" { "Revision" }
+                        td { code { (revision) } }
+                    }
+                }
+                @if let Some(arm) = board.arm_memory {
+                    tr {
+                        td data-i18n="arm_memory" { "ARM Memory" }
+                        td { (pretty_bytes_binary(arm, Some(0))) }
+                    }
+                }
+                @if let Some(gpu) = board.gpu_memory {
+                    tr {
+                        td data-i18n="gpu_memory" { "GPU Memory" }
+                        td { (pretty_bytes_binary(gpu, Some(0))) }
+                    }
+                }
+            }
+        }
+    }
+}
+
 pub async fn page(req: ServerRequest) -> Result<ServerResponse, ServerResponse> {
     req.check_login()?;
 
@@ -72,6 +292,9 @@ pub async fn page(req: ServerRequest) -> Result<ServerResponse, ServerResponse>
                 }
             }
         }
+        @if let Some(board) = &data.board {
+            (board_section(board))
+        }
         br;
         section {
             h2 data-i18n="frontend_config" { "Frontend Config" }
@@ -88,11 +311,41 @@ pub async fn page(req: ServerRequest) -> Result<ServerResponse, ServerResponse>
                 (backend_cfg)
             }
         }
+        br;
+        section {
+            h2 data-i18n="api_tokens" { "API Tokens" }
+
+            .sessions-table-wrap {
+                (tokens_table(&req))
+            }
+            br;
+
+            form .token-form action="/management/tokens/create" method="POST" {
+                label {
+                    span data-i18n="name" { "Name" }
+                    input type="text" name="name" required;
+                }
+                label {
+                    span data-i18n="scope" { "Scope" }
+                    select name="scope" {
+                        option value="read" data-i18n="scope_read" { "Read" }
+                        option value="write" data-i18n="scope_write" { "Read and write" }
+                    }
+                }
+                button .primary-btn data-i18n="create_token" { "Create Token" }
+            }
+        }
         @if req.config().enable_login {
             br;
             section {
                 h2 data-i18n="dashboard_administration" { "Dashboard Administration" }
 
+                h3 data-i18n="active_sessions" { "Active Sessions" }
+                .sessions-table-wrap {
+                    (sessions_table(&req))
+                }
+                br;
+
                 form action="/logout" method="POST" {
                     button .logout data-i18n="logout" { "Logout" }
                 }
@@ -102,3 +355,83 @@ pub async fn page(req: ServerRequest) -> Result<ServerResponse, ServerResponse>
 
     template(&req, content, "")
 }
+
+#[derive(Deserialize)]
+pub struct RevokeForm {
+    id: String,
+}
+
+pub async fn revoke_session(mut req: ServerRequest) -> Result<ServerResponse, ServerResponse> {
+    req.check_login()?;
+
+    let current_id = req.session_id();
+    let form: RevokeForm = req.extract_form().await?;
+
+    let id = u32::from_str_radix(&form.id, 16).map_err(|_| {
+        ServerResponse::new()
+            .status(StatusCode::BAD_REQUEST)
+            .body("invalid session id")
+    })?;
+
+    req.extract_logins().get().revoke(id);
+
+    if current_id == Some(id) {
+        return Ok(ServerResponse::new()
+            .redirect(RedirectType::SeeOther, "/login")
+            .header(header::SET_COOKIE, "token=; Max-Age=0; Path=/; HttpOnly"));
+    }
+
+    Ok(ServerResponse::new().redirect(RedirectType::SeeOther, "/management"))
+}
+
+#[derive(Deserialize)]
+pub struct CreateTokenForm {
+    name: String,
+    scope: ApiScope,
+}
+
+pub async fn create_token(mut req: ServerRequest) -> Result<ServerResponse, ServerResponse> {
+    req.check_login()?;
+
+    let form: CreateTokenForm = req.extract_form().await?;
+
+    let name = form.name.trim();
+    if name.is_empty() {
+        return Err(ServerResponse::new()
+            .status(StatusCode::BAD_REQUEST)
+            .body("token name can't be empty"));
+    }
+
+    let token = req.extract_tokens().get().create(name.into(), form.scope);
+
+    let content = html! {
+        section {
+            h2 data-i18n="api_token_created" { "API Token Created" }
+
+            p data-i18n="api_token_notice" {
+                "Copy this token now, it won't be shown again."
+            }
+            pre .api-token { (token) }
+            br;
+            a .pager-btn href="/management" data-i18n="back" { "Back" }
+        }
+    };
+
+    template(&req, content, "")
+}
+
+pub async fn revoke_token(mut req: ServerRequest) -> Result<ServerResponse, ServerResponse> {
+    req.check_login()?;
+
+    let form: RevokeForm = req.extract_form().await?;
+
+    let id = u32::from_str_radix(&form.id, 16).map_err(|_| {
+        ServerResponse::new()
+            .status(StatusCode::BAD_REQUEST)
+            .body("invalid token id")
+    })?;
+
+    req.extract_tokens().get().revoke(id);
+
+    Ok(ServerResponse::new().redirect(RedirectType::SeeOther, "/management"))
+}
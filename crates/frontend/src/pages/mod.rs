@@ -1,9 +1,13 @@
+pub mod audit;
 pub mod browser;
+pub mod commands;
+pub mod fleet;
+pub mod jobs;
 pub mod login;
 pub mod management;
 pub mod process;
 pub mod service;
 pub mod software;
 pub mod system;
-mod template;
+pub mod template;
 pub mod terminal;
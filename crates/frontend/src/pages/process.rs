@@ -1,11 +1,25 @@
+use std::{
+    cmp::Ordering,
+    collections::{BTreeSet, HashMap},
+    fmt::Display,
+};
+
+use hyper::StatusCode;
 use maud::{Markup, html};
 use pretty_bytes_typed::pretty_bytes_binary;
-use proto::{backend::ProcessStatus, frontend::SignalAction};
+use proto::{
+    backend::{ProcessDetail, ProcessInfo, ProcessStatus},
+    frontend::{ProcessDetailRequest, SignalAction},
+};
+use regex_lite::{Regex, RegexBuilder};
 use serde::{Deserialize, Serialize};
 
 use crate::http::{request::ServerRequest, response::ServerResponse};
 
-use super::template::{Icon, send_act, send_req, template};
+use super::{
+    audit::format_time,
+    template::{Icon, send_act, send_req, template},
+};
 
 #[derive(Deserialize, Serialize, Clone)]
 #[serde(default)]
@@ -14,6 +28,12 @@ pub struct ProcessQuery {
     reverse: bool,
     page: usize,
     per_page: usize,
+    tree: bool,
+    // Matched against the name and command line
+    search: String,
+    regex: bool,
+    user: String,
+    status: StatusFilter,
 }
 
 impl Default for ProcessQuery {
@@ -23,7 +43,38 @@ impl Default for ProcessQuery {
             reverse: false,
             page: 1,
             per_page: 25,
+            tree: false,
+            search: String::new(),
+            regex: false,
+            user: String::new(),
+            status: StatusFilter::default(),
+        }
+    }
+}
+
+impl ProcessQuery {
+    // Plain searches are escaped, so both kinds go through the same case-insensitive matching
+    fn search_regex(&self) -> Result<Option<Regex>, regex_lite::Error> {
+        if self.search.is_empty() {
+            return Ok(None);
         }
+
+        let pattern = if self.regex {
+            self.search.clone()
+        } else {
+            regex_lite::escape(&self.search)
+        };
+
+        RegexBuilder::new(&pattern)
+            .case_insensitive(true)
+            .build()
+            .map(Some)
+    }
+
+    fn matches(&self, search: Option<&Regex>, proc: &ProcessInfo) -> bool {
+        (self.user.is_empty() || proc.user.as_deref() == Some(self.user.as_str()))
+            && self.status.matches(proc.status)
+            && search.is_none_or(|x| x.is_match(&proc.name) || x.is_match(&proc.cmd))
     }
 }
 
@@ -33,11 +84,35 @@ pub enum ColumnSort {
     #[default]
     Pid,
     Name,
+    User,
     Status,
     Cpu,
     Ram,
 }
 
+#[derive(Deserialize, Serialize, PartialEq, Eq, Clone, Copy, Default, Debug)]
+#[serde(rename_all = "lowercase")]
+pub enum StatusFilter {
+    #[default]
+    All,
+    Running,
+    Sleeping,
+    Paused,
+    Other,
+}
+
+impl StatusFilter {
+    fn matches(self, status: ProcessStatus) -> bool {
+        match self {
+            Self::All => true,
+            Self::Running => status == ProcessStatus::Running,
+            Self::Sleeping => status == ProcessStatus::Sleeping,
+            Self::Paused => status == ProcessStatus::Paused,
+            Self::Other => status == ProcessStatus::Other,
+        }
+    }
+}
+
 fn process_link(query: &ProcessQuery, page: usize, per_page: usize) -> String {
     let mut next = query.clone();
     next.page = page;
@@ -48,18 +123,27 @@ fn process_link(query: &ProcessQuery, page: usize, per_page: usize) -> String {
     format!("/process?{query}")
 }
 
+fn view_link(query: &ProcessQuery, tree: bool) -> String {
+    let mut next = query.clone();
+    next.page = 1;
+    next.tree = tree;
+
+    let query = serde_urlencoded::to_string(next).unwrap();
+
+    format!("/process?{query}")
+}
+
 fn table_header(name: &str, i18n_key: &str, sort: ColumnSort, query: &ProcessQuery) -> Markup {
-    let reverse = if query.sort == sort {
+    let mut next = query.clone();
+    next.reverse = if query.sort == sort {
         !query.reverse
     } else {
         false
     };
+    next.sort = sort;
+    next.page = 1;
 
-    let sort_str = serde_plain::to_string(&sort).unwrap();
-    let href = format!(
-        "/process?sort={sort_str}&reverse={reverse}&page=1&per_page={}",
-        query.per_page
-    );
+    let href = format!("/process?{}", serde_urlencoded::to_string(next).unwrap());
 
     html! {
         th {
@@ -86,6 +170,217 @@ fn process_status(status: ProcessStatus) -> (&'static str, &'static str, &'stati
     }
 }
 
+struct ProcessRow {
+    info: ProcessInfo,
+    depth: usize,
+    // PIDs of every ancestor above this row, so collapsing any of them hides it
+    ancestors: String,
+    children: usize,
+    // Including every descendant, which is the same as the process's own usage in the flat view
+    total_cpu: f32,
+    total_mem: u64,
+}
+
+impl ProcessRow {
+    fn new(info: ProcessInfo) -> Self {
+        Self {
+            total_cpu: info.cpu,
+            total_mem: info.mem,
+            info,
+            depth: 0,
+            ancestors: String::new(),
+            children: 0,
+        }
+    }
+}
+
+fn compare_rows(query: &ProcessQuery, a: &ProcessRow, b: &ProcessRow) -> Ordering {
+    let order = match query.sort {
+        ColumnSort::Pid => a.info.pid.cmp(&b.info.pid),
+        ColumnSort::Name => a.info.name.cmp(&b.info.name),
+        ColumnSort::User => a.info.user.cmp(&b.info.user),
+        ColumnSort::Status => a.info.status.cmp(&b.info.status),
+        ColumnSort::Cpu => a.total_cpu.total_cmp(&b.total_cpu),
+        ColumnSort::Ram => a.total_mem.cmp(&b.total_mem),
+    };
+
+    if query.reverse {
+        order.reverse()
+    } else {
+        order
+    }
+}
+
+// Orders processes depth-first below their parents, with siblings sorted by the current column
+fn build_tree(processes: Vec<ProcessInfo>, query: &ProcessQuery) -> Vec<ProcessRow> {
+    let index: HashMap<_, _> = processes
+        .iter()
+        .enumerate()
+        .map(|(idx, proc)| (proc.pid, idx))
+        .collect();
+
+    // Processes whose parent isn't listed, like init and kthreadd, become roots
+    let mut roots = Vec::new();
+    let mut children = vec![Vec::new(); processes.len()];
+    for (idx, proc) in processes.iter().enumerate() {
+        match proc.parent.and_then(|pid| index.get(&pid)) {
+            Some(&parent) if parent != idx => children[parent].push(idx),
+            _ => roots.push(idx),
+        }
+    }
+
+    let mut rows: Vec<_> = processes.into_iter().map(ProcessRow::new).collect();
+
+    // Parents come before their descendants here, so going backwards adds up each subtree bottom-up
+    let mut preorder = Vec::with_capacity(rows.len());
+    let mut stack = roots.clone();
+    while let Some(idx) = stack.pop() {
+        preorder.push(idx);
+        stack.extend(&children[idx]);
+    }
+    for &idx in preorder.iter().rev() {
+        let (cpu, mem) = children[idx].iter().fold((0., 0), |(cpu, mem), &child| {
+            (cpu + rows[child].total_cpu, mem + rows[child].total_mem)
+        });
+        rows[idx].total_cpu += cpu;
+        rows[idx].total_mem += mem;
+        rows[idx].children = children[idx].len();
+    }
+
+    roots.sort_by(|&a, &b| compare_rows(query, &rows[a], &rows[b]));
+    for list in &mut children {
+        list.sort_by(|&a, &b| compare_rows(query, &rows[a], &rows[b]));
+    }
+
+    let mut position = vec![0; rows.len()];
+    let mut stack: Vec<_> = roots.into_iter().rev().map(|idx| (idx, 0)).collect();
+    let mut next_position = 0;
+    while let Some((idx, depth)) = stack.pop() {
+        position[idx] = next_position;
+        next_position += 1;
+
+        let pid = rows[idx].info.pid;
+        let ancestors = if rows[idx].ancestors.is_empty() {
+            pid.to_string()
+        } else {
+            format!("{} {pid}", rows[idx].ancestors)
+        };
+        for &child in children[idx].iter().rev() {
+            rows[child].depth = depth + 1;
+            rows[child].ancestors.clone_from(&ancestors);
+            stack.push((child, depth + 1));
+        }
+    }
+
+    let mut ordered: Vec<_> = rows.into_iter().zip(position).collect();
+    ordered.sort_by_key(|(_, position)| *position);
+    ordered.into_iter().map(|(row, _)| row).collect()
+}
+
+fn name_cell(row: &ProcessRow, tree: bool) -> Markup {
+    let pid = row.info.pid;
+    // Bracketed like in ps, since kernel threads have no command line to tell them apart
+    let name = if row.info.kernel_thread {
+        format!("[{}]", row.info.name)
+    } else {
+        row.info.name.clone()
+    };
+    let link = html! {
+        a .process-name href=(format!("/process/detail?pid={pid}")) title=(name) { (name) }
+    };
+
+    html! {
+        @if tree {
+            .process-tree-cell style=(format!("--depth: {}", row.depth)) {
+                @if row.children > 0 {
+                    button .tree-toggle
+                        type="button"
+                        data-pid=(pid)
+                        title="Collapse or expand children"
+                        aria-label="Collapse or expand children"
+                        data-i18n-title="toggle_children"
+                        data-i18n-aria-label="toggle_children"
+                        nm-bind="
+                            'class.collapsed': () => collapsed.has(this.dataset.pid),
+                            onclick: () => {
+                                const next = new Set(collapsed);
+                                next.has(this.dataset.pid) ? next.delete(this.dataset.pid) : next.add(this.dataset.pid);
+                                collapsed = next;
+                                sessionStorage.setItem('processCollapsed', JSON.stringify([...next]));
+                            }
+                        "
+                    {
+                        (Icon::new("fa6-solid-caret-down"))
+                    }
+                } @else {
+                    span .tree-spacer {}
+                }
+                (link)
+            }
+        } @else {
+            (link)
+        }
+    }
+}
+
+fn filter_form(query: &ProcessQuery, users: &BTreeSet<String>, error: Option<&str>) -> Markup {
+    let statuses = [
+        (StatusFilter::All, "all", "All"),
+        (StatusFilter::Running, "running", "Running"),
+        (StatusFilter::Sleeping, "sleeping", "Sleeping"),
+        (StatusFilter::Paused, "paused", "Paused"),
+        (StatusFilter::Other, "other", "Other"),
+    ];
+
+    html! {
+        form .audit-filters method="GET" action="/process" {
+            input type="hidden" name="sort" value=(serde_plain::to_string(&query.sort).unwrap());
+            input type="hidden" name="reverse" value=(query.reverse);
+            input type="hidden" name="per_page" value=(query.per_page);
+            input type="hidden" name="tree" value=(query.tree);
+
+            label {
+                span data-i18n="search" { "Search" }
+                input
+                    type="search"
+                    name="search"
+                    value=(query.search)
+                    placeholder="Name or command line"
+                    data-i18n-placeholder="process_search_placeholder";
+            }
+            label .process-regex {
+                span data-i18n="regex" { "Regex" }
+                input type="checkbox" name="regex" value="true" checked[query.regex];
+            }
+            label {
+                span data-i18n="user" { "User" }
+                select name="user" {
+                    option value="" data-i18n="all" { "All" }
+                    @for user in users {
+                        option value=(user) selected[*user == query.user] { (user) }
+                    }
+                }
+            }
+            label {
+                span data-i18n="status" { "Status" }
+                select name="status" {
+                    @for (status, i18n_key, label) in statuses {
+                        option value=(serde_plain::to_string(&status).unwrap()) selected[status == query.status] data-i18n=(i18n_key) {
+                            (label)
+                        }
+                    }
+                }
+            }
+            button .primary-btn data-i18n="filter" { "Filter" }
+        }
+        @if let Some(error) = error {
+            p .command-error {
+                span data-i18n="invalid_regex" { "Invalid regex" } ": " (error)
+            }
+        }
+    }
+}
+
 pub async fn page(req: ServerRequest) -> Result<ServerResponse, ServerResponse> {
     req.check_login()?;
 
@@ -94,28 +389,44 @@ pub async fn page(req: ServerRequest) -> Result<ServerResponse, ServerResponse>
     query.per_page = query.per_page.clamp(15, 120);
 
     let mut processes = send_req!(req, Processes)?.processes;
-    match query.sort {
-        ColumnSort::Pid => processes.sort_by_key(|a| a.pid),
-        ColumnSort::Name => processes.sort_by(|a, b| a.name.cmp(&b.name)),
-        ColumnSort::Status => processes.sort_by_key(|a| a.status),
-        ColumnSort::Cpu => processes.sort_by(|a, b| a.cpu.total_cmp(&b.cpu)),
-        ColumnSort::Ram => processes.sort_by_key(|a| a.mem),
-    }
-    if query.reverse {
-        processes.reverse();
+
+    let mut users: BTreeSet<_> = processes.iter().filter_map(|x| x.user.clone()).collect();
+    if !query.user.is_empty() {
+        users.insert(query.user.clone());
     }
 
-    let total_items = processes.len();
-    let total_pages = total_items.div_ceil(query.per_page).max(1);
+    let search_error = match query.search_regex() {
+        Ok(search) => {
+            processes.retain(|x| query.matches(search.as_ref(), x));
+            None
+        }
+        Err(err) => {
+            processes.clear();
+            Some(err.to_string())
+        }
+    };
+
+    let rows = if query.tree {
+        build_tree(processes, &query)
+    } else {
+        let mut rows: Vec<_> = processes.into_iter().map(ProcessRow::new).collect();
+        rows.sort_by(|a, b| compare_rows(&query, a, b));
+        rows
+    };
+
+    let total_items = rows.len();
+    // Collapsing subtrees would leave pages uneven, so the tree is shown all at once
+    let per_page = if query.tree {
+        total_items.max(1)
+    } else {
+        query.per_page
+    };
+    let total_pages = total_items.div_ceil(per_page).max(1);
     query.page = query.page.min(total_pages);
 
-    let start_idx = (query.page - 1) * query.per_page;
-    let end_idx = (start_idx + query.per_page).min(total_items);
-    let page_items: Vec<_> = processes
-        .into_iter()
-        .skip(start_idx)
-        .take(query.per_page)
-        .collect();
+    let start_idx = (query.page - 1) * per_page;
+    let end_idx = (start_idx + per_page).min(total_items);
+    let page_items: Vec<_> = rows.into_iter().skip(start_idx).take(per_page).collect();
 
     let shown_start = if total_items == 0 { 0 } else { start_idx + 1 };
 
@@ -127,14 +438,15 @@ pub async fn page(req: ServerRequest) -> Result<ServerResponse, ServerResponse>
     let next_link = process_link(&query, next_page, query.per_page);
     let last_link = process_link(&query, total_pages, query.per_page);
 
-    let content = html! {
-        section
+    let swap = html! {
+        div
             #process-swap
-            nm-data="denseRows: localStorage.getItem('processDenseRows') === 'true'"
+            nm-data="
+                denseRows: localStorage.getItem('processDenseRows') === 'true',
+                collapsed: new Set(JSON.parse(sessionStorage.getItem('processCollapsed') || '[]'))
+            "
             nm-bind="oninit: () => $debounce(() => $get(window.location.pathname + window.location.search), 2000)"
         {
-            h2 data-i18n="processes_title" { "Processes" }
-
             .process-toolbar {
                 p .process-summary data-i18n-template="process_summary" data-start=(shown_start) data-end=(end_idx) data-total=(total_items) {
                     "Showing " (shown_start) "-" (end_idx) " of " (total_items) " processes"
@@ -156,14 +468,22 @@ pub async fn page(req: ServerRequest) -> Result<ServerResponse, ServerResponse>
                     }
 
                     .process-page-size {
-                        span data-i18n="per_page" { "Per Page" }
-                        @for page_size in [15_usize, 25, 50, 100] {
-                            @let size_link = process_link(&query, 1, page_size);
-                            a .page-size-link
-                                class=(if query.per_page == page_size { "active" } else { "" })
-                                href=(size_link)
-                            {
-                                (page_size)
+                        span data-i18n="view" { "View" }
+                        a .page-size-link class=(if query.tree { "" } else { "active" }) href=(view_link(&query, false)) data-i18n="view_flat" { "Flat" }
+                        a .page-size-link class=(if query.tree { "active" } else { "" }) href=(view_link(&query, true)) data-i18n="view_tree" { "Tree" }
+                    }
+
+                    @if !query.tree {
+                        .process-page-size {
+                            span data-i18n="per_page" { "Per Page" }
+                            @for page_size in [15_usize, 25, 50, 100] {
+                                @let size_link = process_link(&query, 1, page_size);
+                                a .page-size-link
+                                    class=(if query.per_page == page_size { "active" } else { "" })
+                                    href=(size_link)
+                                {
+                                    (page_size)
+                                }
                             }
                         }
                     }
@@ -186,6 +506,7 @@ pub async fn page(req: ServerRequest) -> Result<ServerResponse, ServerResponse>
                     tr {
                         (table_header("PID", "pid", ColumnSort::Pid, &query))
                         (table_header("Name", "name", ColumnSort::Name, &query))
+                        (table_header("User", "user", ColumnSort::User, &query))
                         (table_header("Status", "status", ColumnSort::Status, &query))
                         (table_header("CPU Usage", "cpu_usage", ColumnSort::Cpu, &query))
                         (table_header("RAM Usage", "ram_usage", ColumnSort::Ram, &query))
@@ -193,22 +514,40 @@ pub async fn page(req: ServerRequest) -> Result<ServerResponse, ServerResponse>
                     }
                     @if page_items.is_empty() {
                         tr {
-                            td colspan="6" data-i18n="no_process_data" { "No process data available" }
+                            td colspan="7" data-i18n="no_process_data" { "No process data available" }
                         }
                     } @else {
-                        @for proc in page_items {
+                        @for row in page_items {
+                            @let proc = &row.info;
                             @let pretty_mem = pretty_bytes_binary(proc.mem, Some(0));
                             @let (status_attr, status_label, status_i18n) = process_status(proc.status);
 
-                            tr {
-                                @let proc_name = proc.name.as_str();
+                            tr
+                                data-ancestors=[(row.depth > 0).then_some(&row.ancestors)]
+                                nm-bind=[(row.depth > 0).then_some("hidden: () => this.dataset.ancestors.split(' ').some((pid) => collapsed.has(pid))")]
+                            {
                                 td { (proc.pid) }
-                                td { span .process-name title=(proc_name) { (proc_name) } }
+                                td { (name_cell(&row, query.tree)) }
+                                td { (proc.user.as_deref().unwrap_or("--")) }
                                 td {
                                     span .status-badge data-status=(status_attr) data-i18n=(status_i18n) { (status_label) }
                                 }
-                                td { (format!("{:.1}%", proc.cpu)) }
-                                td { (pretty_mem) }
+                                td {
+                                    (format!("{:.1}%", proc.cpu))
+                                    @if row.children > 0 {
+                                        span .process-subtotal title="Including child processes" data-i18n-title="including_children" {
+                                            "Σ " (format!("{:.1}%", row.total_cpu))
+                                        }
+                                    }
+                                }
+                                td {
+                                    (pretty_mem)
+                                    @if row.children > 0 {
+                                        span .process-subtotal title="Including child processes" data-i18n-title="including_children" {
+                                            "Σ " (pretty_bytes_binary(row.total_mem, Some(0)))
+                                        }
+                                    }
+                                }
                                 td nm-data data-pid=(proc.pid) {
                                     .actions-cell {
                                         button data-signal="kill" title="Kill process" aria-label="Kill process" data-i18n-title="kill_process" data-i18n-aria-label="kill_process" nm-bind="onclick: () => $post('/process/signal')" {
@@ -234,16 +573,31 @@ pub async fn page(req: ServerRequest) -> Result<ServerResponse, ServerResponse>
                 }
             }
 
-            .process-pagination {
-                a .pager-btn class=(if query.page == 1 { "disabled" } else { "" }) href=(first_link) aria-disabled=(if query.page == 1 { "true" } else { "false" }) data-i18n="first" { "First" }
-                a .pager-btn class=(if query.page == 1 { "disabled" } else { "" }) href=(prev_link) aria-disabled=(if query.page == 1 { "true" } else { "false" }) data-i18n="prev" { "Prev" }
-                p .pager-info data-i18n-template="page_of" data-page=(query.page) data-total-pages=(total_pages) { "Page " (query.page) " / " (total_pages) }
-                a .pager-btn class=(if query.page == total_pages { "disabled" } else { "" }) href=(next_link) aria-disabled=(if query.page == total_pages { "true" } else { "false" }) data-i18n="next" { "Next" }
-                a .pager-btn class=(if query.page == total_pages { "disabled" } else { "" }) href=(last_link) aria-disabled=(if query.page == total_pages { "true" } else { "false" }) data-i18n="last" { "Last" }
+            @if !query.tree {
+                .process-pagination {
+                    a .pager-btn class=(if query.page == 1 { "disabled" } else { "" }) href=(first_link) aria-disabled=(if query.page == 1 { "true" } else { "false" }) data-i18n="first" { "First" }
+                    a .pager-btn class=(if query.page == 1 { "disabled" } else { "" }) href=(prev_link) aria-disabled=(if query.page == 1 { "true" } else { "false" }) data-i18n="prev" { "Prev" }
+                    p .pager-info data-i18n-template="page_of" data-page=(query.page) data-total-pages=(total_pages) { "Page " (query.page) " / " (total_pages) }
+                    a .pager-btn class=(if query.page == total_pages { "disabled" } else { "" }) href=(next_link) aria-disabled=(if query.page == total_pages { "true" } else { "false" }) data-i18n="next" { "Next" }
+                    a .pager-btn class=(if query.page == total_pages { "disabled" } else { "" }) href=(last_link) aria-disabled=(if query.page == total_pages { "true" } else { "false" }) data-i18n="last" { "Last" }
+                }
             }
         }
     };
 
+    // Refreshes only replace the table, so the filters aren't reset while they're being typed in
+    if req.is_fixi() {
+        return template(&req, swap, "");
+    }
+
+    let content = html! {
+        section .process-page {
+            h2 data-i18n="processes_title" { "Processes" }
+            (filter_form(&query, &users, search_error.as_deref()))
+            (swap)
+        }
+    };
+
     template(&req, content, "")
 }
 
@@ -256,3 +610,176 @@ pub async fn signal(req: ServerRequest) -> Result<ServerResponse, ServerResponse
 
     Ok(ServerResponse::new())
 }
+
+#[derive(Deserialize)]
+pub struct DetailQuery {
+    pid: u32,
+    #[serde(default)]
+    environment: bool,
+}
+
+fn optional(value: Option<impl Display>) -> Markup {
+    html! {
+        @if let Some(value) = value {
+            (value)
+        } @else {
+            span .unreadable data-i18n="not_readable" { "Not readable" }
+        }
+    }
+}
+
+fn sockets_table(data: &ProcessDetail) -> Markup {
+    html! {
+        @match &data.sockets {
+            None => p { (optional(None::<&str>)) },
+            Some(sockets) if sockets.is_empty() => p data-i18n="no_listening_sockets" { "No listening sockets" },
+            Some(sockets) => table .sessions-table {
+                tr {
+                    th data-i18n="protocol" { "Protocol" }
+                    th data-i18n="address" { "Address" }
+                }
+                @for socket in sockets {
+                    tr {
+                        td { (socket.protocol) }
+                        td { code { (socket.addr) } }
+                    }
+                }
+            },
+        }
+    }
+}
+
+// The environment can hold secrets, so it's only shown to the logged in admin after an explicit click
+// Every read is recorded in the audit log by send_req
+fn environment_section(data: &ProcessDetail, requested: bool, login: bool) -> Markup {
+    html! {
+        @match &data.environment {
+            None if !login => p data-i18n="environment_needs_login" {
+                "The environment is only shown when login is enabled"
+            },
+            Some(vars) => pre .process-environment {
+                @for var in vars {
+                    (var) "\n"
+                }
+            },
+            None if requested => p { (optional(None::<&str>)) },
+            None => a .pager-btn href=(format!("/process/detail?pid={}&environment=true", data.pid)) data-i18n="show_environment" {
+                "Show Environment"
+            },
+        }
+    }
+}
+
+pub async fn detail(req: ServerRequest) -> Result<ServerResponse, ServerResponse> {
+    req.check_login()?;
+
+    let query: DetailQuery = req.extract_query()?;
+
+    // Without login, anyone who can reach the dashboard would be able to read it
+    let login = req.config().enable_login;
+    if query.environment && !login {
+        return Err(req.error(
+            StatusCode::FORBIDDEN,
+            "the environment is only shown when enable_login is on",
+        ));
+    }
+
+    let data = send_req!(
+        req,
+        ProcessDetail(ProcessDetailRequest {
+            pid: query.pid,
+            environment: query.environment,
+        })
+    )?
+    .ok_or_else(|| req.error(StatusCode::NOT_FOUND, "process not found"))?;
+
+    let (status_attr, status_label, status_i18n) = process_status(data.status);
+    let user = data
+        .uid
+        .map(|uid| format!("{} ({uid})", data.user.as_deref().unwrap_or("?")));
+    let group = data
+        .gid
+        .map(|gid| format!("{} ({gid})", data.group.as_deref().unwrap_or("?")));
+
+    let content = html! {
+        section #process-detail {
+            .process-toolbar {
+                h2 { (data.name) " " span .process-summary { "PID " (data.pid) } }
+                a .pager-btn href="/process" data-i18n="back" { "Back" }
+            }
+
+            table .management-table {
+                tr {
+                    td data-i18n="status" { "Status" }
+                    td { span .status-badge data-status=(status_attr) data-i18n=(status_i18n) { (status_label) } }
+                }
+                tr {
+                    td data-i18n="cpu_usage" { "CPU Usage" }
+                    td { (format!("{:.1}%", data.cpu)) }
+                }
+                tr {
+                    td data-i18n="ram_usage" { "RAM Usage" }
+                    td { (pretty_bytes_binary(data.mem, Some(0))) }
+                }
+                tr {
+                    td data-i18n="command_line" { "Command Line" }
+                    td { code .process-cmdline { (data.cmd.join(" ")) } }
+                }
+                tr {
+                    td data-i18n="executable" { "Executable" }
+                    td { (optional(data.exe.as_deref())) }
+                }
+                tr {
+                    td data-i18n="working_directory" { "Working Directory" }
+                    td { (optional(data.cwd.as_deref())) }
+                }
+                tr {
+                    td data-i18n="user" { "User" }
+                    td { (optional(user)) }
+                }
+                tr {
+                    td data-i18n="group" { "Group" }
+                    td { (optional(group)) }
+                }
+                tr {
+                    td data-i18n="parent_pid" { "Parent PID" }
+                    td {
+                        @if let Some(parent) = data.parent {
+                            a href=(format!("/process/detail?pid={parent}")) { (parent) }
+                        } @else {
+                            "--"
+                        }
+                    }
+                }
+                tr {
+                    td data-i18n="start_time" { "Start Time" }
+                    td { (format_time(data.start_time)) }
+                }
+                tr {
+                    td data-i18n="threads" { "Threads" }
+                    td { (optional(data.threads)) }
+                }
+                tr {
+                    td data-i18n="nice_value" { "Nice Value" }
+                    td { (optional(data.nice)) }
+                }
+                tr {
+                    td data-i18n="open_fds" { "Open File Descriptors" }
+                    td { (optional(data.open_fds)) }
+                }
+                tr {
+                    td data-i18n="cgroup" { "Cgroup" }
+                    td { (optional(data.cgroup.as_deref())) }
+                }
+            }
+
+            h3 data-i18n="listening_sockets" { "Listening Sockets" }
+            (sockets_table(&data))
+
+            h3 data-i18n="environment" { "Environment" }
+            (environment_section(&data, query.environment, login))
+        }
+    };
+
+    template(&req, content, "")
+}
@@ -1,16 +1,22 @@
+use hyper::{StatusCode, header};
 use maud::{Markup, html};
 use proto::{
     backend::{SoftwareInfo, SoftwareResponse},
-    frontend::CommandAction,
+    frontend::{CommandAction, StartCommandAction},
 };
 use serde::Deserialize;
+use tokio_stream::wrappers::UnboundedReceiverStream;
 
 use crate::{
+    backend::CommandJobInfo,
     http::{query_array::QueryArray, request::ServerRequest, response::ServerResponse},
     pages::template::Icon,
 };
 
-use super::template::{send_req, template};
+use super::{
+    audit::format_time,
+    template::{send_act, send_req, template},
+};
 
 fn software_table(
     list: &[SoftwareInfo],
@@ -93,15 +99,159 @@ fn card(data: &SoftwareResponse) -> Markup {
     }
 }
 
+fn job_badge(job: &CommandJobInfo) -> Markup {
+    let (status, key, name) = match &job.exit {
+        None => ("running", "running", "Running"),
+        Some(exit) if exit.exit_code == Some(0) => ("active", "succeeded", "Succeeded"),
+        Some(exit) if exit.blocked => ("failed", "blocked", "Blocked by Policy"),
+        Some(_) => ("failed", "failed", "Failed"),
+    };
+
+    html! {
+        span .status-badge data-status=(status) data-i18n=(key) { (name) }
+    }
+}
+
+fn job_status(job: &CommandJobInfo) -> Markup {
+    let failed = job.exit.as_ref().is_some_and(|x| x.exit_code != Some(0));
+
+    html! {
+        #output-status .operation-status .failed[failed] {
+            p .command-meta {
+                (job_badge(job))
+                @if let Some(exit) = job.exit.as_ref().filter(|x| x.error.is_none()) {
+                    " · "
+                    @if let Some(signal) = exit.signal {
+                        span data-i18n-template="killed_by_signal" data-signal=(signal) {
+                            "Killed by signal " (signal)
+                        }
+                    } @else {
+                        @let exit_code = exit.exit_code.map(|x| x.to_string()).unwrap_or_else(|| "--".into());
+                        span data-i18n-template="exit_code_value" data-value=(exit_code) {
+                            "Exit code: " (exit_code)
+                        }
+                    }
+                    " · " (format!("{:.1}s", exit.duration_ms as f32 / 1000.))
+                }
+            }
+            @if let Some(err) = job.exit.as_ref().and_then(|x| x.error.as_ref()) {
+                p .command-error { (err) }
+            }
+            @if !job.stderr.is_empty() {
+                details open[failed] {
+                    summary data-i18n="error_output" { "Error Output" }
+                    pre .command-stderr { (String::from_utf8_lossy(&job.stderr)) }
+                }
+            }
+        }
+    }
+}
+
+fn output(job: &CommandJobInfo) -> Markup {
+    let id = format!("{:08x}", job.id);
+
+    html! {
+        // Puts the job in the URL, so it can be come back to after navigating away
+        section #output nm-bind={
+            "oninit: () => {
+                this.scrollIntoView();
+                history.replaceState(null, '', '/software?job=" (id) "');
+            }"
+        } {
+            h2 data-i18n="operation_summary" { "Operation Summary" }
+            p .command-summary {
+                code { (job.command) }
+                " · " (format_time(job.started))
+            }
+            command-output data-src={ "/software/stream?job=" (id) } nm-bind={
+                "ondone: () => $get('/software/status?job=" (id) "')"
+            } {}
+            (job_status(job))
+        }
+    }
+}
+
+fn recent_jobs(jobs: &[CommandJobInfo]) -> Markup {
+    html! {
+        section #software-jobs {
+            h2 data-i18n="recent_operations" { "Recent Operations" }
+            .audit-table-wrap {
+                table .audit-table {
+                    tr {
+                        th data-i18n="time" { "Time" }
+                        th data-i18n="command" { "Command" }
+                        th data-i18n="status" { "Status" }
+                        th {}
+                    }
+                    @if jobs.is_empty() {
+                        tr {
+                            td colspan="4" data-i18n="no_operations" { "No operations have been run" }
+                        }
+                    }
+                    @for job in jobs.iter().rev() {
+                        tr {
+                            td { (format_time(job.started)) }
+                            td { span .audit-args title=(job.command) { (job.command) } }
+                            td { (job_badge(job)) }
+                            td {
+                                a .pager-btn href={ "/software?job=" (format!("{:08x}", job.id)) } data-i18n="view" { "View" }
+                            }
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+async fn command_jobs(req: &ServerRequest) -> Result<Vec<CommandJobInfo>, ServerResponse> {
+    let backend = req.extract_backends()?.current_backend;
+
+    backend.handle.command_jobs().await.map_err(|err| {
+        req.error(
+            StatusCode::BAD_GATEWAY,
+            format!("failed to get operations: {err:#}"),
+        )
+    })
+}
+
+async fn find_job(req: &ServerRequest, id: &str) -> Result<CommandJobInfo, ServerResponse> {
+    let id = u32::from_str_radix(id, 16).ok();
+
+    command_jobs(req)
+        .await?
+        .into_iter()
+        .find(|x| Some(x.id) == id)
+        .ok_or_else(|| req.error(StatusCode::NOT_FOUND, "operation not found"))
+}
+
+#[derive(Deserialize, Default)]
+#[serde(default)]
+pub struct JobQuery {
+    job: String,
+}
+
 pub async fn page(req: ServerRequest) -> Result<ServerResponse, ServerResponse> {
     req.check_login()?;
 
+    let query: JobQuery = req.extract_query()?;
+
     let data = send_req!(req, Software)?;
+    let jobs = command_jobs(&req).await?;
+
+    let id = u32::from_str_radix(&query.job, 16).ok();
+    let job = jobs.iter().find(|x| Some(x.id) == id);
 
     let content = html! {
         (card(&data))
         br;
-        #output {}
+        @if let Some(job) = job {
+            (output(job))
+        } @else {
+            #output {}
+        }
+        br;
+        (recent_jobs(&jobs))
     };
 
     template(&req, content, "")
@@ -136,25 +286,63 @@ pub async fn form(mut req: ServerRequest) -> Result<ServerResponse, ServerRespon
         args.push(id.to_string());
     }
 
-    let msg = CommandAction {
-        cmd: "/boot/dietpi/dietpi-software".into(),
-        args,
+    let msg = StartCommandAction {
+        id: rand::random(),
+        action: CommandAction {
+            cmd: "/boot/dietpi/dietpi-software".into(),
+            args,
+        },
     };
+    let id = msg.id;
+
+    // Installs can take a long time, so the output gets streamed in instead of waited on
+    send_act!(req, StartCommand(msg))?;
 
-    let resp = send_req!(req, Command(msg))?;
-    let output = String::from_utf8_lossy(&resp.output);
+    let job = find_job(&req, &format!("{id:08x}")).await?;
+    let jobs = command_jobs(&req).await?;
+
+    let content = html! {
+        (output(&job))
+        (recent_jobs(&jobs))
+    };
+
+    template(&req, content, "")
+}
+
+pub async fn stream(req: ServerRequest) -> Result<ServerResponse, ServerResponse> {
+    req.check_login()?;
+
+    let query: JobQuery = req.extract_query()?;
+    let id = u32::from_str_radix(&query.job, 16)
+        .map_err(|_| req.error(StatusCode::BAD_REQUEST, "invalid operation id"))?;
+
+    let backend = req.extract_backends()?.current_backend.handle;
+    let output_rx = backend.watch_command(id).await.map_err(|err| {
+        req.error(
+            StatusCode::BAD_GATEWAY,
+            format!("failed to watch operation: {err:#}"),
+        )
+    })?;
+
+    Ok(ServerResponse::new()
+        .header(header::CONTENT_TYPE, "application/octet-stream")
+        .stream_body(UnboundedReceiverStream::new(output_rx)))
+}
+
+// Fetched once the output stream ends, with the software list refreshed to match what was (un)installed
+pub async fn status(req: ServerRequest) -> Result<ServerResponse, ServerResponse> {
+    req.check_login()?;
+
+    let query: JobQuery = req.extract_query()?;
+    let job = find_job(&req, &query.job).await?;
 
     let data = send_req!(req, Software)?;
+    let jobs = command_jobs(&req).await?;
 
     let content = html! {
         (card(&data))
-        br;
-        section #output nm-bind="oninit: () => this.scrollIntoView()" {
-            h2 data-i18n="operation_summary" { "Operation Summary" }
-            pre {
-                (output)
-            }
-        }
+        (job_status(&job))
+        (recent_jobs(&jobs))
     };
 
     template(&req, content, "")
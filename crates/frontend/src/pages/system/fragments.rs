@@ -1,8 +1,11 @@
 use maud::{Markup, html};
 use pretty_bytes_typed::{pretty_bytes, pretty_bytes_binary};
-use proto::backend::{CpuResponse, DiskResponse, MemResponse, NetworkResponse, TempResponse};
+use proto::backend::{
+    CpuResponse, DiskIOResponse, DiskResponse, MemResponse, NetworkResponse, Pressure,
+    PressureInfo, TempResponse,
+};
 
-use crate::http::query_array::QueryArray;
+use crate::history::{HistoryRange, Sample};
 
 use super::graph::{Axis, SvgGraph};
 
@@ -26,14 +29,22 @@ pub fn cpu_meters(cpu_data: &CpuResponse, temp_data: &TempResponse) -> Markup {
                 @let temp_text = format!("{temp:.1}ºC");
                 p data-i18n-template="cpu_temperature_value" data-value=(temp_text) { "CPU Temperature: " (format!("{temp:.1}")) "ºC" }
             }
+            @let load = &cpu_data.load;
+            @let load_text = format!("{:.2} / {:.2} / {:.2}", load.one, load.five, load.fifteen);
+            p data-i18n-template="load_average_value" data-value=(load_text) { "Load Average: " (load_text) }
             @let global_cpu_text = format!("{:.1}%", cpu_data.global_cpu);
             p data-i18n-template="global_cpu_value" data-value=(global_cpu_text) { "Global CPU: " (format!("{:.1}", cpu_data.global_cpu)) "%" }
             .meter-container {
                 .bar.cpu style={"--scale:"(cpu_data.global_cpu / 100.)} {}
             }
-            @for (usage, num) in cpu_iter {
+            @for (idx, (usage, num)) in cpu_iter.enumerate() {
                 @let core_cpu_text = format!("{usage:.1}%");
-                p data-i18n-template="cpu_core_usage" data-core=(num) data-value=(core_cpu_text) { "CPU "(num)": " (format!("{usage:.1}")) "%" }
+                p {
+                    span data-i18n-template="cpu_core_usage" data-core=(num) data-value=(core_cpu_text) { "CPU "(num)": " (format!("{usage:.1}")) "%" }
+                    @if let Some(freq) = cpu_data.frequencies.get(idx) {
+                        span .cpu-freq title={ (freq.min) " - " (freq.max) " MHz" } { (freq.current) " MHz" }
+                    }
+                }
                 .meter-container {
                     .bar.cpu style={"--scale:"(usage / 100.)} {}
                 }
@@ -42,16 +53,53 @@ pub fn cpu_meters(cpu_data: &CpuResponse, temp_data: &TempResponse) -> Markup {
     }
 }
 
-pub fn cpu_graph(data: &CpuResponse, points: &mut QueryArray) -> Markup {
-    let mut graph = SvgGraph::new(Axis::Percent);
+fn pressure_row(name: &str, key: &str, pressure: Option<Pressure>) -> Markup {
+    html! {
+        @if let Some(pressure) = pressure {
+            @let some = pressure.some;
+            @let text = format!("{:.2}% / {:.2}% / {:.2}%", some.avg10, some.avg60, some.avg300);
+            p {
+                span data-i18n=(key) { (name) }
+                ": " (text)
+                @if let Some(full) = pressure.full {
+                    " "
+                    span data-i18n-template="pressure_full" data-value=(format!("{:.2}%", full.avg10)) {
+                        "(full: " (format!("{:.2}%", full.avg10)) ")"
+                    }
+                }
+            }
+            .meter-container {
+                .bar.pressure style={"--scale:"(some.avg10 / 100.)} {}
+            }
+        }
+    }
+}
 
-    let points_iter = std::iter::once(data.global_cpu)
-        .chain(points.iter())
-        .take(20);
+pub fn pressure_meters(data: &PressureInfo) -> Option<Markup> {
+    if data.cpu.is_none() && data.memory.is_none() && data.io.is_none() {
+        return None;
+    }
+
+    Some(html! {
+        section {
+            h2 data-i18n="pressure_stall" { "Pressure Stall" }
+            p .sensor-limits data-i18n="pressure_hint" { "Share of time tasks were stalled over 10s / 60s / 300s" }
+            (pressure_row("CPU", "cpu", data.cpu))
+            (pressure_row("Memory", "memory_label", data.memory))
+            (pressure_row("I/O", "io", data.io))
+        }
+    })
+}
 
-    graph.add_series(points_iter.clone(), "var(--gray-12)", "CPU", "cpu");
+pub fn cpu_graph(history: &[Option<Sample>]) -> Markup {
+    let mut graph = SvgGraph::new(Axis::Percent);
 
-    *points = points_iter.collect();
+    graph.add_series(
+        history.iter().map(|x| x.map(|x| x.cpu)),
+        "var(--gray-12)",
+        "CPU",
+        "cpu",
+    );
 
     html! {
         section {
@@ -61,15 +109,57 @@ pub fn cpu_graph(data: &CpuResponse, points: &mut QueryArray) -> Markup {
     }
 }
 
-pub fn temp_graph(data: &TempResponse, points: &mut QueryArray) -> Option<Markup> {
-    data.temp.map(|temp| {
-        let mut graph = SvgGraph::new(Axis::Temp);
+fn format_temp(temp: Option<f32>) -> String {
+    temp.map(|x| format!("{x:.1}ºC"))
+        .unwrap_or_else(|| "--".into())
+}
 
-        let points_iter = std::iter::once(temp).chain(points.iter()).take(20);
+pub fn temp_meters(data: &TempResponse) -> Option<Markup> {
+    if data.sensors.is_empty() {
+        return None;
+    }
 
-        graph.add_series(points_iter.clone(), "var(--red-6)", "Temperature", "temperature");
+    Some(html! {
+        section {
+            h2 data-i18n="temperature_sensors" { "Temperature Sensors" }
+
+            @for sensor in &data.sensors {
+                // Scaled to the critical temperature where there is one, so a full bar means trouble
+                @let limit = sensor.critical.or(sensor.max).filter(|x| *x > 0.).unwrap_or(100.);
+                @let scale = sensor.temp.map_or(0., |x| (x / limit).clamp(0., 1.));
+
+                p .sensor-name {
+                    (sensor.label) ": " (format_temp(sensor.temp))
+                    @if sensor.primary {
+                        " "
+                        span .sensor-primary data-i18n="primary_sensor" { "Primary" }
+                    }
+                }
+                @if sensor.max.is_some() || sensor.critical.is_some() {
+                    @let max = format_temp(sensor.max);
+                    @let critical = format_temp(sensor.critical);
+                    p .sensor-limits data-i18n-template="sensor_limits" data-max=(max) data-critical=(critical) {
+                        "Max: " (max) ", Critical: " (critical)
+                    }
+                }
+                .meter-container {
+                    .bar.temp style={"--scale:"(scale)} {}
+                }
+            }
+        }
+    })
+}
 
-        *points = points_iter.collect();
+pub fn temp_graph(data: &TempResponse, history: &[Option<Sample>]) -> Option<Markup> {
+    data.temp.map(|_| {
+        let mut graph = SvgGraph::new(Axis::Temp);
+
+        graph.add_series(
+            history.iter().map(|x| x.and_then(|x| x.temp)),
+            "var(--red-6)",
+            "Temperature",
+            "temperature",
+        );
 
         html! {
             section {
@@ -104,36 +194,85 @@ pub fn mem_meters(data: &MemResponse) -> Markup {
             div .meter-container {
                 div .bar.swap style={"--scale:"(swap_percent / 100.)} {}
             }
+
+            @let details = &data.details;
+            dl .mem-details {
+                dt data-i18n="available" { "Available" }
+                dd { (pretty_bytes_binary(details.available, Some(1))) }
+                dt data-i18n="free" { "Free" }
+                dd { (pretty_bytes_binary(details.free, Some(1))) }
+                dt data-i18n="buffers" { "Buffers" }
+                dd { (pretty_bytes_binary(details.buffers, Some(1))) }
+                dt data-i18n="cached" { "Cached" }
+                dd { (pretty_bytes_binary(details.cached, Some(1))) }
+                dt data-i18n="shared" { "Shared" }
+                dd { (pretty_bytes_binary(details.shared, Some(1))) }
+                dt data-i18n="dirty" { "Dirty" }
+                dd { (pretty_bytes_binary(details.dirty, Some(1))) }
+                @if let Some(zram) = details.zram {
+                    dt { "zram" }
+                    dd { (pretty_bytes_binary(zram.compressed, Some(1))) " / " (pretty_bytes_binary(zram.original, Some(1))) }
+                }
+                @if let Some(zswap) = details.zswap {
+                    dt { "zswap" }
+                    dd { (pretty_bytes_binary(zswap.compressed, Some(1))) " / " (pretty_bytes_binary(zswap.original, Some(1))) }
+                }
+                dt data-i18n="swap_in_out" { "Swap In / Out" }
+                dd { (pretty_bytes(details.swap_in, Some(1))) "/s / " (pretty_bytes(details.swap_out, Some(1))) "/s" }
+            }
         }
     }
 }
 
-pub fn mem_graph(
-    data: &MemResponse,
-    ram_points: &mut QueryArray,
-    swap_points: &mut QueryArray,
-) -> Markup {
+pub fn mem_graph(history: &[Option<Sample>]) -> Markup {
     let mut graph = SvgGraph::new(Axis::Percent);
 
-    let ram_percent = calc_percent(data.ram.used, data.ram.total);
-    let swap_percent = calc_percent(data.swap.used, data.swap.total);
+    graph.add_series(
+        history.iter().map(|x| x.map(|x| x.ram)),
+        "var(--gray-12)",
+        "RAM",
+        "ram",
+    );
+    graph.add_series(
+        history.iter().map(|x| x.map(|x| x.swap)),
+        "var(--red-6)",
+        "Swap",
+        "swap",
+    );
 
-    let ram_points_iter = std::iter::once(ram_percent)
-        .chain(ram_points.iter())
-        .take(20);
-    let swap_points_iter = std::iter::once(swap_percent)
-        .chain(swap_points.iter())
-        .take(20);
-
-    graph.add_series(ram_points_iter.clone(), "var(--gray-12)", "RAM", "ram");
-    graph.add_series(swap_points_iter.clone(), "var(--red-6)", "Swap", "swap");
+    html! {
+        section {
+            h2 data-i18n="memory_graph" { "Memory Graph" }
+            (graph)
+        }
+    }
+}
 
-    *ram_points = ram_points_iter.collect();
-    *swap_points = swap_points_iter.collect();
+pub fn mem_breakdown_graph(history: &[Option<Sample>]) -> Markup {
+    let mut graph = SvgGraph::stacked(Axis::Percent);
+
+    graph.add_series(
+        history.iter().map(|x| x.map(|x| x.apps)),
+        "var(--gray-12)",
+        "Apps",
+        "apps",
+    );
+    graph.add_series(
+        history.iter().map(|x| x.map(|x| x.buffers)),
+        "var(--gray-8)",
+        "Buffers",
+        "buffers",
+    );
+    graph.add_series(
+        history.iter().map(|x| x.map(|x| x.cached)),
+        "var(--red-6)",
+        "Cached",
+        "cached",
+    );
 
     html! {
         section {
-            h2 data-i18n="memory_graph" { "Memory Graph" }
+            h2 data-i18n="memory_breakdown_graph" { "Memory Breakdown" }
             (graph)
         }
     }
@@ -154,30 +293,110 @@ pub fn disk_meters(data: &DiskResponse) -> Markup {
                 .meter-container {
                     .bar.disk style={"--scale:"(disk_percent / 100.)} {}
                 }
+                p .disk-details {
+                    (disk.fs_type) " · " (disk.device)
+                    @if disk.inodes.total > 0 {
+                        @let inode_percent = format!("{:.1}%", calc_percent(disk.inodes.used, disk.inodes.total));
+                        " · "
+                        span data-i18n-template="inode_usage" data-value=(inode_percent) { "Inodes: " (inode_percent) }
+                    }
+                    @if disk.read_only {
+                        " "
+                        span .status-badge data-status="inactive" data-i18n="read_only" { "Read-only" }
+                    }
+                    @if disk.removable {
+                        " "
+                        span .status-badge data-status="other" data-i18n="removable" { "Removable" }
+                    }
+                }
             }
         }
     }
 }
 
-pub fn net_graph(
-    data: &NetworkResponse,
-    sent_points: &mut QueryArray,
-    recv_points: &mut QueryArray,
-) -> Markup {
-    let mut graph = SvgGraph::new(Axis::Bytes);
+pub fn disk_io(data: &DiskIOResponse) -> Markup {
+    html! {
+        section #disk-io {
+            h2 data-i18n="disk_io" { "Disk I/O" }
+            .audit-table-wrap {
+                table .audit-table {
+                    tr {
+                        th data-i18n="device" { "Device" }
+                        th data-i18n="read" { "Read" }
+                        th data-i18n="written" { "Written" }
+                        th data-i18n="iops" { "IOPS" }
+                        th data-i18n="busy" { "Busy" }
+                        th data-i18n="total" { "Total" }
+                    }
+                    @if data.devices.is_empty() {
+                        tr {
+                            td colspan="6" data-i18n="no_disk_io" { "No block devices found" }
+                        }
+                    }
+                    @for device in &data.devices {
+                        tr {
+                            td { (device.name) }
+                            td { (pretty_bytes(device.read_bytes, Some(1))) "/s" }
+                            td { (pretty_bytes(device.write_bytes, Some(1))) "/s" }
+                            td { "R " (format!("{:.1}", device.read_ops)) br; "W " (format!("{:.1}", device.write_ops)) }
+                            td {
+                                (format!("{:.1}%", device.busy))
+                                .meter-container {
+                                    .bar.disk style={"--scale:"(device.busy / 100.)} {}
+                                }
+                            }
+                            td {
+                                "R " (pretty_bytes(device.total_read, Some(1)))
+                                br;
+                                "W " (pretty_bytes(device.total_written, Some(1)))
+                            }
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
 
-    let sent_points_iter = std::iter::once(data.sent as f32)
-        .chain(sent_points.iter())
-        .take(20);
-    let recv_points_iter = std::iter::once(data.recv as f32)
-        .chain(recv_points.iter())
-        .take(20);
+pub fn disk_io_graph(name: &str, history: &[Option<Sample>]) -> Markup {
+    let mut graph = SvgGraph::new(Axis::ByteRate);
+
+    graph.add_series(
+        history.iter().map(|x| x.map(|x| x.recv)),
+        "var(--gray-12)",
+        "Read",
+        "read",
+    );
+    graph.add_series(
+        history.iter().map(|x| x.map(|x| x.sent)),
+        "var(--red-6)",
+        "Written",
+        "written",
+    );
 
-    graph.add_series(sent_points_iter.clone(), "var(--gray-12)", "Sent", "sent");
-    graph.add_series(recv_points_iter.clone(), "var(--red-6)", "Received", "received");
+    html! {
+        section {
+            h2 data-i18n-template="disk_io_graph" data-name=(name) { "Disk I/O Graph: " (name) }
+            (graph)
+        }
+    }
+}
 
-    *sent_points = sent_points_iter.collect();
-    *recv_points = recv_points_iter.collect();
+pub fn net_graph(history: &[Option<Sample>]) -> Markup {
+    let mut graph = SvgGraph::new(Axis::ByteRate);
+
+    graph.add_series(
+        history.iter().map(|x| x.map(|x| x.sent)),
+        "var(--gray-12)",
+        "Sent",
+        "sent",
+    );
+    graph.add_series(
+        history.iter().map(|x| x.map(|x| x.recv)),
+        "var(--red-6)",
+        "Received",
+        "received",
+    );
 
     html! {
         section {
@@ -186,3 +405,90 @@ pub fn net_graph(
         }
     }
 }
+
+pub fn interface_graph(name: &str, history: &[Option<Sample>]) -> Markup {
+    let mut graph = SvgGraph::new(Axis::ByteRate);
+
+    graph.add_series(
+        history.iter().map(|x| x.map(|x| x.sent)),
+        "var(--gray-12)",
+        "Sent",
+        "sent",
+    );
+    graph.add_series(
+        history.iter().map(|x| x.map(|x| x.recv)),
+        "var(--red-6)",
+        "Received",
+        "received",
+    );
+
+    html! {
+        section {
+            h2 data-i18n-template="interface_graph" data-name=(name) { "Network Graph: " (name) }
+            (graph)
+        }
+    }
+}
+
+fn state_status(state: &str) -> &'static str {
+    match state {
+        "up" => "active",
+        "down" | "lowerlayerdown" | "notpresent" => "inactive",
+        _ => "unknown",
+    }
+}
+
+pub fn net_interfaces(data: &NetworkResponse, range: HistoryRange, selected: &str) -> Markup {
+    html! {
+        section #network-interfaces {
+            h2 data-i18n="network_interfaces" { "Network Interfaces" }
+            .audit-table-wrap {
+                table .audit-table {
+                    tr {
+                        th data-i18n="interface" { "Interface" }
+                        th data-i18n="state" { "State" }
+                        th data-i18n="mac_address" { "MAC Address" }
+                        th data-i18n="addresses" { "Addresses" }
+                        th data-i18n="rate" { "Rate" }
+                        th data-i18n="total" { "Total" }
+                        th data-i18n="packets" { "Packets" }
+                        th data-i18n="errors" { "Errors" }
+                        th data-i18n="drops" { "Drops" }
+                    }
+                    @if data.interfaces.is_empty() {
+                        tr {
+                            td colspan="9" data-i18n="no_interfaces" { "No network interfaces match this backend's filter" }
+                        }
+                    }
+                    @for interface in &data.interfaces {
+                        tr .active[interface.name == selected] {
+                            td {
+                                a href={ "/system?range=" (range) "&interface=" (interface.name) } { (interface.name) }
+                            }
+                            td { span .status-badge data-status=(state_status(&interface.state)) { (interface.state) } }
+                            td { code { (interface.mac) } }
+                            td .interface-addresses {
+                                @for addr in interface.ipv4.iter().chain(&interface.ipv6) {
+                                    span { (addr) }
+                                }
+                            }
+                            td {
+                                "↑ " (pretty_bytes(interface.sent, Some(1))) "/s"
+                                br;
+                                "↓ " (pretty_bytes(interface.recv, Some(1))) "/s"
+                            }
+                            td {
+                                "↑ " (pretty_bytes(interface.total_sent, Some(1)))
+                                br;
+                                "↓ " (pretty_bytes(interface.total_recv, Some(1)))
+                            }
+                            td { "↑ " (interface.packets_sent) br; "↓ " (interface.packets_recv) }
+                            td { "↑ " (interface.errors_sent) br; "↓ " (interface.errors_recv) }
+                            td { "↑ " (interface.drops_sent) br; "↓ " (interface.drops_recv) }
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
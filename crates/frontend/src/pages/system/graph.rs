@@ -8,7 +8,8 @@ const GRAPH_X_LINES: u32 = 20;
 const LINE_SPACING: u32 = 10;
 
 pub struct GraphSeries {
-    points: Vec<(u32, f32)>,
+    // Newest point first, with None where there's no data
+    points: Vec<Option<f32>>,
     color: &'static str,
     label: &'static str,
     i18n_key: &'static str,
@@ -18,7 +19,7 @@ pub struct GraphSeries {
 pub enum Axis {
     Percent,
     Temp,
-    Bytes,
+    ByteRate,
 }
 
 impl Axis {
@@ -26,7 +27,7 @@ impl Axis {
         match self {
             Self::Percent => format!("{val:.1}%"),
             Self::Temp => format!("{val:.1}ºC"),
-            Self::Bytes => pretty_bytes(val as u64, Some(0)).to_string(),
+            Self::ByteRate => format!("{}/s", pretty_bytes(val as u64, Some(0))),
         }
     }
 
@@ -34,7 +35,7 @@ impl Axis {
         match self {
             Self::Percent => format!("{val:.0}%"),
             Self::Temp => format!("{val:.0}º"),
-            Self::Bytes => pretty_bytes(val as u64, Some(0)).to_string(),
+            Self::ByteRate => pretty_bytes(val as u64, Some(0)).to_string(),
         }
     }
 
@@ -43,7 +44,7 @@ impl Axis {
             self.format_tick(match self {
                 Self::Percent => (10 * x) as f32,
                 Self::Temp => (10 * x + 20) as f32,
-                Self::Bytes => 10_u64.pow(x as u32) as f32,
+                Self::ByteRate => 10_u64.pow(x as u32) as f32,
             })
         };
         std::array::from_fn(generator_fn)
@@ -52,12 +53,13 @@ impl Axis {
     // Translates data from [min, max] to [0, 100]
     // Percent: [0, 100]
     // Temp: [20, 120]
-    // Bytes: [1, 10^10] (log)
+    // ByteRate: [1, 10^10] (log)
     fn interpolate(self, data: f32) -> f32 {
         match self {
             Self::Percent => data,
             Self::Temp => data - 20.,
-            Self::Bytes => 10. * data.log10(),
+            // Anything under a byte would go off the bottom of the graph
+            Self::ByteRate => 10. * data.max(1.).log10(),
         }
     }
 }
@@ -65,6 +67,8 @@ impl Axis {
 pub struct SvgGraph {
     series: Vec<GraphSeries>,
     axis: Axis,
+    // Each series is drawn as a filled area on top of the ones before it
+    stacked: bool,
 }
 
 impl SvgGraph {
@@ -72,18 +76,25 @@ impl SvgGraph {
         Self {
             series: Vec::new(),
             axis,
+            stacked: false,
+        }
+    }
+
+    pub fn stacked(axis: Axis) -> Self {
+        Self {
+            stacked: true,
+            ..Self::new(axis)
         }
     }
 
     pub fn add_series(
         &mut self,
-        points: impl Iterator<Item = f32>,
+        points: impl Iterator<Item = Option<f32>>,
         color: &'static str,
         label: &'static str,
         i18n_key: &'static str,
     ) {
-        // Creates (x, y) pairs starting from the right
-        let points: Vec<_> = (0..GRAPH_X_LINES).rev().zip(points).collect();
+        let points: Vec<_> = points.collect();
 
         let series = GraphSeries {
             points,
@@ -96,6 +107,97 @@ impl SvgGraph {
     }
 }
 
+impl SvgGraph {
+    fn last_idx(&self) -> usize {
+        let num_points = self.series.iter().map(|x| x.points.len()).max();
+        num_points.unwrap_or_default().saturating_sub(1).max(1)
+    }
+
+    // Splits points into runs of coordinates, leaving gaps where there's no data
+    fn runs(&self, points: &[Option<f32>], left_margin: u32, y_end: u32) -> Vec<Vec<(f32, f32)>> {
+        let graph_width = (LINE_SPACING * (GRAPH_X_LINES - 1)) as f32;
+        let x_end = left_margin as f32 + graph_width;
+        let last_idx = self.last_idx() as f32;
+
+        let mut runs = Vec::new();
+        let mut acc = Vec::new();
+
+        for (i, point) in points.iter().enumerate() {
+            match point {
+                Some(y) => {
+                    // Newest point is on the right
+                    let x = x_end - graph_width * i as f32 / last_idx;
+                    let y = y_end as f32 - self.axis.interpolate(*y);
+                    acc.push((x, y));
+                }
+                None if !acc.is_empty() => runs.push(std::mem::take(&mut acc)),
+                None => {}
+            }
+        }
+
+        if !acc.is_empty() {
+            runs.push(acc);
+        }
+
+        runs
+    }
+
+    fn to_points<'a>(coords: impl Iterator<Item = &'a (f32, f32)>) -> String {
+        use core::fmt::Write;
+
+        let mut acc = String::new();
+        for (x, y) in coords {
+            let _ = write!(acc, "{x},{y} ");
+        }
+        acc
+    }
+
+    // Polyline point lists for a series
+    fn segments(&self, series: &GraphSeries, left_margin: u32, y_end: u32) -> Vec<String> {
+        self.runs(&series.points, left_margin, y_end)
+            .iter()
+            .map(|run| Self::to_points(run.iter()))
+            .collect()
+    }
+
+    // Polygon point lists for each series, filling the area between it and the series below
+    fn areas(&self, left_margin: u32, y_end: u32) -> Vec<Vec<String>> {
+        let mut below: Vec<Option<f32>> = Vec::new();
+
+        self.series
+            .iter()
+            .map(|series| {
+                let top: Vec<_> = series
+                    .points
+                    .iter()
+                    .enumerate()
+                    .map(|(i, point)| match below.get(i) {
+                        Some(below) => point.zip(*below).map(|(a, b)| a + b),
+                        None if below.is_empty() => *point,
+                        None => None,
+                    })
+                    .collect();
+                // Lines up with the top, so both split into the same runs
+                let bottom: Vec<_> = top
+                    .iter()
+                    .enumerate()
+                    .map(|(i, top)| top.map(|_| below.get(i).copied().flatten().unwrap_or(0.)))
+                    .collect();
+
+                let areas = self
+                    .runs(&top, left_margin, y_end)
+                    .iter()
+                    .zip(self.runs(&bottom, left_margin, y_end))
+                    .map(|(top, bottom)| Self::to_points(top.iter().chain(bottom.iter().rev())))
+                    .collect();
+
+                below = top;
+                areas
+            })
+            .collect()
+    }
+}
+
 impl Render for SvgGraph {
     fn render(&self) -> maud::Markup {
         let left_margin = LINE_SPACING * 5;
@@ -114,6 +216,8 @@ impl Render for SvgGraph {
 
         let axis = self.axis.get_labels().into_iter().enumerate();
 
+        let last_idx = self.last_idx();
+
         html! {
             div .graph-wrapper {
                 svg .graph viewBox={"0 0 " (total_width) " " (total_height)} {
@@ -126,31 +230,34 @@ impl Render for SvgGraph {
                         @let x = left_margin + i * LINE_SPACING;
                         line x1=(x) y1=(top_margin) x2=(x) y2=(y_end) {}
                     }
+                    @if self.stacked {
+                        @for (series, areas) in self.series.iter().zip(self.areas(left_margin, y_end)) {
+                            @for area in areas {
+                                polygon
+                                    points=(area)
+                                    stroke=(&series.color)
+                                    fill=(&series.color)
+                                    fill-opacity="0.5"
+                                {}
+                            }
+                        }
+                    }
                     @for series in &self.series {
-                        @let points = series.points.iter().map(|&(x, y)| {
-                            let y = self.axis.interpolate(y);
-                            (left_margin + (LINE_SPACING * x), y_end as f32 - y)
-                        });
-                        @let polyline_points = {
-                            use core::fmt::Write;
-
-                            let mut acc = String::new();
-                            for (x, y) in points.clone() {
-                                let _ = write!(acc, "{x},{y} ");
+                        @if !self.stacked {
+                            @for segment in self.segments(series, left_margin, y_end) {
+                                polyline
+                                    points=(segment)
+                                    stroke=(&series.color)
+                                    fill="none"
+                                {}
                             }
-                            acc
-                        };
-                        polyline
-                            points=(polyline_points)
-                            stroke=(&series.color)
-                            fill="none"
-                        {}
+                        }
                         rect width=(graph_width) height=(graph_height) x=(left_margin) y=(top_margin) fill="transparent"
                             nm-bind={"
                                 onmousemove: (e) => {
                                     x = e.offsetX;
                                     const { x: rectX, width } = this.getBoundingClientRect();
-                                    idx = Math.round(19-(e.clientX - rectX)/width*19);
+                                    idx = Math.round("(last_idx)"-(e.clientX - rectX)/width*"(last_idx)");
                                 },
                                 onmouseleave: () => (x = null, idx = 0)
                             "}
@@ -165,8 +272,11 @@ impl Render for SvgGraph {
                         use core::fmt::Write;
 
                         let mut acc = String::new();
-                        for &(_, y) in &series.points {
-                            let val = self.axis.format_val(y);
+                        for y in &series.points {
+                            let val = match y {
+                                Some(y) => self.axis.format_val(*y),
+                                None => "--".into(),
+                            };
 
                             let _ = write!(acc, "'{val}',");
                         }
@@ -1,44 +1,81 @@
 use maud::html;
 use pretty_bytes_typed::{pretty_bytes, pretty_bytes_binary};
-use serde::{Deserialize, Serialize};
+use serde::Deserialize;
 
-use crate::http::{query_array::QueryArray, request::ServerRequest, response::ServerResponse};
+use crate::{
+    history::HistoryRange,
+    http::{request::ServerRequest, response::ServerResponse},
+};
 
 use super::template::{send_req, template};
 
 mod fragments;
 mod graph;
 
-#[derive(Default, Serialize, Deserialize)]
+#[derive(Default, Deserialize)]
 #[serde(default)]
 pub struct SystemQuery {
-    cpu: QueryArray,
-    temp: QueryArray,
-    ram: QueryArray,
-    swap: QueryArray,
-    sent: QueryArray,
-    recv: QueryArray,
+    range: HistoryRange,
+    interface: String,
 }
 
 pub async fn page(req: ServerRequest) -> Result<ServerResponse, ServerResponse> {
     req.check_login()?;
 
-    let mut query: SystemQuery = req.extract_query()?;
+    let query: SystemQuery = req.extract_query()?;
 
     let cpu_data = send_req!(req, Cpu)?;
     let temp_data = send_req!(req, Temp)?;
     let mem_data = send_req!(req, Mem)?;
     let disk_data = send_req!(req, Disk)?;
     let net_data = send_req!(req, NetIO)?;
+    let disk_io_data = send_req!(req, DiskIO)?;
 
     let cpu_meters = fragments::cpu_meters(&cpu_data, &temp_data);
+    let temp_meters = fragments::temp_meters(&temp_data);
+    let pressure_meters = fragments::pressure_meters(&cpu_data.pressure);
     let mem_meters = fragments::mem_meters(&mem_data);
     let disk_meters = fragments::disk_meters(&disk_data);
 
-    let cpu_graph = fragments::cpu_graph(&cpu_data, &mut query.cpu);
-    let temp_graph = fragments::temp_graph(&temp_data, &mut query.temp);
-    let mem_graph = fragments::mem_graph(&mem_data, &mut query.ram, &mut query.swap);
-    let net_graph = fragments::net_graph(&net_data, &mut query.sent, &mut query.recv);
+    let backend_addr = req.extract_backends()?.current_backend.addr;
+
+    // Falls back to the first interface, so there's always a graph when there are any
+    let interface = net_data
+        .interfaces
+        .iter()
+        .find(|x| x.name == query.interface)
+        .or(net_data.interfaces.first())
+        .map(|x| x.name.clone())
+        .unwrap_or_default();
+
+    let (history, interface_history, disk_io_history) = {
+        let history = req.extract_history();
+        let store = history.get();
+        (
+            store.range(backend_addr, query.range),
+            store.interface_range(backend_addr, &interface, query.range),
+            disk_io_data
+                .devices
+                .iter()
+                .map(|x| {
+                    (
+                        &x.name,
+                        store.disk_range(backend_addr, &x.name, query.range),
+                    )
+                })
+                .collect::<Vec<_>>(),
+        )
+    };
+
+    let cpu_graph = fragments::cpu_graph(&history);
+    let temp_graph = fragments::temp_graph(&temp_data, &history);
+    let mem_graph = fragments::mem_graph(&history);
+    let mem_breakdown_graph = fragments::mem_breakdown_graph(&history);
+    let net_graph = fragments::net_graph(&history);
+    let interface_graph =
+        (!interface.is_empty()).then(|| fragments::interface_graph(&interface, &interface_history));
+    let net_interfaces = fragments::net_interfaces(&net_data, query.range, &interface);
+    let disk_io = fragments::disk_io(&disk_io_data);
 
     let ram_percent = if mem_data.ram.total == 0 {
         0.
@@ -55,6 +92,13 @@ pub async fn page(req: ServerRequest) -> Result<ServerResponse, ServerResponse>
         .map(|temp| format!("{temp:.1}ºC"))
         .unwrap_or_else(|| "--".to_string());
 
+    // Share of the last 10 seconds that some tasks were waiting on memory
+    let memory_pressure = cpu_data
+        .pressure
+        .memory
+        .map(|x| format!("{:.2}%", x.some.avg10))
+        .unwrap_or_else(|| "--".to_string());
+
     let peak_disk = disk_data.disks.iter().max_by(|a, b| {
         let a_ratio = if a.usage.total == 0 {
             0.
@@ -88,7 +132,7 @@ pub async fn page(req: ServerRequest) -> Result<ServerResponse, ServerResponse>
         pretty_bytes_binary(mem_data.ram.total, Some(1))
     );
     let net_usage = format!(
-        "↑ {}  ↓ {}",
+        "↑ {}/s  ↓ {}/s",
         pretty_bytes(net_data.sent, Some(1)),
         pretty_bytes(net_data.recv, Some(1))
     );
@@ -96,12 +140,8 @@ pub async fn page(req: ServerRequest) -> Result<ServerResponse, ServerResponse>
     let content = html! {
             div #system-swap
                 nm-bind="oninit: () => $debounce(() => $get('/system'), 2000)"
-                data-cpu=(query.cpu)
-                data-ram=(query.ram)
-                data-swap=(query.swap)
-                data-temp=(query.temp)
-                data-sent=(query.sent)
-                data-recv=(query.recv)
+                data-range=(query.range)
+                data-interface=(interface)
             {
                 section #system-overview {
                     h2 data-i18n="system_overview" { "System Overview" }
@@ -119,7 +159,7 @@ pub async fn page(req: ServerRequest) -> Result<ServerResponse, ServerResponse>
                         article .kpi-card {
                             p .kpi-label data-i18n="swap_label" { "Swap" }
                             p .kpi-value { (format!("{swap_percent:.1}%")) }
-                            p .kpi-sub data-i18n="pressure_monitor" { "Pressure monitor" }
+                            p .kpi-sub data-i18n-template="memory_pressure_value" data-value=(memory_pressure) { "Memory pressure: " (memory_pressure) }
                         }
                         article .kpi-card {
                             p .kpi-label data-i18n="peak_disk" { "Peak Disk" }
@@ -132,17 +172,38 @@ pub async fn page(req: ServerRequest) -> Result<ServerResponse, ServerResponse>
                 .system-layout {
                     .system-metrics {
                         (cpu_meters)
+                        @if let Some(temp_meters) = temp_meters {
+                            (temp_meters)
+                        }
+                        @if let Some(pressure_meters) = pressure_meters {
+                            (pressure_meters)
+                        }
                         (mem_meters)
                         (disk_meters)
                     }
                     .system-graphs {
+                        div .range-select {
+                            span data-i18n="time_range" { "Range" }
+                            @for range in HistoryRange::ALL {
+                                a .pager-btn href={"/system?range=" (range) "&interface=" (interface)} aria-current=[(range == query.range).then_some("true")] { (range) }
+                            }
+                        }
                         (cpu_graph)
                         @if let Some(temp_graph) = temp_graph {
                             (temp_graph)
                         }
                         (mem_graph)
+                        (mem_breakdown_graph)
                         (net_graph)
+                        @if let Some(interface_graph) = interface_graph {
+                            (interface_graph)
+                        }
+                        @for (name, history) in &disk_io_history {
+                            (fragments::disk_io_graph(name, history))
+                        }
                     }
+                    (net_interfaces)
+                    (disk_io)
                 }
             }
     };
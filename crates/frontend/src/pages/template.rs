@@ -1,9 +1,14 @@
+use std::net::IpAddr;
+
 use hyper::header;
 use maud::{DOCTYPE, Markup, PreEscaped, Render, html};
 
-use crate::http::{
-    request::{BackendData, ServerRequest},
-    response::ServerResponse,
+use crate::{
+    backend::group_by_tag,
+    http::{
+        request::{CurrentBackendData, ServerRequest},
+        response::ServerResponse,
+    },
 };
 
 macro_rules! send_req {
@@ -31,11 +36,22 @@ macro_rules! send_act {
 
 pub(crate) use send_act;
 
+fn backend_options(backends: &[CurrentBackendData], current: IpAddr) -> Markup {
+    html! {
+        @for backend in backends {
+            option value=(backend.addr) selected[backend.addr == current] {
+                (backend.nickname) " (" (backend.addr) ")"
+            }
+        }
+    }
+}
+
 fn header(req: &ServerRequest) -> Result<Markup, ServerResponse> {
-    let BackendData {
-        backend_list,
-        current_backend,
-    } = req.extract_backends()?;
+    let current_backend = req.extract_backends()?.current_backend;
+
+    let mut backends = req.extract_all_backends();
+    backends.sort_by(|a, b| a.nickname.cmp(&b.nickname).then(a.addr.cmp(&b.addr)));
+    let groups = group_by_tag(backends, &req.config().backend_group_tag, |x| &x.tags);
 
     Ok(html! {
         header {
@@ -55,10 +71,14 @@ fn header(req: &ServerRequest) -> Result<Markup, ServerResponse> {
                 select
                     onchange="document.cookie = `backend=${this.value}; MaxAge=999999999`; window.location.reload()"
                 {
-                    @for backend in backend_list {
-                        @let is_current_backend = backend.0 == current_backend.addr;
-                        option value=(backend.0) selected[is_current_backend] {
-                            (backend.1) " (" (backend.0) ")"
+                    // Only use groups if at least one backend has the tag
+                    @if let [(None, backends)] = groups.as_slice() {
+                        (backend_options(backends, current_backend.addr))
+                    } @else {
+                        @for (value, backends) in &groups {
+                            optgroup label=(value.as_deref().unwrap_or("--")) {
+                                (backend_options(backends, current_backend.addr))
+                            }
                         }
                     }
                 }
@@ -128,6 +148,15 @@ fn header(req: &ServerRequest) -> Result<Markup, ServerResponse> {
                         data-version=(update)
                     { "DietPi Update Available: " (update) }
                 }
+                @for alert in req.extract_alerts().get().firing() {
+                    li .alert-msg
+                        nm-bind="oninit: () => newMsg = true"
+                        data-i18n-template="alert_firing"
+                        data-rule=(alert.rule)
+                        data-target=(alert.target())
+                        data-value=(alert.value_display())
+                    { (alert.rule) ": " (alert.target()) " " (alert.value_display()) }
+                }
             }
         }
     })
@@ -148,6 +177,18 @@ fn nav(req: &ServerRequest) -> Markup {
                 (Icon::new("fa6-solid-gauge"))
                 span data-i18n="nav_system" { "System" }
             }
+            a href="/fleet" class=(if current_page == "fleet" { "active" } else { "" }) aria-current=(if current_page == "fleet" { "page" } else { "false" }) {
+                (Icon::new("fa6-solid-server"))
+                span data-i18n="nav_fleet" { "Fleet" }
+            }
+            a href="/commands" class=(if current_page == "commands" { "active" } else { "" }) aria-current=(if current_page == "commands" { "page" } else { "false" }) {
+                (Icon::new("fa6-solid-play"))
+                span data-i18n="nav_commands" { "Commands" }
+            }
+            a href="/jobs" class=(if current_page == "jobs" { "active" } else { "" }) aria-current=(if current_page == "jobs" { "page" } else { "false" }) {
+                (Icon::new("fa6-solid-clock"))
+                span data-i18n="nav_jobs" { "Jobs" }
+            }
             a href="/process" class=(if current_page == "process" { "active" } else { "" }) aria-current=(if current_page == "process" { "page" } else { "false" }) {
                 (Icon::new("fa6-solid-microchip"))
                 span data-i18n="nav_processes" { "Processes" }
@@ -172,6 +213,10 @@ fn nav(req: &ServerRequest) -> Markup {
                 (Icon::new("fa6-solid-folder"))
                 span data-i18n="nav_file_browser" { "File Browser" }
             }
+            a href="/audit" class=(if current_page == "audit" { "active" } else { "" }) aria-current=(if current_page == "audit" { "page" } else { "false" }) {
+                (Icon::new("fa6-solid-file-lines"))
+                span data-i18n="nav_audit_log" { "Audit Log" }
+            }
         }
     }
 }
@@ -25,8 +25,11 @@ pub async fn page(req: ServerRequest) -> Result<ServerResponse, ServerResponse>
 pub async fn stream(req: ServerRequest) -> Result<ServerResponse, ServerResponse> {
     req.check_login()?;
 
-    let backend = req.extract_backends()?.current_backend.handle;
-    let term_rx = backend.get_terminal_handle().await.unwrap();
+    let backend = req.extract_backends()?.current_backend;
+    let term_rx = backend.handle.get_terminal_handle().await.unwrap();
+
+    // Input isn't recorded, since it can include passwords typed at prompts
+    req.record_audit(&backend, "terminal", "session opened".into(), "ok".into());
 
     let term_stream = UnboundedReceiverStream::new(term_rx);
 
@@ -0,0 +1,119 @@
+use std::{fs, io, path::PathBuf};
+
+use anyhow::{Context, Result};
+use log::error;
+use ring::aead::{Aad, CHACHA20_POLY1305, LessSafeKey, NONCE_LEN, Nonce, UnboundKey};
+use tokio::sync::mpsc;
+
+// All state files are stored next to the executable, the same as the config file
+pub fn data_path(name: &str) -> Result<PathBuf> {
+    let mut path = std::env::current_exe().context("couldn't get path to executable")?;
+    path.set_file_name(name);
+    Ok(path)
+}
+
+fn make_key(key: &[u8; 32]) -> LessSafeKey {
+    // Length of buffer is guaranteed to be 32 bytes
+    let key = UnboundKey::new(&CHACHA20_POLY1305, key).unwrap();
+    LessSafeKey::new(key)
+}
+
+fn write_file(name: &str, data: Vec<u8>) -> Result<()> {
+    // Write to a temporary file first so that a crash can't leave a truncated file behind
+    let path = data_path(name)?;
+    let tmp_path = path.with_extension("tmp");
+    fs::write(&tmp_path, data).with_context(|| format!("failed to write {name}"))?;
+    fs::rename(tmp_path, path).with_context(|| format!("failed to replace {name}"))
+}
+
+fn read_file(name: &str) -> Result<Option<Vec<u8>>> {
+    match fs::read(data_path(name)?) {
+        Ok(data) => Ok(Some(data)),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(None),
+        Err(e) => Err(e).with_context(|| format!("failed to read {name}")),
+    }
+}
+
+pub fn write<T: bitcode::Encode>(name: &str, val: &T) -> Result<()> {
+    write_file(name, bitcode::encode(val))
+}
+
+pub fn read<T: bitcode::DecodeOwned>(name: &str) -> Result<Option<T>> {
+    let Some(data) = read_file(name)? else {
+        return Ok(None);
+    };
+
+    let val = bitcode::decode(&data).with_context(|| format!("failed to decode {name}"))?;
+
+    Ok(Some(val))
+}
+
+pub fn write_encrypted<T: bitcode::Encode>(name: &str, key: &[u8; 32], val: &T) -> Result<()> {
+    let mut data = bitcode::encode(val);
+
+    let nonce: [u8; NONCE_LEN] = rand::random();
+
+    make_key(key)
+        .seal_in_place_append_tag(Nonce::assume_unique_for_key(nonce), Aad::empty(), &mut data)
+        .map_err(|_| anyhow::anyhow!("encryption failed"))?;
+
+    data.extend(nonce);
+
+    write_file(name, data)
+}
+
+pub fn read_encrypted<T: bitcode::DecodeOwned>(name: &str, key: &[u8; 32]) -> Result<Option<T>> {
+    let Some(mut data) = read_file(name)? else {
+        return Ok(None);
+    };
+
+    if data.len() < NONCE_LEN {
+        anyhow::bail!("{name} is truncated");
+    }
+
+    let nonce = data.split_off(data.len() - NONCE_LEN);
+    let nonce = Nonce::assume_unique_for_key(nonce.try_into().unwrap());
+
+    let data = make_key(key)
+        .open_in_place(nonce, Aad::empty(), &mut data)
+        .map_err(|_| anyhow::anyhow!("failed to decrypt {name}, secret may have changed"))?;
+
+    let val = bitcode::decode(data).with_context(|| format!("failed to decode {name}"))?;
+
+    Ok(Some(val))
+}
+
+// Saves an encrypted state file from a background task, so nothing waits on the disk while holding a lock.
+// Saves that pile up while a write is in progress are collapsed into the newest one
+pub struct EncryptedWriter<T> {
+    tx: mpsc::UnboundedSender<T>,
+}
+
+impl<T: bitcode::Encode + Send + 'static> EncryptedWriter<T> {
+    pub fn new(name: &'static str, key: [u8; 32]) -> Self {
+        let (tx, mut rx) = mpsc::unbounded_channel::<T>();
+
+        tokio::spawn(async move {
+            while let Some(mut val) = rx.recv().await {
+                while let Ok(newer) = rx.try_recv() {
+                    val = newer;
+                }
+
+                let written =
+                    tokio::task::spawn_blocking(move || write_encrypted(name, &key, &val)).await;
+
+                match written {
+                    Ok(Ok(())) => {}
+                    Ok(Err(err)) => error!("Failed to save {name}: {err:#}"),
+                    Err(err) => error!("Failed to save {name}: {err}"),
+                }
+            }
+        });
+
+        Self { tx }
+    }
+
+    pub fn save(&self, val: T) {
+        let _ = self.tx.send(val);
+    }
+}
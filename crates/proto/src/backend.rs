@@ -1,3 +1,5 @@
+use std::collections::BTreeMap;
+
 use bitcode::{Decode, Encode};
 use serde::{Deserialize, Serialize};
 
@@ -11,6 +13,8 @@ pub enum BackendMessage {
 pub enum ActionBackendMessage {
     Handshake(Handshake),
     Terminal(Vec<u8>),
+    CommandOutput(CommandOutput),
+    CommandExit(CommandExit),
 }
 
 #[derive(Debug, Clone, Encode, Decode)]
@@ -20,7 +24,9 @@ pub enum ResponseBackendMessage {
     Mem(MemResponse),
     Disk(DiskResponse),
     NetIO(NetworkResponse),
+    DiskIO(DiskIOResponse),
     Processes(ProcessResponse),
+    ProcessDetail(Option<Box<ProcessDetail>>),
     Host(HostResponse),
     Software(SoftwareResponse),
     Command(CommandResponse),
@@ -28,71 +34,292 @@ pub enum ResponseBackendMessage {
     Directory(DirectoryResponse),
     Download(Vec<u8>),
     ReadConfig(String),
+    HistorySample(HistorySampleResponse),
 }
 
 #[derive(Debug, Clone, Encode, Decode)]
 pub struct Handshake {
     pub nickname: String,
     pub update: Option<String>,
+    pub tags: BTreeMap<String, String>,
     pub version: u32,
 }
 
+// Stdout and stderr are sent in the order they were written, so they can be shown together
 #[derive(Debug, Clone, Encode, Decode)]
+pub struct CommandOutput {
+    pub id: u32,
+    pub stderr: bool,
+    pub data: Vec<u8>,
+}
+
+#[derive(Debug, Clone, Encode, Decode)]
+pub struct CommandExit {
+    pub id: u32,
+    pub exit_code: Option<i32>,
+    pub signal: Option<i32>,
+    pub duration_ms: u64,
+    pub error: Option<String>,
+    // The command was turned away by the backend's command_policy, error says why
+    pub blocked: bool,
+}
+
+#[derive(Debug, Clone, Encode, Decode, Serialize)]
 pub struct CpuResponse {
     pub global_cpu: f32,
     pub cpus: Vec<f32>,
+    pub load: LoadAverage,
+    pub pressure: PressureInfo,
+    // In the same order as cpus, empty if cpufreq isn't available
+    pub frequencies: Vec<CpuFrequency>,
 }
 
-#[derive(Debug, Clone, Encode, Decode)]
+#[derive(Debug, Clone, Copy, Encode, Decode, Serialize)]
+pub struct LoadAverage {
+    pub one: f32,
+    pub five: f32,
+    pub fifteen: f32,
+}
+
+// Each is None if the kernel was built without PSI
+#[derive(Debug, Clone, Copy, Encode, Decode, Serialize)]
+pub struct PressureInfo {
+    pub cpu: Option<Pressure>,
+    pub memory: Option<Pressure>,
+    pub io: Option<Pressure>,
+}
+
+#[derive(Debug, Clone, Copy, Encode, Decode, Serialize)]
+pub struct Pressure {
+    // Some tasks were stalled
+    pub some: PressureAverages,
+    // All non-idle tasks were stalled at once
+    pub full: Option<PressureAverages>,
+}
+
+// Percentage of time stalled over the last 10, 60 and 300 seconds
+#[derive(Debug, Clone, Copy, Encode, Decode, Serialize)]
+pub struct PressureAverages {
+    pub avg10: f32,
+    pub avg60: f32,
+    pub avg300: f32,
+}
+
+// In MHz
+#[derive(Debug, Clone, Copy, Encode, Decode, Serialize)]
+pub struct CpuFrequency {
+    pub current: u32,
+    pub min: u32,
+    pub max: u32,
+}
+
+#[derive(Debug, Clone, Encode, Decode, Serialize)]
 pub struct TempResponse {
+    // Current temperature of the primary sensor
     pub temp: Option<f32>,
+    pub sensors: Vec<TempSensor>,
 }
 
-#[derive(Debug, Clone, Encode, Decode)]
+#[derive(Debug, Clone, Encode, Decode, Serialize)]
+pub struct TempSensor {
+    pub label: String,
+    pub temp: Option<f32>,
+    pub max: Option<f32>,
+    pub critical: Option<f32>,
+    pub primary: bool,
+}
+
+#[derive(Debug, Clone, Encode, Decode, Serialize)]
 pub struct MemResponse {
     pub ram: UsageData,
     pub swap: UsageData,
+    pub details: MemDetails,
 }
 
-#[derive(Debug, Clone, Encode, Decode)]
+// From /proc/meminfo and /proc/vmstat, in bytes
+#[derive(Debug, Clone, Encode, Decode, Serialize)]
+pub struct MemDetails {
+    pub free: u64,
+    pub available: u64,
+    pub buffers: u64,
+    // Page cache along with reclaimable kernel memory, like free(1) shows it
+    pub cached: u64,
+    pub shared: u64,
+    pub dirty: u64,
+    pub zram: Option<CompressedMemory>,
+    pub zswap: Option<CompressedMemory>,
+    // Bytes per second
+    pub swap_in: u64,
+    pub swap_out: u64,
+}
+
+#[derive(Debug, Clone, Copy, Encode, Decode, Serialize)]
+pub struct CompressedMemory {
+    // Size of the data before it was compressed
+    pub original: u64,
+    // Memory actually taken up
+    pub compressed: u64,
+}
+
+#[derive(Debug, Clone, Encode, Decode, Serialize)]
 pub struct UsageData {
     pub used: u64,
     pub total: u64,
 }
 
-#[derive(Debug, Clone, Encode, Decode)]
+#[derive(Debug, Clone, Encode, Decode, Serialize)]
 pub struct DiskResponse {
     pub disks: Vec<DiskInfo>,
 }
 
-#[derive(Debug, Clone, Encode, Decode)]
+#[derive(Debug, Clone, Encode, Decode, Serialize)]
 pub struct DiskInfo {
+    // As listed in /proc/mounts, e.g. "/dev/root"
     pub name: String,
     pub mnt_point: String,
     pub usage: UsageData,
+    pub fs_type: String,
+    // The name with symlinks resolved, e.g. "/dev/mmcblk0p2"
+    pub device: String,
+    pub read_only: bool,
+    pub removable: bool,
+    pub inodes: UsageData,
 }
 
-#[derive(Debug, Clone, Encode, Decode)]
+#[derive(Debug, Clone, Encode, Decode, Serialize)]
 pub struct NetworkResponse {
+    // Bytes per second
     pub sent: u64,
     pub recv: u64,
+    // Counted since boot, unlike the fields above
+    pub total_sent: u64,
+    pub total_recv: u64,
+    pub interfaces: Vec<NetworkInterface>,
 }
 
-#[derive(Debug, Clone, Encode, Decode)]
+#[derive(Debug, Clone, Encode, Decode, Serialize)]
+pub struct NetworkInterface {
+    pub name: String,
+    pub mac: String,
+    pub ipv4: Vec<String>,
+    pub ipv6: Vec<String>,
+    // The kernel's operstate, e.g. "up", "down" or "unknown"
+    pub state: String,
+    // Bytes per second
+    pub sent: u64,
+    pub recv: u64,
+    // Everything below is counted since boot
+    pub total_sent: u64,
+    pub total_recv: u64,
+    pub packets_sent: u64,
+    pub packets_recv: u64,
+    pub errors_sent: u64,
+    pub errors_recv: u64,
+    pub drops_sent: u64,
+    pub drops_recv: u64,
+}
+
+#[derive(Debug, Clone, Encode, Decode, Serialize)]
+pub struct DiskIOResponse {
+    pub devices: Vec<DiskIOInfo>,
+}
+
+#[derive(Debug, Clone, Encode, Decode, Serialize)]
+pub struct DiskIOInfo {
+    pub name: String,
+    // Per second
+    pub read_bytes: u64,
+    pub write_bytes: u64,
+    pub read_ops: f32,
+    pub write_ops: f32,
+    // Percentage of time the device had requests in flight
+    pub busy: f32,
+    // Counted since boot
+    pub total_read: u64,
+    pub total_written: u64,
+}
+
+// Everything the frontend keeps history of, with rates averaged since the previous sample
+#[derive(Debug, Clone, Encode, Decode, Serialize)]
+pub struct HistorySampleResponse {
+    pub cpu: f32,
+    pub temp: Option<f32>,
+    pub ram: UsageData,
+    pub swap: UsageData,
+    pub free: u64,
+    pub buffers: u64,
+    pub cached: u64,
+    // Per second, summed over the included interfaces
+    pub sent: u64,
+    pub recv: u64,
+    pub interfaces: Vec<DeviceRate>,
+    pub disks: Vec<DeviceRate>,
+}
+
+// For disks, sent is bytes written and recv is bytes read
+#[derive(Debug, Clone, Encode, Decode, Serialize)]
+pub struct DeviceRate {
+    pub name: String,
+    pub sent: u64,
+    pub recv: u64,
+}
+
+#[derive(Debug, Clone, Encode, Decode, Serialize)]
 pub struct ProcessResponse {
     pub processes: Vec<ProcessInfo>,
 }
 
-#[derive(Debug, Clone, Encode, Decode)]
+#[derive(Debug, Clone, Encode, Decode, Serialize)]
 pub struct ProcessInfo {
     pub pid: u32,
     pub name: String,
     pub cpu: f32,
     pub mem: u64,
     pub status: ProcessStatus,
+    // Arguments joined by spaces
+    pub cmd: String,
+    pub parent: Option<u32>,
+    // The user name, or the UID if it has none
+    pub user: Option<String>,
+    // kthreadd and everything it starts, which have no command line
+    pub kernel_thread: bool,
+}
+
+// Fields that can't be read, usually because the process belongs to another user, are None
+#[derive(Debug, Clone, Encode, Decode, Serialize)]
+pub struct ProcessDetail {
+    pub pid: u32,
+    pub name: String,
+    pub status: ProcessStatus,
+    pub cpu: f32,
+    pub mem: u64,
+    pub cmd: Vec<String>,
+    pub exe: Option<String>,
+    pub cwd: Option<String>,
+    pub uid: Option<u32>,
+    pub user: Option<String>,
+    pub gid: Option<u32>,
+    pub group: Option<String>,
+    pub parent: Option<u32>,
+    // Seconds since the Unix epoch
+    pub start_time: u64,
+    pub threads: Option<u32>,
+    pub nice: Option<i32>,
+    pub open_fds: Option<u32>,
+    pub cgroup: Option<String>,
+    pub sockets: Option<Vec<ListeningSocket>>,
+    // Only filled in if it was requested
+    pub environment: Option<Vec<String>>,
+}
+
+#[derive(Debug, Clone, Encode, Decode, Serialize)]
+pub struct ListeningSocket {
+    // "tcp", "tcp6", "udp" or "udp6"
+    pub protocol: String,
+    pub addr: String,
 }
 
-#[derive(Debug, Clone, Copy, Encode, Decode, PartialEq, Eq, PartialOrd, Ord)]
+#[derive(Debug, Clone, Copy, Encode, Decode, PartialEq, Eq, PartialOrd, Ord, Serialize)]
 pub enum ProcessStatus {
     Running,
     Paused,
@@ -100,7 +327,7 @@ pub enum ProcessStatus {
     Other,
 }
 
-#[derive(Debug, Clone, Encode, Decode)]
+#[derive(Debug, Clone, Encode, Decode, Serialize)]
 pub struct HostResponse {
     pub hostname: String,
     pub nic: String,
@@ -110,15 +337,43 @@ pub struct HostResponse {
     pub os_version: String,
     pub dp_version: String,
     pub num_pkgs: usize,
+    // None where the board can't be identified, like most x86 machines
+    pub board: Option<BoardInfo>,
 }
 
-#[derive(Debug, Clone, Encode, Decode)]
+#[derive(Debug, Clone, Encode, Decode, Serialize)]
+pub struct BoardInfo {
+    pub model: String,
+    pub serial: Option<String>,
+    pub revision: Option<String>,
+    // Only available on Raspberry Pis
+    pub throttling: Option<Throttling>,
+    // In bytes
+    pub arm_memory: Option<u64>,
+    pub gpu_memory: Option<u64>,
+}
+
+// Decoded from the Raspberry Pi firmware's get_throttled bits
+#[derive(Debug, Clone, Copy, Encode, Decode, Serialize)]
+pub struct Throttling {
+    pub under_voltage: bool,
+    pub freq_capped: bool,
+    pub throttled: bool,
+    pub soft_temp_limit: bool,
+    // Each of the above, at any point since boot
+    pub under_voltage_occurred: bool,
+    pub freq_capped_occurred: bool,
+    pub throttled_occurred: bool,
+    pub soft_temp_limit_occurred: bool,
+}
+
+#[derive(Debug, Clone, Encode, Decode, Serialize)]
 pub struct SoftwareResponse {
     pub installed: Vec<SoftwareInfo>,
     pub uninstalled: Vec<SoftwareInfo>,
 }
 
-#[derive(Debug, Clone, Encode, Decode)]
+#[derive(Debug, Clone, Encode, Decode, Serialize)]
 pub struct SoftwareInfo {
     pub id: u16,
     pub name: String,
@@ -127,17 +382,25 @@ pub struct SoftwareInfo {
     pub docs: String,
 }
 
-#[derive(Debug, Clone, Encode, Decode)]
+#[derive(Debug, Clone, Encode, Decode, Serialize)]
 pub struct CommandResponse {
     pub output: Vec<u8>,
+    pub stderr: Vec<u8>,
+    // Missing if the command couldn't be started, or was killed by a signal
+    pub exit_code: Option<i32>,
+    pub signal: Option<i32>,
+    pub duration_ms: u64,
+    // Set if the command was rejected by the backend's policy, or failed to start
+    pub error: Option<String>,
+    pub blocked: bool,
 }
 
-#[derive(Debug, Clone, Encode, Decode, Default)]
+#[derive(Debug, Clone, Encode, Decode, Default, Serialize)]
 pub struct ServiceResponse {
     pub services: Vec<ServiceInfo>,
 }
 
-#[derive(Debug, Clone, Encode, Decode)]
+#[derive(Debug, Clone, Encode, Decode, Serialize)]
 pub struct ServiceInfo {
     pub name: String,
     pub status: ServiceStatus,
@@ -145,7 +408,7 @@ pub struct ServiceInfo {
     pub err_log: String,
 }
 
-#[derive(Debug, Clone, Copy, Encode, Decode)]
+#[derive(Debug, Clone, Copy, Encode, Decode, Serialize)]
 pub enum ServiceStatus {
     Active,
     Inactive,
@@ -153,12 +416,12 @@ pub enum ServiceStatus {
     Unknown,
 }
 
-#[derive(Debug, Clone, Encode, Decode, Default)]
+#[derive(Debug, Clone, Encode, Decode, Default, Serialize)]
 pub struct DirectoryResponse {
     pub dir_list: Vec<DirectoryItemInfo>,
 }
 
-#[derive(Debug, Clone, Encode, Decode)]
+#[derive(Debug, Clone, Encode, Decode, Serialize)]
 pub struct DirectoryItemInfo {
     pub path: String,
     pub kind: FileKind,
@@ -14,7 +14,9 @@ pub enum RequestFrontendMessage {
     Mem,
     Disk,
     NetIO,
+    DiskIO,
     Processes,
+    ProcessDetail(ProcessDetailRequest),
     Host,
     Software,
     Command(CommandAction),
@@ -22,6 +24,7 @@ pub enum RequestFrontendMessage {
     Directory(String),
     Download(String),
     ReadConfig,
+    HistorySample,
 }
 
 #[derive(Debug, Encode, Decode)]
@@ -35,6 +38,7 @@ pub enum ActionFrontendMessage {
     DeleteFolder(String),
     Upload(UploadAction),
     ResizeTerminal(TerminalDimensions),
+    StartCommand(StartCommandAction),
 }
 
 #[derive(Debug, Encode, Decode, Deserialize)]
@@ -52,12 +56,26 @@ pub enum Signal {
     Kill,
 }
 
+#[derive(Debug, Encode, Decode)]
+pub struct ProcessDetailRequest {
+    pub pid: u32,
+    // The environment often holds secrets, so it's only read when asked for
+    pub environment: bool,
+}
+
 #[derive(Debug, Encode, Decode)]
 pub struct CommandAction {
     pub cmd: String,
     pub args: Vec<String>,
 }
 
+// Runs a command in the background, streaming its output back instead of waiting for it to finish
+#[derive(Debug, Encode, Decode)]
+pub struct StartCommandAction {
+    pub id: u32,
+    pub action: CommandAction,
+}
+
 #[derive(Debug, Encode, Decode)]
 pub struct RenameAction {
     pub from: String,
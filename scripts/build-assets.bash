@@ -15,6 +15,7 @@ css_assets=(
   "$asset_path/css/vars-clean.css"
   "$asset_path/css/global.css"
   "$asset_path/css/system.css"
+  "$asset_path/css/fleet.css"
   "$asset_path/css/process.css"
   "$asset_path/css/management.css"
   "$asset_path/css/software.css"